@@ -4,4 +4,4 @@ pub mod queue_entry;
 pub mod redis;
 pub mod transaction;
 pub use queue_entry::QueueEntry;
-pub use redis::SmartAccountRedisClient;
+pub use redis::{RelevanceStore, SmartAccountRedisClient};
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+// Associated token account of a wallet, as derived by the ATA program, thats
+// compatible with Diesel / Postgres
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct AssociatedTokenAccount {
+    pub network: i32,
+    pub signature: String,
+    pub slot: i64,
+    /// Position of the ATA program instruction, e.g. "2" or "2.1" for a CPI
+    pub instruction_path: String,
+    pub kind: AssociatedTokenAccountKind,
+    /// The derived associated token account address
+    pub address: String,
+    pub wallet: String,
+    pub mint: String,
+    pub token_program: String,
+}
+
+/// ATA program instruction that named the account
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum AssociatedTokenAccountKind {
+    #[default]
+    Create,
+    /// Creates the account unless it already exists
+    CreateIdempotent,
+    /// The wallet's account for the nested mint that `RecoverNested` moves
+    /// tokens into
+    RecoverNestedDestination,
+    /// The wallet's account that owned the nested account `RecoverNested`
+    /// closed
+    RecoverNestedOwner,
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+// Native SOL balance change thats compatible with Diesel / Postgres
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct LamportChange {
+    /// Relevant wallet (or nonce account) whose balance changed
+    pub address: String,
+    pub network: i32,
+    pub pre_lamports: String,
+    pub post_lamports: String,
+    /// Signed `post_lamports - pre_lamports`, fees included
+    pub lamport_delta: String,
+    pub last_updated_signature: String,
+    pub last_updated_slot: i64,
+}
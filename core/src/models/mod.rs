@@ -1,2 +1,6 @@
+pub mod associated_token_accounts;
+pub mod lamport_changes;
+pub mod sol_transfers;
 pub mod token_accounts;
+pub mod token_transfers;
 pub mod types;
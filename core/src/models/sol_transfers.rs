@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+// Native SOL transfer event thats compatible with Diesel / Postgres
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct SolTransfer {
+    pub network: i32,
+    pub signature: String,
+    pub slot: i64,
+    /// Position of the System Program instruction, e.g. "2" or "2.1" for a CPI
+    pub instruction_path: String,
+    pub kind: SolTransferKind,
+    pub source: String,
+    pub destination: String,
+    pub lamports: String,
+}
+
+/// System Program instruction that moved the lamports
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum SolTransferKind {
+    #[default]
+    Transfer,
+    TransferWithSeed,
+    /// Funding of a newly created account
+    CreateAccount,
+    WithdrawNonce,
+}
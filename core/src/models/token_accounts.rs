@@ -1,20 +1,40 @@
 use serde::{Deserialize, Serialize};
-use solana_pubkey::Pubkey;
-use solana_signature::Signature;
-
 
 // Token account type thats compatible with Diesel / Postgres
-#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
 pub struct TokenAccountChange {
     pub address: String,
     pub network: i32,
     pub owner: String,
     pub mint: String,
     pub amount: String,
+    /// Raw amount before the transaction, "0" for accounts created by it
+    pub pre_amount: String,
+    /// Signed `amount - pre_amount`
+    pub amount_delta: String,
     pub decimals: i32,
     pub ui_amount: String,
+    /// Program id of the token program that owns the account (SPL Token or Token-2022)
+    pub token_program: String,
+    /// Only meaningful when `delegated_amount` is set
     pub delegate: Option<String>,
-    pub is_frozen: bool,
+    /// `None` when the transaction didn't determine the delegation; the stored
+    /// delegate and delegated amount must then be left as they are
+    pub delegated_amount: Option<String>,
+    /// `None` when the transaction didn't freeze, thaw or create the account;
+    /// the stored flag must then be left as it is
+    pub is_frozen: Option<bool>,
     pub last_updated_signature: String,
     pub last_updated_slot: i64,
+    pub change_kind: TokenAccountChangeKind,
+}
+
+/// Lifecycle of the account within the transaction, so consumers know whether
+/// to insert, upsert or tombstone the row
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum TokenAccountChangeKind {
+    Created,
+    #[default]
+    Updated,
+    Closed,
 }
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+use super::types::TransferDirection;
+
+// Token transfer event thats compatible with Diesel / Postgres
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct TokenTransfer {
+    pub network: i32,
+    pub signature: String,
+    pub slot: i64,
+    /// Position of the transfer instruction, e.g. "2" or "2.1" for a CPI
+    pub instruction_path: String,
+    pub token_program: String,
+    pub source: String,
+    pub source_owner: Option<String>,
+    pub destination: String,
+    pub destination_owner: Option<String>,
+    /// Owner or delegate that signed the transfer
+    pub authority: String,
+    pub mint: String,
+    /// Raw amount debited from the source
+    pub amount: String,
+    pub decimals: i32,
+    /// Transfer fee withheld by Token-2022 `TransferCheckedWithFee`
+    pub fee: Option<String>,
+}
+
+/// A transfer as seen by one relevant owner
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct OwnerTransfer {
+    pub owner: String,
+    pub direction: TransferDirection,
+    /// Index into `QueueEntry::token_transfers`
+    pub transfer_index: usize,
+}
@@ -13,7 +13,7 @@ pub enum Environment {
     Production,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TransferDirection {
     Inflow,
     Outflow,
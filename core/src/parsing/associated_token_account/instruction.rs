@@ -0,0 +1,79 @@
+use solana_pubkey::Pubkey;
+use spl_associated_token_account_client::address::get_associated_token_address_with_program_id;
+
+use crate::models::associated_token_accounts::AssociatedTokenAccountKind;
+use crate::transaction::transaction::CompiledInstruction;
+
+pub fn associated_token_program_id() -> Pubkey {
+    spl_associated_token_account_client::program::ID
+}
+
+/// An associated token account named by an ATA program instruction. Indices
+/// point into the transaction's account keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedAssociatedAccount {
+    pub kind: AssociatedTokenAccountKind,
+    pub account_index: usize,
+    pub wallet_index: usize,
+    pub mint_index: usize,
+    pub token_program_index: usize,
+}
+
+impl DecodedAssociatedAccount {
+    /// Whether the account is the address the ATA program derives for the
+    /// wallet, mint and token program, i.e. the instruction could succeed
+    pub fn verify(&self, account_keys: &[Pubkey]) -> bool {
+        let key = |index: usize| account_keys.get(index);
+        let (Some(account), Some(wallet), Some(mint), Some(token_program)) = (
+            key(self.account_index),
+            key(self.wallet_index),
+            key(self.mint_index),
+            key(self.token_program_index),
+        ) else {
+            return false;
+        };
+        get_associated_token_address_with_program_id(wallet, mint, token_program) == *account
+    }
+}
+
+/// Decodes Create, CreateIdempotent and RecoverNested into the wallet
+/// accounts they name. Empty data is the original Create. Unknown
+/// instructions and short account lists yield nothing.
+pub fn decode_associated_accounts(ix: &CompiledInstruction) -> Vec<DecodedAssociatedAccount> {
+    let account = |position: usize| ix.accounts.get(position).map(|index| *index as usize);
+    let named = |kind, account_position, wallet_position, mint_position, program_position| {
+        Some(DecodedAssociatedAccount {
+            kind,
+            account_index: account(account_position)?,
+            wallet_index: account(wallet_position)?,
+            mint_index: account(mint_position)?,
+            token_program_index: account(program_position)?,
+        })
+    };
+
+    match ix.data.as_slice() {
+        // [funder, ata, wallet, mint, system program, token program]
+        [] | [0] => named(AssociatedTokenAccountKind::Create, 1, 2, 3, 5)
+            .into_iter()
+            .collect(),
+        [1] => named(AssociatedTokenAccountKind::CreateIdempotent, 1, 2, 3, 5)
+            .into_iter()
+            .collect(),
+        // [nested ata, nested mint, destination ata, owner ata, owner mint,
+        //  wallet, token program]
+        [2] => [
+            named(
+                AssociatedTokenAccountKind::RecoverNestedDestination,
+                2,
+                5,
+                1,
+                6,
+            ),
+            named(AssociatedTokenAccountKind::RecoverNestedOwner, 3, 5, 4, 6),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,291 @@
+use super::instruction::{
+    associated_token_program_id, decode_associated_accounts, DecodedAssociatedAccount,
+};
+use crate::models::associated_token_accounts::AssociatedTokenAccount;
+use crate::{
+    parsing::error::ParseError,
+    parsing::parser_trait::{ExtendQueueEntry, ParsingResult, PendingParse, ProgramParser},
+    redis::{RelevanceAnswers, RelevanceQuery},
+    transaction::transaction::{CompiledInstruction, InstructionPath},
+    QueueEntry,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use solana_pubkey::Pubkey;
+use std::collections::HashMap;
+
+/// Associated token accounts of relevant wallets. Their token account ->
+/// owner mappings are recorded in the owner index, so the token parser knows
+/// the owner of a new account before it holds any balance.
+pub struct AssociatedTokenAccountParser {}
+
+impl Default for AssociatedTokenAccountParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AssociatedTokenAccountParser {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AssociatedTokenAccountParsingResult {
+    pub associated_token_accounts: Vec<AssociatedTokenAccount>,
+}
+
+impl ExtendQueueEntry for AssociatedTokenAccountParsingResult {
+    fn extend_queue_entry(self, queue_entry: &mut QueueEntry) {
+        queue_entry
+            .associated_token_accounts
+            .extend(self.associated_token_accounts);
+    }
+}
+
+/// ATA program state carried from `prepare` to `finalize`
+pub struct PendingAssociatedTokenAccountParse {
+    account_keys: Vec<Pubkey>,
+    /// Accounts whose address matched the derivation
+    accounts: Vec<(InstructionPath, DecodedAssociatedAccount)>,
+}
+
+#[async_trait]
+impl ProgramParser for AssociatedTokenAccountParser {
+    fn name(&self) -> &'static str {
+        "associated_token_account"
+    }
+
+    fn program_ids(&self) -> Vec<Pubkey> {
+        vec![associated_token_program_id()]
+    }
+
+    fn prepare(
+        &self,
+        queue_entry: &QueueEntry,
+        _network: i32,
+        instructions: &HashMap<Pubkey, Vec<CompiledInstruction>>,
+        query: &mut RelevanceQuery,
+    ) -> Result<PendingParse, ParseError> {
+        if !instructions.contains_key(&associated_token_program_id()) {
+            return Ok(PendingParse::Irrelevant);
+        }
+
+        let account_keys = queue_entry.transaction.get_account_keys();
+        let mut accounts = Vec::new();
+        for (path, _, ix) in queue_entry
+            .transaction
+            .get_instructions_with_path_by_programs(&[associated_token_program_id()])
+        {
+            for account in decode_associated_accounts(&ix) {
+                // The program rejects anything else, so a mismatch means the
+                // instruction did not create or recover this account
+                if !account.verify(&account_keys) {
+                    tracing::debug!(
+                        "Skipping ATA instruction {} with a non-derived account",
+                        path
+                    );
+                    continue;
+                }
+                query.check_owner(account_keys[account.wallet_index]);
+                accounts.push((path, account));
+            }
+        }
+        if accounts.is_empty() {
+            return Ok(PendingParse::Irrelevant);
+        }
+
+        Ok(PendingParse::AssociatedTokenAccount(Box::new(
+            PendingAssociatedTokenAccountParse {
+                account_keys,
+                accounts,
+            },
+        )))
+    }
+
+    fn finalize(
+        &self,
+        queue_entry: &QueueEntry,
+        network: i32,
+        pending: PendingParse,
+        answers: &RelevanceAnswers,
+        learned_owners: &mut Vec<(Pubkey, Pubkey)>,
+    ) -> Result<Option<ParsingResult>, ParseError> {
+        let pending = match pending {
+            PendingParse::AssociatedTokenAccount(pending) => *pending,
+            PendingParse::Irrelevant => return Ok(None),
+            _ => return Err(anyhow!("ATA parser given another parser's state").into()),
+        };
+        let key = |index: usize| pending.account_keys[index];
+
+        let mut result = AssociatedTokenAccountParsingResult::default();
+        for (path, account) in pending.accounts {
+            let wallet = key(account.wallet_index);
+            if !answers.is_relevant_owner(&wallet) {
+                continue;
+            }
+            let address = key(account.account_index);
+            learned_owners.push((address, wallet));
+            result
+                .associated_token_accounts
+                .push(AssociatedTokenAccount {
+                    network,
+                    signature: queue_entry.signature.to_string(),
+                    slot: queue_entry.slot as i64,
+                    instruction_path: path.to_string(),
+                    kind: account.kind,
+                    address: address.to_string(),
+                    wallet: wallet.to_string(),
+                    mint: key(account.mint_index).to_string(),
+                    token_program: key(account.token_program_index).to_string(),
+                });
+        }
+
+        if result.associated_token_accounts.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(ParsingResult::AssociatedTokenAccount(result)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spl_associated_token_account_client::address::get_associated_token_address_with_program_id as ata;
+    use spl_associated_token_account_client::instruction as ata_instruction;
+
+    use super::*;
+    use crate::models::associated_token_accounts::AssociatedTokenAccountKind;
+    use crate::parsing::test_support::{parse, relevant_owners, TestTransaction, NETWORK};
+
+    fn ata_result(result: Option<ParsingResult>) -> AssociatedTokenAccountParsingResult {
+        match result {
+            Some(ParsingResult::AssociatedTokenAccount(result)) => result,
+            _ => panic!("expected an ATA program result"),
+        }
+    }
+
+    #[test]
+    fn accounts_of_relevant_wallets() {
+        let [funder, wallet, stranger, mint, nested_mint] = [(); 5].map(|_| Pubkey::new_unique());
+        let token_2022 = spl_token_2022::ID;
+
+        // The original Create sent no instruction data
+        let mut legacy_create =
+            ata_instruction::create_associated_token_account(&funder, &wallet, &mint, &token_2022);
+        legacy_create.data.clear();
+        // Points at an address the program doesn't derive, so it would fail
+        let mut mismatched = ata_instruction::create_associated_token_account(
+            &funder,
+            &wallet,
+            &nested_mint,
+            &token_2022,
+        );
+        mismatched.accounts[1].pubkey = Pubkey::new_unique();
+
+        let transaction = TestTransaction::new(
+            &funder,
+            &[
+                ata_instruction::create_associated_token_account(
+                    &funder,
+                    &wallet,
+                    &mint,
+                    &spl_token::ID,
+                ),
+                ata_instruction::create_associated_token_account_idempotent(
+                    &funder,
+                    &wallet,
+                    &nested_mint,
+                    &spl_token::ID,
+                ),
+                legacy_create,
+                mismatched,
+                ata_instruction::create_associated_token_account(
+                    &funder,
+                    &stranger,
+                    &mint,
+                    &spl_token::ID,
+                ),
+                ata_instruction::recover_nested(&wallet, &mint, &nested_mint, &spl_token::ID),
+            ],
+        )
+        .build();
+        let signature = transaction.signature.to_string();
+
+        let (result, learned_owners) = parse(
+            &AssociatedTokenAccountParser::new(),
+            &relevant_owners(&[wallet]),
+            transaction,
+        );
+        let result = ata_result(result);
+
+        let expected = [
+            ("0", AssociatedTokenAccountKind::Create, mint, spl_token::ID),
+            (
+                "1",
+                AssociatedTokenAccountKind::CreateIdempotent,
+                nested_mint,
+                spl_token::ID,
+            ),
+            ("2", AssociatedTokenAccountKind::Create, mint, token_2022),
+            (
+                "5",
+                AssociatedTokenAccountKind::RecoverNestedDestination,
+                nested_mint,
+                spl_token::ID,
+            ),
+            (
+                "5",
+                AssociatedTokenAccountKind::RecoverNestedOwner,
+                mint,
+                spl_token::ID,
+            ),
+        ]
+        .map(|(path, kind, mint, token_program)| AssociatedTokenAccount {
+            network: NETWORK,
+            signature: signature.clone(),
+            slot: 100,
+            instruction_path: path.to_string(),
+            kind,
+            address: ata(&wallet, &mint, &token_program).to_string(),
+            wallet: wallet.to_string(),
+            mint: mint.to_string(),
+            token_program: token_program.to_string(),
+        });
+        assert_eq!(result.associated_token_accounts, expected);
+
+        // Every recorded account is indexed under the wallet
+        assert_eq!(
+            learned_owners,
+            expected
+                .iter()
+                .map(|account| (account.address.parse().unwrap(), wallet))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn other_wallets_are_irrelevant() {
+        let [funder, wallet, mint] = [(); 3].map(|_| Pubkey::new_unique());
+        let transaction = TestTransaction::new(
+            &funder,
+            &[ata_instruction::create_associated_token_account(
+                &funder,
+                &wallet,
+                &mint,
+                &spl_token::ID,
+            )],
+        )
+        .build();
+
+        // The funder paying for it doesn't make the account relevant
+        let (result, learned_owners) = parse(
+            &AssociatedTokenAccountParser::new(),
+            &relevant_owners(&[funder]),
+            transaction,
+        );
+        assert!(result.is_none());
+        assert!(learned_owners.is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt;
+
+/// Why a parser couldn't produce a result for a transaction
+#[derive(Debug)]
+pub enum ParseError {
+    /// A relevance store call failed and the lookup policy gave up on it. The
+    /// transaction's relevance is unknown, so it must not be treated as
+    /// irrelevant.
+    RelevanceLookup {
+        operation: &'static str,
+        source: anyhow::Error,
+    },
+    Other(anyhow::Error),
+}
+
+impl ParseError {
+    /// Adapter for `map_err` on relevance store calls
+    pub fn relevance_lookup(operation: &'static str) -> impl FnOnce(anyhow::Error) -> Self {
+        move |source| ParseError::RelevanceLookup { operation, source }
+    }
+
+    pub fn is_relevance_lookup(&self) -> bool {
+        matches!(self, ParseError::RelevanceLookup { .. })
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::RelevanceLookup { operation, source } => {
+                write!(f, "relevance lookup {} failed: {:#}", operation, source)
+            }
+            ParseError::Other(source) => write!(f, "{:#}", source),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::RelevanceLookup { source, .. } | ParseError::Other(source) => {
+                Some(source.as_ref())
+            }
+        }
+    }
+}
+
+impl From<anyhow::Error> for ParseError {
+    fn from(source: anyhow::Error) -> Self {
+        ParseError::Other(source)
+    }
+}
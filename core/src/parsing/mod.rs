@@ -1,3 +1,10 @@
+pub mod associated_token_account;
+pub mod error;
 pub mod parser_trait;
+pub mod pipeline;
+pub mod registry;
 pub mod simple_parser;
+pub mod system_program;
+#[cfg(test)]
+mod test_support;
 pub mod token_program;
@@ -3,14 +3,32 @@ use std::collections::HashMap;
 use async_trait::async_trait;
 use solana_pubkey::Pubkey;
 
-use super::token_program::parser::TokenProgramParsingResult;
+use super::associated_token_account::parser::{
+    AssociatedTokenAccountParsingResult, PendingAssociatedTokenAccountParse,
+};
+use super::error::ParseError;
+use super::system_program::parser::{PendingSystemParse, SystemProgramParsingResult};
+use super::token_program::parser::{PendingTokenParse, TokenProgramParsingResult};
 
 use crate::{
-    redis::SmartAccountRedisClient, transaction::transaction::CompiledInstruction, QueueEntry,
+    redis::{RelevanceAnswers, RelevanceQuery, RelevanceStore},
+    transaction::transaction::CompiledInstruction,
+    QueueEntry,
 };
 
 pub enum ParsingResult {
     Token(TokenProgramParsingResult),
+    System(SystemProgramParsingResult),
+    AssociatedTokenAccount(AssociatedTokenAccountParsingResult),
+}
+
+/// A parser's state between `prepare` and `finalize`
+pub enum PendingParse {
+    /// Nothing for this parser in the transaction; finalizes to `None`
+    Irrelevant,
+    Token(Box<PendingTokenParse>),
+    System(Box<PendingSystemParse>),
+    AssociatedTokenAccount(Box<PendingAssociatedTokenAccountParse>),
 }
 
 pub trait ExtendQueueEntry {
@@ -21,17 +39,93 @@ impl ExtendQueueEntry for ParsingResult {
     fn extend_queue_entry(self, queue_entry: &mut QueueEntry) {
         match self {
             ParsingResult::Token(result) => result.extend_queue_entry(queue_entry),
+            ParsingResult::System(result) => result.extend_queue_entry(queue_entry),
+            ParsingResult::AssociatedTokenAccount(result) => result.extend_queue_entry(queue_entry),
         }
     }
 }
+
+/// Parsers run in two phases so that all parsers of a transaction share one
+/// relevance lookup: `prepare` declares what needs checking, the caller
+/// answers the combined query with `RelevanceStore::batch_check`, and
+/// `finalize` builds the result from the answers.
 #[async_trait]
 pub trait ProgramParser: Send + Sync {
-    /// Parse a transaction and update the QueueEntry with program-specific data
+    /// Short name used in logs and per-parser stats
+    fn name(&self) -> &'static str;
+
+    /// Programs whose instructions this parser handles. The registry only runs
+    /// a parser for transactions that invoke one of them.
+    fn program_ids(&self) -> Vec<Pubkey>;
+
+    /// Decode the parser's instructions and add the owners and accounts whose
+    /// relevance it needs to `query`
+    fn prepare(
+        &self,
+        queue_entry: &QueueEntry,
+        network: i32,
+        instructions: &HashMap<Pubkey, Vec<CompiledInstruction>>,
+        query: &mut RelevanceQuery,
+    ) -> Result<PendingParse, ParseError>;
+
+    /// Build the program-specific result from the answers to the query.
+    /// Token account -> owner mappings worth recording go to `learned_owners`.
+    fn finalize(
+        &self,
+        queue_entry: &QueueEntry,
+        network: i32,
+        pending: PendingParse,
+        answers: &RelevanceAnswers,
+        learned_owners: &mut Vec<(Pubkey, Pubkey)>,
+    ) -> Result<Option<ParsingResult>, ParseError>;
+
+    /// Parse a transaction with this parser alone: both phases around a
+    /// lookup of its own. Relevance store failures come back as
+    /// `ParseError::RelevanceLookup` rather than as "nothing relevant".
     async fn parse_transaction(
         &self,
         queue_entry: &QueueEntry,
         network: i32,
-        redis_client: &SmartAccountRedisClient,
+        relevance_store: &dyn RelevanceStore,
         instructions: &HashMap<Pubkey, Vec<CompiledInstruction>>,
-    ) -> Result<Option<ParsingResult>, anyhow::Error>;
+    ) -> Result<Option<ParsingResult>, ParseError> {
+        let mut query = RelevanceQuery::default();
+        let pending = self.prepare(queue_entry, network, instructions, &mut query)?;
+        let answers = lookup_relevance(relevance_store, network, &query).await?;
+
+        let mut learned_owners = Vec::new();
+        let result = self.finalize(queue_entry, network, pending, &answers, &mut learned_owners)?;
+        store_learned_owners(relevance_store, network, &learned_owners).await?;
+        Ok(result)
+    }
+}
+
+/// Answers `query` in one `batch_check`, skipping the call when there is
+/// nothing to ask
+pub async fn lookup_relevance(
+    relevance_store: &dyn RelevanceStore,
+    network: i32,
+    query: &RelevanceQuery,
+) -> Result<RelevanceAnswers, ParseError> {
+    if query.is_empty() {
+        return Ok(RelevanceAnswers::default());
+    }
+    relevance_store
+        .batch_check(network, query)
+        .await
+        .map_err(ParseError::relevance_lookup("batch_check"))
+}
+
+pub async fn store_learned_owners(
+    relevance_store: &dyn RelevanceStore,
+    network: i32,
+    learned_owners: &[(Pubkey, Pubkey)],
+) -> Result<(), ParseError> {
+    if learned_owners.is_empty() {
+        return Ok(());
+    }
+    relevance_store
+        .store_token_account_owners(network, learned_owners)
+        .await
+        .map_err(ParseError::relevance_lookup("store_token_account_owners"))
 }
@@ -0,0 +1,291 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use solana_pubkey::Pubkey;
+use tokio::task::JoinHandle;
+
+use super::registry::{ParserRegistry, PreparedTransaction, RegistryParse, RelevanceLookup};
+use crate::{redis::RelevanceStore, transaction::transaction::UnifiedTransaction};
+
+/// Parses transactions with up to `depth` of them prepared and looked up ahead
+/// of the one being committed. Results are committed strictly in input order.
+///
+/// A speculative lookup is redone at commit time when it may have missed an
+/// earlier write: an owner set change seen by a later lookup (its answers
+/// carry a newer owner set version), or owner index entries recorded by an
+/// earlier transaction that it looked up. Vault sets carry no version, so a
+/// lookup that raced a concurrent vault add or remove is kept as answered;
+/// with vaults changing while the pipeline runs, results can differ from
+/// parsing the transactions one at a time.
+pub struct ParsePipeline {
+    registry: Arc<ParserRegistry>,
+    relevance_store: Arc<dyn RelevanceStore>,
+    network: i32,
+    depth: usize,
+}
+
+/// Counts from a `ParsePipeline::run`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    pub transactions: u64,
+    /// Lookups redone because a newer owner set version was seen
+    pub owner_set_revalidations: u64,
+    /// Lookups redone because an earlier transaction recorded owners they
+    /// looked up
+    pub owner_index_revalidations: u64,
+}
+
+/// A transaction whose prepare and speculative lookup run in their own task
+struct InFlight {
+    handle: JoinHandle<(PreparedTransaction, RelevanceLookup)>,
+    /// Owner index writes committed before the lookup could have started
+    writes_before: u64,
+}
+
+impl ParsePipeline {
+    /// A depth of 1 parses one transaction at a time
+    pub fn new(
+        registry: Arc<ParserRegistry>,
+        relevance_store: Arc<dyn RelevanceStore>,
+        network: i32,
+        depth: usize,
+    ) -> Self {
+        Self {
+            registry,
+            relevance_store,
+            network,
+            depth: depth.max(1),
+        }
+    }
+
+    /// Parses every transaction, handing each result to `commit` in input
+    /// order along with its index
+    pub async fn run(
+        &self,
+        transactions: impl IntoIterator<Item = UnifiedTransaction>,
+        mut commit: impl FnMut(usize, RegistryParse),
+    ) -> PipelineStats {
+        let mut stats = PipelineStats::default();
+        // Highest owner set version any lookup has answered at
+        let latest_version = Arc::new(AtomicU64::new(0));
+        // Accounts recorded in the owner index by each committed write, by
+        // write number, kept while an in-flight lookup may predate them
+        let mut writes: VecDeque<(u64, HashSet<Pubkey>)> = VecDeque::new();
+        let mut writes_committed = 0;
+
+        let mut transactions = transactions.into_iter();
+        let mut in_flight: VecDeque<InFlight> = VecDeque::with_capacity(self.depth);
+        let mut index = 0;
+        loop {
+            while in_flight.len() < self.depth {
+                let Some(transaction) = transactions.next() else {
+                    break;
+                };
+                in_flight.push_back(InFlight {
+                    handle: self.spawn(transaction, latest_version.clone()),
+                    writes_before: writes_committed,
+                });
+            }
+            let Some(next) = in_flight.pop_front() else {
+                break;
+            };
+
+            let (prepared, mut lookup) = match next.handle.await {
+                Ok(result) => result,
+                Err(e) => std::panic::resume_unwind(e.into_panic()),
+            };
+
+            // Failed lookups and transactions with nothing to look up have
+            // nothing to go stale
+            let answered_version = match (&lookup.answers, lookup.elapsed) {
+                (Ok(answers), Some(_)) => Some(answers.owner_set_version),
+                _ => None,
+            };
+            let stale_owner_set = answered_version
+                .is_some_and(|version| version < latest_version.load(Ordering::Acquire));
+            let stale_owner_index = answered_version.is_some()
+                && !stale_owner_set
+                && writes
+                    .iter()
+                    .filter(|(write, _)| *write >= next.writes_before)
+                    .any(|(_, accounts)| {
+                        prepared
+                            .query()
+                            .indexed_accounts()
+                            .iter()
+                            .any(|account| accounts.contains(account))
+                    });
+            if stale_owner_set || stale_owner_index {
+                if stale_owner_set {
+                    stats.owner_set_revalidations += 1;
+                } else {
+                    stats.owner_index_revalidations += 1;
+                }
+                lookup = prepared.lookup(self.relevance_store.as_ref()).await;
+                record_version(&latest_version, &lookup);
+            }
+
+            let parse = self
+                .registry
+                .complete(self.relevance_store.as_ref(), prepared, lookup)
+                .await;
+            if !parse.learned_owners.is_empty() {
+                writes.push_back((
+                    writes_committed,
+                    parse
+                        .learned_owners
+                        .iter()
+                        .map(|(account, _)| *account)
+                        .collect(),
+                ));
+                writes_committed += 1;
+            }
+            // Lookups still in flight started after everything before their
+            // own mark
+            let oldest_mark = in_flight
+                .front()
+                .map_or(writes_committed, |next| next.writes_before);
+            while writes
+                .front()
+                .is_some_and(|(write, _)| *write < oldest_mark)
+            {
+                writes.pop_front();
+            }
+
+            stats.transactions += 1;
+            commit(index, parse);
+            index += 1;
+        }
+        stats
+    }
+
+    fn spawn(
+        &self,
+        transaction: UnifiedTransaction,
+        latest_version: Arc<AtomicU64>,
+    ) -> JoinHandle<(PreparedTransaction, RelevanceLookup)> {
+        let registry = self.registry.clone();
+        let relevance_store = self.relevance_store.clone();
+        let network = self.network;
+        tokio::spawn(async move {
+            let prepared = registry.prepare(network, transaction);
+            let lookup = prepared.lookup(relevance_store.as_ref()).await;
+            record_version(&latest_version, &lookup);
+            (prepared, lookup)
+        })
+    }
+}
+
+fn record_version(latest_version: &AtomicU64, lookup: &RelevanceLookup) {
+    if let (Ok(answers), Some(_)) = (&lookup.answers, lookup.elapsed) {
+        latest_version.fetch_max(answers.owner_set_version, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::message::{Message, VersionedMessage};
+    use solana_sdk::transaction::VersionedTransaction;
+    use solana_signature::Signature;
+
+    use super::*;
+    use crate::queue_entry::QueueEntry;
+    use crate::redis::LocalRedisEmulator;
+    use crate::transaction::transaction::{Transaction, TransactionStatusMeta};
+
+    const NETWORK: i32 = 1;
+
+    fn transaction(id: u8, payer: &Pubkey, instructions: &[Instruction]) -> UnifiedTransaction {
+        let message = Message::new(instructions, Some(payer));
+        let versioned = VersionedTransaction {
+            signatures: vec![
+                Signature::from([id; 64]);
+                message.header.num_required_signatures as usize
+            ],
+            message: VersionedMessage::Legacy(message),
+        };
+        UnifiedTransaction {
+            signature: versioned.signatures[0],
+            slot: id as u64,
+            block_time: Some(1_700_000_000),
+            transaction: Transaction::from(&versioned),
+            meta: TransactionStatusMeta::default(),
+            index: None,
+        }
+    }
+
+    /// A relevant owner's account is initialized, then spent from without
+    /// token balances, so only the owner index recorded by the first
+    /// transaction attributes the later ones
+    fn fixture(owner: &Pubkey) -> Vec<UnifiedTransaction> {
+        let payer = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let transfer = |amount| {
+            spl_token::instruction::transfer(
+                &spl_token::ID,
+                &account,
+                &destination,
+                owner,
+                &[],
+                amount,
+            )
+            .unwrap()
+        };
+
+        vec![
+            transaction(
+                1,
+                &payer,
+                &[spl_token::instruction::initialize_account3(
+                    &spl_token::ID,
+                    &account,
+                    &mint,
+                    owner,
+                )
+                .unwrap()],
+            ),
+            transaction(2, &payer, &[transfer(10)]),
+            transaction(3, &payer, &[transfer(20)]),
+        ]
+    }
+
+    async fn run(
+        depth: usize,
+        owner: Pubkey,
+        transactions: Vec<UnifiedTransaction>,
+    ) -> (Vec<Option<QueueEntry>>, PipelineStats) {
+        let emulator = LocalRedisEmulator::new();
+        emulator.add_owners(NETWORK, &[owner]).await.unwrap();
+        let pipeline = ParsePipeline::new(
+            Arc::new(ParserRegistry::with_default_parsers()),
+            Arc::new(emulator),
+            NETWORK,
+            depth,
+        );
+
+        let mut entries = Vec::new();
+        let stats = pipeline
+            .run(transactions, |_, parse| {
+                entries.push(parse.result.expect("parse failed"));
+            })
+            .await;
+        (entries, stats)
+    }
+
+    #[tokio::test]
+    async fn depth_does_not_change_committed_entries() {
+        let owner = Pubkey::new_unique();
+        let transactions = fixture(&owner);
+        let (sequential, sequential_stats) = run(1, owner, transactions.clone()).await;
+        let (pipelined, pipelined_stats) = run(3, owner, transactions).await;
+
+        assert!(sequential.iter().all(Option::is_some));
+        assert_eq!(sequential_stats.owner_index_revalidations, 0);
+        assert_eq!(pipelined_stats.owner_index_revalidations, 2);
+        assert_eq!(pipelined, sequential);
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use solana_pubkey::Pubkey;
+
+use super::associated_token_account::parser::AssociatedTokenAccountParser;
+use super::error::ParseError;
+use super::parser_trait::{
+    lookup_relevance, store_learned_owners, ExtendQueueEntry, PendingParse, ProgramParser,
+};
+use super::system_program::parser::SystemProgramParser;
+use super::token_program::parser::TokenProgramParser;
+use crate::{
+    redis::{RelevanceAnswers, RelevanceQuery, RelevanceStore},
+    transaction::transaction::UnifiedTransaction,
+    QueueEntry,
+};
+
+/// Runs every registered parser that handles a program invoked by the
+/// transaction, in registration order, merging their results into one
+/// `QueueEntry`. All parsers are prepared first so the transaction costs a
+/// single relevance lookup however many parsers run.
+pub struct ParserRegistry {
+    parsers: Vec<Box<dyn ProgramParser>>,
+    /// Program id -> indices into `parsers`
+    by_program_id: HashMap<Pubkey, Vec<usize>>,
+}
+
+/// What a single parser did for a transaction
+#[derive(Debug)]
+pub enum ParserOutcome {
+    /// Returned a result that was merged into the queue entry
+    Relevant,
+    /// Ran and found nothing relevant
+    Irrelevant,
+    /// Failed; the error is logged and the first one is returned from the
+    /// registry
+    Failed { relevance_lookup: bool },
+}
+
+#[derive(Debug)]
+pub struct ParserReport {
+    pub parser: &'static str,
+    /// Time in `prepare` and `finalize`; the shared lookup is reported
+    /// separately
+    pub elapsed: Duration,
+    pub outcome: ParserOutcome,
+}
+
+/// Result of running the registry over one transaction
+#[derive(Debug)]
+pub struct RegistryParse {
+    /// `Ok(None)` when no parser found anything relevant. A failing parser
+    /// fails the transaction, even if other parsers found relevant changes,
+    /// since its part of the entry is unknown.
+    pub result: Result<Option<QueueEntry>, ParseError>,
+    /// One report per parser that ran, in run order
+    pub reports: Vec<ParserReport>,
+    /// Time spent in the shared relevance lookup, if one was needed
+    pub lookup_elapsed: Option<Duration>,
+    /// Token account -> owner mappings recorded for this transaction
+    pub learned_owners: Vec<(Pubkey, Pubkey)>,
+}
+
+impl Default for ParserRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParserRegistry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Self {
+            parsers: Vec::new(),
+            by_program_id: HashMap::new(),
+        }
+    }
+
+    /// The parsers we deploy, in production order
+    pub fn with_default_parsers() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(TokenProgramParser::new()));
+        registry.register(Box::new(SystemProgramParser::new()));
+        registry.register(Box::new(AssociatedTokenAccountParser::new()));
+        registry
+    }
+
+    /// Parsers run in the order they are registered
+    pub fn register(&mut self, parser: Box<dyn ProgramParser>) -> &mut Self {
+        let index = self.parsers.len();
+        for program_id in parser.program_ids() {
+            let indices = self.by_program_id.entry(program_id).or_default();
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+        self.parsers.push(parser);
+        self
+    }
+
+    pub fn parsers(&self) -> impl Iterator<Item = &dyn ProgramParser> {
+        self.parsers.iter().map(|parser| parser.as_ref())
+    }
+
+    /// Parsers handling `program_id`, in run order
+    pub fn parsers_for(&self, program_id: &Pubkey) -> impl Iterator<Item = &dyn ProgramParser> {
+        self.by_program_id
+            .get(program_id)
+            .into_iter()
+            .flatten()
+            .map(|index| self.parsers[*index].as_ref())
+    }
+
+    /// Prepare, look up and complete one transaction
+    pub async fn parse_transaction(
+        &self,
+        relevance_store: &dyn RelevanceStore,
+        network: i32,
+        transaction: UnifiedTransaction,
+    ) -> RegistryParse {
+        let prepared = self.prepare(network, transaction);
+        let lookup = prepared.lookup(relevance_store).await;
+        self.complete(relevance_store, prepared, lookup).await
+    }
+
+    /// Phase one: every applicable parser declares what it needs looked up
+    pub fn prepare(&self, network: i32, transaction: UnifiedTransaction) -> PreparedTransaction {
+        let instructions = transaction.get_instructions_by_program_id();
+
+        // Registration order, each parser once however many of its programs
+        // are invoked
+        let mut applicable: Vec<usize> = instructions
+            .keys()
+            .filter_map(|program_id| self.by_program_id.get(program_id))
+            .flatten()
+            .copied()
+            .collect();
+        applicable.sort_unstable();
+        applicable.dedup();
+
+        let queue_entry = QueueEntry::new(network, transaction.signature, transaction);
+        let mut query = RelevanceQuery::default();
+        let mut pending = Vec::with_capacity(applicable.len());
+        for index in applicable {
+            let parser = self.parsers[index].as_ref();
+            let start = Instant::now();
+            let parse = parser.prepare(&queue_entry, network, &instructions, &mut query);
+            let elapsed = start.elapsed();
+            if let Err(e) = &parse {
+                tracing::error!("{} parser error: {}", parser.name(), e);
+            }
+            pending.push((index, parse, elapsed));
+        }
+
+        PreparedTransaction {
+            queue_entry,
+            query,
+            pending,
+        }
+    }
+
+    /// Phase two, in registration order so results merge the same way every
+    /// time, then records the owners the parsers learned
+    pub async fn complete(
+        &self,
+        relevance_store: &dyn RelevanceStore,
+        prepared: PreparedTransaction,
+        lookup: RelevanceLookup,
+    ) -> RegistryParse {
+        let PreparedTransaction {
+            mut queue_entry,
+            pending,
+            ..
+        } = prepared;
+        let network = queue_entry.network;
+        if let Err(e) = &lookup.answers {
+            tracing::error!("Relevance lookup error: {}", e);
+        }
+
+        let mut reports = Vec::with_capacity(pending.len());
+        let mut learned_owners = Vec::new();
+        let mut first_error = None;
+        let mut relevant = false;
+        for (index, pending, mut elapsed) in pending {
+            let parser = self.parsers[index].as_ref();
+            let parse_result = match (pending, &lookup.answers) {
+                (Ok(PendingParse::Irrelevant), _) => Ok(None),
+                (Ok(pending), Ok(answers)) => {
+                    let start = Instant::now();
+                    let result = parser.finalize(
+                        &queue_entry,
+                        network,
+                        pending,
+                        answers,
+                        &mut learned_owners,
+                    );
+                    elapsed += start.elapsed();
+                    if let Err(e) = &result {
+                        tracing::error!("{} parser error: {}", parser.name(), e);
+                    }
+                    result
+                }
+                (Err(e), _) => Err(e),
+                // The lookup error itself is returned below
+                (Ok(_), Err(_)) => {
+                    reports.push(ParserReport {
+                        parser: parser.name(),
+                        elapsed,
+                        outcome: ParserOutcome::Failed {
+                            relevance_lookup: true,
+                        },
+                    });
+                    continue;
+                }
+            };
+
+            let outcome = match parse_result {
+                Ok(Some(parsing_result)) => {
+                    parsing_result.extend_queue_entry(&mut queue_entry);
+                    relevant = true;
+                    ParserOutcome::Relevant
+                }
+                Ok(None) => ParserOutcome::Irrelevant,
+                Err(e) => {
+                    let outcome = ParserOutcome::Failed {
+                        relevance_lookup: e.is_relevance_lookup(),
+                    };
+                    first_error.get_or_insert(e);
+                    outcome
+                }
+            };
+            reports.push(ParserReport {
+                parser: parser.name(),
+                elapsed,
+                outcome,
+            });
+        }
+
+        if let Err(e) = lookup.answers {
+            first_error.get_or_insert(e);
+        }
+        if first_error.is_none() {
+            if let Err(e) = store_learned_owners(relevance_store, network, &learned_owners).await {
+                tracing::error!("Relevance store error: {}", e);
+                first_error = Some(e);
+            }
+        }
+        if first_error.is_some() {
+            learned_owners.clear();
+        }
+
+        let result = match first_error {
+            Some(e) => Err(e),
+            None if relevant && queue_entry.contains_changes() => Ok(Some(queue_entry)),
+            None => Ok(None),
+        };
+        RegistryParse {
+            result,
+            reports,
+            lookup_elapsed: lookup.elapsed,
+            learned_owners,
+        }
+    }
+}
+
+/// A transaction between `ParserRegistry::prepare` and `complete`
+pub struct PreparedTransaction {
+    queue_entry: QueueEntry,
+    query: RelevanceQuery,
+    /// Parser index, its pending state and the time spent preparing it
+    pending: Vec<(usize, Result<PendingParse, ParseError>, Duration)>,
+}
+
+/// Answers to a prepared transaction's query
+pub struct RelevanceLookup {
+    pub answers: Result<RelevanceAnswers, ParseError>,
+    /// `None` when there was nothing to look up
+    pub elapsed: Option<Duration>,
+}
+
+impl PreparedTransaction {
+    pub fn query(&self) -> &RelevanceQuery {
+        &self.query
+    }
+
+    /// The single relevance round trip shared by all parsers
+    pub async fn lookup(&self, relevance_store: &dyn RelevanceStore) -> RelevanceLookup {
+        let start = Instant::now();
+        let answers =
+            lookup_relevance(relevance_store, self.queue_entry.network, &self.query).await;
+        RelevanceLookup {
+            answers,
+            elapsed: (!self.query.is_empty()).then(|| start.elapsed()),
+        }
+    }
+}
+
+/// Per-parser totals accumulated from `ParserReport`s
+#[derive(Debug, Clone, Default)]
+pub struct ParserStats {
+    pub runs: u64,
+    pub relevant: u64,
+    pub errors: u64,
+    pub relevance_lookup_errors: u64,
+    pub total_time: Duration,
+}
+
+impl ParserStats {
+    /// Folds reports into per-parser stats keyed by parser name
+    pub fn record(stats: &mut HashMap<&'static str, ParserStats>, reports: &[ParserReport]) {
+        for report in reports {
+            let entry = stats.entry(report.parser).or_default();
+            entry.runs += 1;
+            entry.total_time += report.elapsed;
+            match report.outcome {
+                ParserOutcome::Relevant => entry.relevant += 1,
+                ParserOutcome::Irrelevant => {}
+                ParserOutcome::Failed { relevance_lookup } => {
+                    entry.errors += 1;
+                    if relevance_lookup {
+                        entry.relevance_lookup_errors += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn average_time(&self) -> Duration {
+        if self.runs == 0 {
+            Duration::ZERO
+        } else {
+            self.total_time.div_f64(self.runs as f64)
+        }
+    }
+}
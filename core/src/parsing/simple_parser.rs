@@ -1,18 +1,19 @@
 use crate::{
     parsing::{
+        error::ParseError,
         parser_trait::{ExtendQueueEntry, ProgramParser},
         token_program::parser::TokenProgramParser,
     },
     transaction::transaction::UnifiedTransaction,
-    QueueEntry, SmartAccountRedisClient,
+    QueueEntry, RelevanceStore,
 };
 
 /// Simplified parser that only runs the token program parser
 pub async fn parse_transaction_simple(
-    redis_client: &SmartAccountRedisClient,
+    relevance_store: &dyn RelevanceStore,
     network: i32,
     transaction: UnifiedTransaction,
-) -> Result<Option<QueueEntry>, anyhow::Error> {
+) -> Result<Option<QueueEntry>, ParseError> {
     // Gets all instructions by program id
     let instructions = transaction.get_instructions_by_program_id();
 
@@ -26,7 +27,7 @@ pub async fn parse_transaction_simple(
         .parse_transaction(
             &state_and_transaction_changes,
             network,
-            redis_client,
+            relevance_store,
             &instructions,
         )
         .await;
@@ -0,0 +1,55 @@
+// Deprecated in newer SDKs in favour of solana-system-interface, which would be
+// a second dependency for the same types
+#![allow(deprecated)]
+
+use solana_pubkey::Pubkey;
+use solana_sdk::program_utils::limited_deserialize;
+use solana_sdk::system_instruction::SystemInstruction;
+
+use crate::models::sol_transfers::SolTransferKind;
+use crate::transaction::transaction::CompiledInstruction;
+
+pub fn system_program_id() -> Pubkey {
+    solana_sdk::system_program::ID
+}
+
+/// Lamports moved by a System Program instruction. Indices point into the
+/// transaction's account keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedSolTransfer {
+    pub kind: SolTransferKind,
+    pub source_index: usize,
+    pub destination_index: usize,
+    pub lamports: u64,
+}
+
+/// Decodes the System Program instructions that move lamports between
+/// accounts: Transfer, TransferWithSeed, CreateAccount and
+/// WithdrawNonceAccount. Everything else, and malformed data, is `None`.
+pub fn decode_sol_transfer(ix: &CompiledInstruction) -> Option<DecodedSolTransfer> {
+    // Bounded by the packet size, like the runtime
+    let instruction: SystemInstruction = limited_deserialize(&ix.data).ok()?;
+    // Positions of the source and destination within the instruction accounts
+    let (kind, source, destination, lamports) = match instruction {
+        SystemInstruction::Transfer { lamports } => (SolTransferKind::Transfer, 0, 1, lamports),
+        // [from, base, to]
+        SystemInstruction::TransferWithSeed { lamports, .. } => {
+            (SolTransferKind::TransferWithSeed, 0, 2, lamports)
+        }
+        SystemInstruction::CreateAccount { lamports, .. } => {
+            (SolTransferKind::CreateAccount, 0, 1, lamports)
+        }
+        // [nonce, to, recent blockhashes sysvar, rent sysvar, authority]
+        SystemInstruction::WithdrawNonceAccount(lamports) => {
+            (SolTransferKind::WithdrawNonce, 0, 1, lamports)
+        }
+        _ => return None,
+    };
+
+    Some(DecodedSolTransfer {
+        kind,
+        source_index: *ix.accounts.get(source)? as usize,
+        destination_index: *ix.accounts.get(destination)? as usize,
+        lamports,
+    })
+}
@@ -0,0 +1,309 @@
+use super::instruction::{decode_sol_transfer, system_program_id, DecodedSolTransfer};
+use crate::models::lamport_changes::LamportChange;
+use crate::models::sol_transfers::SolTransfer;
+use crate::{
+    parsing::error::ParseError,
+    parsing::parser_trait::{ExtendQueueEntry, ParsingResult, PendingParse, ProgramParser},
+    redis::{RelevanceAnswers, RelevanceQuery},
+    transaction::transaction::{CompiledInstruction, InstructionPath},
+    QueueEntry,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use solana_pubkey::Pubkey;
+use std::collections::HashMap;
+
+/// Native SOL movements of relevant wallets. A system account's address is
+/// its owner, so both sides of a transfer are checked against the owner set.
+pub struct SystemProgramParser {}
+
+impl Default for SystemProgramParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemProgramParser {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SystemProgramParsingResult {
+    pub lamport_changes: HashMap<Pubkey, LamportChange>,
+    pub sol_transfers: Vec<SolTransfer>,
+}
+
+impl ExtendQueueEntry for SystemProgramParsingResult {
+    fn extend_queue_entry(self, queue_entry: &mut QueueEntry) {
+        queue_entry.lamport_changes.extend(self.lamport_changes);
+        queue_entry.sol_transfers.extend(self.sol_transfers);
+    }
+}
+
+/// System Program state carried from `prepare` to `finalize`
+pub struct PendingSystemParse {
+    account_keys: Vec<Pubkey>,
+    transfers: Vec<(InstructionPath, DecodedSolTransfer)>,
+}
+
+#[async_trait]
+impl ProgramParser for SystemProgramParser {
+    fn name(&self) -> &'static str {
+        "system"
+    }
+
+    fn program_ids(&self) -> Vec<Pubkey> {
+        vec![system_program_id()]
+    }
+
+    fn prepare(
+        &self,
+        queue_entry: &QueueEntry,
+        _network: i32,
+        instructions: &HashMap<Pubkey, Vec<CompiledInstruction>>,
+        query: &mut RelevanceQuery,
+    ) -> Result<PendingParse, ParseError> {
+        if !instructions.contains_key(&system_program_id()) {
+            return Ok(PendingParse::Irrelevant);
+        }
+
+        let account_keys = queue_entry.transaction.get_account_keys();
+        let transfers: Vec<(InstructionPath, DecodedSolTransfer)> = queue_entry
+            .transaction
+            .get_instructions_with_path_by_programs(&[system_program_id()])
+            .into_iter()
+            .filter_map(|(path, _, ix)| decode_sol_transfer(&ix).map(|transfer| (path, transfer)))
+            .collect();
+        if transfers.is_empty() {
+            return Ok(PendingParse::Irrelevant);
+        }
+
+        for (_, transfer) in &transfers {
+            for index in [transfer.source_index, transfer.destination_index] {
+                if let Some(&address) = account_keys.get(index) {
+                    query.check_owner(address);
+                }
+            }
+        }
+
+        Ok(PendingParse::System(Box::new(PendingSystemParse {
+            account_keys,
+            transfers,
+        })))
+    }
+
+    fn finalize(
+        &self,
+        queue_entry: &QueueEntry,
+        network: i32,
+        pending: PendingParse,
+        answers: &RelevanceAnswers,
+        _learned_owners: &mut Vec<(Pubkey, Pubkey)>,
+    ) -> Result<Option<ParsingResult>, ParseError> {
+        let pending = match pending {
+            PendingParse::System(pending) => *pending,
+            PendingParse::Irrelevant => return Ok(None),
+            _ => return Err(anyhow!("system parser given another parser's state").into()),
+        };
+        let meta = &queue_entry.transaction.meta;
+        let key = |index: usize| pending.account_keys.get(index).copied();
+
+        let mut result = SystemProgramParsingResult::default();
+        for (path, transfer) in pending.transfers {
+            let (Some(source), Some(destination)) =
+                (key(transfer.source_index), key(transfer.destination_index))
+            else {
+                continue;
+            };
+            let relevant_sides: Vec<(usize, Pubkey)> = [
+                (transfer.source_index, source),
+                (transfer.destination_index, destination),
+            ]
+            .into_iter()
+            .filter(|(_, address)| answers.is_relevant_owner(address))
+            .collect();
+            if relevant_sides.is_empty() {
+                continue;
+            }
+
+            // Balances are indexed like the account keys; the delta covers
+            // everything in the transaction, fees included
+            for (index, address) in relevant_sides {
+                let (Some(&pre_lamports), Some(&post_lamports)) =
+                    (meta.pre_balances.get(index), meta.post_balances.get(index))
+                else {
+                    continue;
+                };
+                result
+                    .lamport_changes
+                    .entry(address)
+                    .or_insert_with(|| LamportChange {
+                        address: address.to_string(),
+                        network,
+                        pre_lamports: pre_lamports.to_string(),
+                        post_lamports: post_lamports.to_string(),
+                        lamport_delta: (post_lamports as i128 - pre_lamports as i128).to_string(),
+                        last_updated_signature: queue_entry.signature.to_string(),
+                        last_updated_slot: queue_entry.slot as i64,
+                    });
+            }
+
+            result.sol_transfers.push(SolTransfer {
+                network,
+                signature: queue_entry.signature.to_string(),
+                slot: queue_entry.slot as i64,
+                instruction_path: path.to_string(),
+                kind: transfer.kind,
+                source: source.to_string(),
+                destination: destination.to_string(),
+                lamports: transfer.lamports.to_string(),
+            });
+        }
+
+        if result.sol_transfers.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(ParsingResult::System(result)))
+    }
+}
+
+#[cfg(test)]
+#[allow(deprecated)]
+mod tests {
+    use solana_sdk::system_instruction;
+
+    use super::*;
+    use crate::models::sol_transfers::SolTransferKind;
+    use crate::parsing::test_support::{parse, relevant_owners, TestTransaction, NETWORK};
+
+    fn system_result(result: Option<ParsingResult>) -> SystemProgramParsingResult {
+        match result {
+            Some(ParsingResult::System(result)) => result,
+            _ => panic!("expected a system program result"),
+        }
+    }
+
+    fn transfers(
+        result: &SystemProgramParsingResult,
+    ) -> Vec<(&str, SolTransferKind, Pubkey, Pubkey, u64)> {
+        result
+            .sol_transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.instruction_path.as_str(),
+                    transfer.kind,
+                    transfer.source.parse().unwrap(),
+                    transfer.destination.parse().unwrap(),
+                    transfer.lamports.parse().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn transfers_of_relevant_wallets() {
+        let [sender, receiver, base, created, nonce, stranger, other] =
+            [(); 7].map(|_| Pubkey::new_unique());
+        let seeded = Pubkey::create_with_seed(&base, "seed", &system_program_id()).unwrap();
+        let transaction = TestTransaction::new(
+            &sender,
+            &[
+                system_instruction::transfer(&sender, &receiver, 100),
+                // [from, base, to]: the base only signs
+                system_instruction::transfer_with_seed(
+                    &seeded,
+                    &base,
+                    "seed".to_string(),
+                    &system_program_id(),
+                    &receiver,
+                    30,
+                ),
+                system_instruction::create_account(&sender, &created, 50, 0, &spl_token::ID),
+                system_instruction::withdraw_nonce_account(&nonce, &sender, &receiver, 20),
+                // Neither side is relevant
+                system_instruction::transfer(&stranger, &other, 1),
+            ],
+        )
+        .lamports(&sender, 10_000, 4_850)
+        .lamports(&receiver, 0, 150)
+        .lamports(&seeded, 30, 0)
+        .fee(5_000)
+        .build();
+        let signature = transaction.signature.to_string();
+
+        let (result, learned_owners) = parse(
+            &SystemProgramParser::new(),
+            &relevant_owners(&[sender, receiver]),
+            transaction,
+        );
+        let result = system_result(result);
+        assert!(learned_owners.is_empty());
+
+        assert_eq!(
+            transfers(&result),
+            [
+                ("0", SolTransferKind::Transfer, sender, receiver, 100),
+                ("1", SolTransferKind::TransferWithSeed, seeded, receiver, 30),
+                ("2", SolTransferKind::CreateAccount, sender, created, 50),
+                ("3", SolTransferKind::WithdrawNonce, nonce, receiver, 20),
+            ]
+        );
+        assert!(result
+            .sol_transfers
+            .iter()
+            .all(|transfer| transfer.network == NETWORK
+                && transfer.signature == signature
+                && transfer.slot == 100));
+
+        // Only relevant wallets get a lamport change, taken from the balances
+        // so the fee paid by the sender is included
+        let change = |key: &Pubkey| {
+            let change = &result.lamport_changes[key];
+            (
+                change.pre_lamports.as_str(),
+                change.post_lamports.as_str(),
+                change.lamport_delta.as_str(),
+            )
+        };
+        assert_eq!(result.lamport_changes.len(), 2);
+        assert_eq!(change(&sender), ("10000", "4850", "-5150"));
+        assert_eq!(change(&receiver), ("0", "150", "150"));
+        assert_eq!(
+            result.lamport_changes[&sender].last_updated_signature,
+            signature
+        );
+    }
+
+    #[test]
+    fn either_side_makes_a_transfer_relevant() {
+        let [sender, receiver] = [(); 2].map(|_| Pubkey::new_unique());
+        let transaction = || {
+            TestTransaction::new(
+                &sender,
+                &[system_instruction::transfer(&sender, &receiver, 100)],
+            )
+            .lamports(&sender, 1_000, 895)
+            .lamports(&receiver, 0, 100)
+            .fee(5)
+            .build()
+        };
+        let parser = SystemProgramParser::new();
+
+        for relevant in [sender, receiver] {
+            let (result, _) = parse(&parser, &relevant_owners(&[relevant]), transaction());
+            let result = system_result(result);
+            assert_eq!(result.sol_transfers.len(), 1);
+            assert_eq!(
+                result.lamport_changes.keys().collect::<Vec<_>>(),
+                [&relevant]
+            );
+        }
+
+        let (result, _) = parse(&parser, &relevant_owners(&[]), transaction());
+        assert!(result.is_none());
+    }
+}
@@ -0,0 +1,143 @@
+//! Transactions and relevance data for parser tests
+
+use solana_pubkey::Pubkey;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::{Message, VersionedMessage};
+use solana_sdk::transaction::VersionedTransaction;
+use solana_signature::Signature;
+
+use super::parser_trait::{ParsingResult, ProgramParser};
+use crate::redis::{RelevanceQuery, RelevantAccountCache};
+use crate::transaction::transaction::{
+    TokenAmount, TokenBalance, Transaction, TransactionStatusMeta, UnifiedTransaction,
+};
+use crate::QueueEntry;
+
+pub const NETWORK: i32 = 1;
+
+/// Decimals of every token balance built here
+pub const DECIMALS: u32 = 6;
+
+/// A legacy transaction with balances filled in by account key
+pub struct TestTransaction {
+    transaction: UnifiedTransaction,
+}
+
+impl TestTransaction {
+    /// Paid by `payer`, with every lamport balance zero and no token balances
+    pub fn new(payer: &Pubkey, instructions: &[Instruction]) -> Self {
+        let message = Message::new(instructions, Some(payer));
+        let signature = Signature::new_unique();
+        let versioned = VersionedTransaction {
+            signatures: vec![signature; message.header.num_required_signatures as usize],
+            message: VersionedMessage::Legacy(message),
+        };
+        let transaction = Transaction::from(&versioned);
+        let keys = transaction.message.as_ref().unwrap().account_keys.len();
+        Self {
+            transaction: UnifiedTransaction {
+                signature,
+                slot: 100,
+                block_time: Some(1_700_000_000),
+                transaction,
+                meta: TransactionStatusMeta {
+                    pre_balances: vec![0; keys],
+                    post_balances: vec![0; keys],
+                    ..TransactionStatusMeta::default()
+                },
+                index: None,
+            },
+        }
+    }
+
+    pub fn index_of(&self, key: &Pubkey) -> usize {
+        self.transaction
+            .transaction
+            .message
+            .as_ref()
+            .unwrap()
+            .account_keys
+            .iter()
+            .position(|account| account == key)
+            .unwrap_or_else(|| panic!("{} is not in the transaction", key))
+    }
+
+    pub fn lamports(mut self, account: &Pubkey, pre: u64, post: u64) -> Self {
+        let index = self.index_of(account);
+        self.transaction.meta.pre_balances[index] = pre;
+        self.transaction.meta.post_balances[index] = post;
+        self
+    }
+
+    pub fn fee(mut self, fee: u64) -> Self {
+        self.transaction.meta.fee = fee;
+        self
+    }
+
+    /// Token balances of `account` before and after, `None` where it didn't
+    /// exist
+    pub fn token_balance(
+        mut self,
+        account: &Pubkey,
+        mint: &Pubkey,
+        owner: &Pubkey,
+        pre: Option<u64>,
+        post: Option<u64>,
+    ) -> Self {
+        let balance = |amount: u64| TokenBalance {
+            account_index: self.index_of(account) as u32,
+            mint: *mint,
+            ui_token_amount: Some(TokenAmount {
+                ui_amount: Some(amount as f64 / 10f64.powi(DECIMALS as i32)),
+                decimals: DECIMALS,
+                amount,
+                ui_amount_string: amount.to_string(),
+            }),
+            owner: *owner,
+            program_id: spl_token::ID,
+        };
+        let (pre, post) = (pre.map(balance), post.map(balance));
+        let meta = &mut self.transaction.meta;
+        meta.pre_token_balances.extend(pre);
+        meta.post_token_balances.extend(post);
+        self
+    }
+
+    pub fn build(self) -> UnifiedTransaction {
+        self.transaction
+    }
+}
+
+/// Runs both phases of `parser` against `relevance`, returning its result and
+/// the owners it learned
+pub fn parse(
+    parser: &dyn ProgramParser,
+    relevance: &RelevantAccountCache,
+    transaction: UnifiedTransaction,
+) -> (Option<ParsingResult>, Vec<(Pubkey, Pubkey)>) {
+    let instructions = transaction.get_instructions_by_program_id();
+    let queue_entry = QueueEntry::new(NETWORK, transaction.signature, transaction);
+    let mut query = RelevanceQuery::default();
+    let pending = parser
+        .prepare(&queue_entry, NETWORK, &instructions, &mut query)
+        .unwrap();
+    let answers = relevance.answer(NETWORK, &query);
+    let mut learned_owners = Vec::new();
+    let result = parser
+        .finalize(
+            &queue_entry,
+            NETWORK,
+            pending,
+            &answers,
+            &mut learned_owners,
+        )
+        .unwrap();
+    (result, learned_owners)
+}
+
+/// Relevance data with `owners` registered on `NETWORK`
+pub fn relevant_owners(owners: &[Pubkey]) -> RelevantAccountCache {
+    let mut relevance = RelevantAccountCache::default();
+    relevance.add_owners(NETWORK, owners);
+    relevance
+}
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use solana_pubkey::Pubkey;
+use spl_token_2022::extension::transfer_fee::instruction::TransferFeeInstruction;
+use spl_token_2022::instruction::{AuthorityType, TokenInstruction};
+
+use super::instruction::{DecodedTokenInstruction, TokenProgram};
+use crate::transaction::transaction::CompiledInstruction;
+
+/// A token account's delegate and the amount it may still spend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Delegation {
+    pub delegate: Option<Pubkey>,
+    pub amount: u64,
+}
+
+/// Delegate and freeze state of a token account after the instructions of a
+/// transaction that touched it. `None` means no instruction in the
+/// transaction determined that part of the state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenAccountAuthorityState {
+    pub delegation: Option<Delegation>,
+    pub is_frozen: Option<bool>,
+}
+
+/// Replays Approve/Revoke/Freeze/Thaw (and the delegate spends and owner
+/// changes that implicitly update a delegation) in instruction order.
+///
+/// Transaction metadata doesn't carry the delegate or frozen flag, so state is
+/// only known once an instruction in this transaction sets it. A spend on an
+/// account whose delegation is unknown leaves it unknown.
+#[derive(Debug, Default)]
+pub struct AuthorityStateTracker {
+    accounts: HashMap<Pubkey, TokenAccountAuthorityState>,
+}
+
+impl AuthorityStateTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, account: &Pubkey) -> Option<&TokenAccountAuthorityState> {
+        self.accounts.get(account)
+    }
+
+    /// Applies a single decoded instruction. Must be called in execution order,
+    /// inner instructions included.
+    pub fn apply(
+        &mut self,
+        token_program: TokenProgram,
+        ix: &CompiledInstruction,
+        decoded: &DecodedTokenInstruction,
+        account_keys: &[Pubkey],
+    ) {
+        let key = |position: usize| {
+            ix.accounts
+                .get(position)
+                .and_then(|&index| account_keys.get(index as usize))
+                .copied()
+        };
+        let Some(account) = key(0) else {
+            return;
+        };
+
+        match &decoded.instruction {
+            TokenInstruction::Approve { amount } => {
+                self.approve(account, key(1), *amount);
+            }
+            TokenInstruction::ApproveChecked { amount, .. } => {
+                self.approve(account, key(2), *amount);
+            }
+            TokenInstruction::Revoke => self.revoke(account),
+            // Changing the owner clears any delegation on the account
+            TokenInstruction::SetAuthority {
+                authority_type: AuthorityType::AccountOwner,
+                ..
+            } => self.revoke(account),
+            TokenInstruction::FreezeAccount => {
+                self.accounts.entry(account).or_default().is_frozen = Some(true);
+            }
+            TokenInstruction::ThawAccount => {
+                self.accounts.entry(account).or_default().is_frozen = Some(false);
+            }
+            TokenInstruction::CloseAccount => {
+                self.accounts.remove(&account);
+            }
+            TokenInstruction::InitializeAccount
+            | TokenInstruction::InitializeAccount2 { .. }
+            | TokenInstruction::InitializeAccount3 { .. } => {
+                // Token-2022 mints with a default account state can create
+                // accounts frozen
+                let is_frozen = match token_program {
+                    TokenProgram::Token => Some(false),
+                    TokenProgram::Token2022 => None,
+                };
+                self.accounts.insert(
+                    account,
+                    TokenAccountAuthorityState {
+                        delegation: Some(Delegation::default()),
+                        is_frozen,
+                    },
+                );
+            }
+            #[allow(deprecated)]
+            TokenInstruction::Transfer { amount } => self.spend(account, key(2), *amount),
+            TokenInstruction::TransferChecked { amount, .. } => {
+                self.spend(account, key(3), *amount)
+            }
+            TokenInstruction::Burn { amount } | TokenInstruction::BurnChecked { amount, .. } => {
+                self.spend(account, key(2), *amount)
+            }
+            TokenInstruction::TransferFeeExtension => {
+                if let Some(TransferFeeInstruction::TransferCheckedWithFee { amount, .. }) =
+                    decoded.transfer_fee_instruction
+                {
+                    self.spend(account, key(3), amount);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn approve(&mut self, account: Pubkey, delegate: Option<Pubkey>, amount: u64) {
+        self.accounts.entry(account).or_default().delegation =
+            Some(Delegation { delegate, amount });
+    }
+
+    fn revoke(&mut self, account: Pubkey) {
+        self.accounts.entry(account).or_default().delegation = Some(Delegation::default());
+    }
+
+    /// A spend signed by the delegate draws down the delegated amount, and the
+    /// program drops the delegate once it reaches zero.
+    fn spend(&mut self, account: Pubkey, authority: Option<Pubkey>, amount: u64) {
+        let Some(delegation) = self
+            .accounts
+            .get_mut(&account)
+            .and_then(|state| state.delegation.as_mut())
+        else {
+            return;
+        };
+        if delegation.delegate.is_none() || delegation.delegate != authority {
+            return;
+        }
+        delegation.amount = delegation.amount.saturating_sub(amount);
+        if delegation.amount == 0 {
+            delegation.delegate = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::Instruction;
+    use spl_token::instruction::{self as token, AuthorityType as TokenAuthorityType};
+
+    use super::*;
+    use crate::parsing::token_program::instruction::decode_token_instruction;
+
+    /// Replays `instructions` in order, compiling their accounts against one
+    /// shared key list like a transaction would
+    fn replay(token_program: TokenProgram, instructions: &[Instruction]) -> AuthorityStateTracker {
+        let mut account_keys: Vec<Pubkey> = Vec::new();
+        let mut tracker = AuthorityStateTracker::new();
+        for ix in instructions {
+            let accounts = ix
+                .accounts
+                .iter()
+                .map(|meta| {
+                    let index = account_keys
+                        .iter()
+                        .position(|key| *key == meta.pubkey)
+                        .unwrap_or_else(|| {
+                            account_keys.push(meta.pubkey);
+                            account_keys.len() - 1
+                        });
+                    index as u8
+                })
+                .collect();
+            let compiled = CompiledInstruction {
+                program_id_index: 0,
+                accounts,
+                data: ix.data.clone(),
+            };
+            let decoded = decode_token_instruction(&compiled).unwrap();
+            tracker.apply(token_program, &compiled, &decoded, &account_keys);
+        }
+        tracker
+    }
+
+    struct Keys {
+        account: Pubkey,
+        mint: Pubkey,
+        owner: Pubkey,
+        delegate: Pubkey,
+        destination: Pubkey,
+    }
+
+    fn keys() -> Keys {
+        Keys {
+            account: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            delegate: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+        }
+    }
+
+    fn state(
+        delegation: Option<Delegation>,
+        is_frozen: Option<bool>,
+    ) -> TokenAccountAuthorityState {
+        TokenAccountAuthorityState {
+            delegation,
+            is_frozen,
+        }
+    }
+
+    fn delegated(delegate: Pubkey, amount: u64) -> Option<Delegation> {
+        Some(Delegation {
+            delegate: Some(delegate),
+            amount,
+        })
+    }
+
+    #[test]
+    fn approve_and_revoke() {
+        let k = keys();
+        let id = &spl_token::ID;
+        let approve = token::approve(id, &k.account, &k.delegate, &k.owner, &[], 100).unwrap();
+        let approve_checked =
+            token::approve_checked(id, &k.account, &k.mint, &k.delegate, &k.owner, &[], 70, 6)
+                .unwrap();
+        let revoke = token::revoke(id, &k.account, &k.owner, &[]).unwrap();
+
+        let tracker = replay(TokenProgram::Token, std::slice::from_ref(&approve));
+        assert_eq!(
+            tracker.get(&k.account),
+            Some(&state(delegated(k.delegate, 100), None))
+        );
+
+        let tracker = replay(TokenProgram::Token, &[approve_checked]);
+        assert_eq!(
+            tracker.get(&k.account),
+            Some(&state(delegated(k.delegate, 70), None))
+        );
+
+        let tracker = replay(TokenProgram::Token, &[approve, revoke]);
+        assert_eq!(
+            tracker.get(&k.account),
+            Some(&state(Some(Delegation::default()), None))
+        );
+    }
+
+    #[test]
+    fn owner_change_clears_delegation() {
+        let k = keys();
+        let id = &spl_token::ID;
+        let tracker = replay(
+            TokenProgram::Token,
+            &[
+                token::approve(id, &k.account, &k.delegate, &k.owner, &[], 100).unwrap(),
+                token::set_authority(
+                    id,
+                    &k.account,
+                    Some(&k.destination),
+                    TokenAuthorityType::AccountOwner,
+                    &k.owner,
+                    &[],
+                )
+                .unwrap(),
+            ],
+        );
+        assert_eq!(
+            tracker.get(&k.account),
+            Some(&state(Some(Delegation::default()), None))
+        );
+    }
+
+    #[test]
+    fn freeze_and_thaw() {
+        let k = keys();
+        let id = &spl_token::ID;
+        let freeze = token::freeze_account(id, &k.account, &k.mint, &k.owner, &[]).unwrap();
+        let thaw = token::thaw_account(id, &k.account, &k.mint, &k.owner, &[]).unwrap();
+
+        let tracker = replay(TokenProgram::Token, std::slice::from_ref(&freeze));
+        assert_eq!(tracker.get(&k.account), Some(&state(None, Some(true))));
+
+        let tracker = replay(TokenProgram::Token, &[freeze, thaw]);
+        assert_eq!(tracker.get(&k.account), Some(&state(None, Some(false))));
+    }
+
+    #[test]
+    fn close_forgets_state() {
+        let k = keys();
+        let id = &spl_token::ID;
+        let tracker = replay(
+            TokenProgram::Token,
+            &[
+                token::approve(id, &k.account, &k.delegate, &k.owner, &[], 100).unwrap(),
+                token::close_account(id, &k.account, &k.destination, &k.owner, &[]).unwrap(),
+            ],
+        );
+        assert_eq!(tracker.get(&k.account), None);
+    }
+
+    #[test]
+    fn initialize_account() {
+        let k = keys();
+        let tracker = replay(
+            TokenProgram::Token,
+            &[token::initialize_account3(&spl_token::ID, &k.account, &k.mint, &k.owner).unwrap()],
+        );
+        assert_eq!(
+            tracker.get(&k.account),
+            Some(&state(Some(Delegation::default()), Some(false)))
+        );
+
+        // A default account state extension may create the account frozen
+        let tracker = replay(
+            TokenProgram::Token2022,
+            &[spl_token_2022::instruction::initialize_account3(
+                &spl_token_2022::ID,
+                &k.account,
+                &k.mint,
+                &k.owner,
+            )
+            .unwrap()],
+        );
+        assert_eq!(
+            tracker.get(&k.account),
+            Some(&state(Some(Delegation::default()), None))
+        );
+    }
+
+    #[test]
+    fn delegate_spends() {
+        let k = keys();
+        let id = &spl_token::ID;
+        let approve = token::approve(id, &k.account, &k.delegate, &k.owner, &[], 100).unwrap();
+        let delegate_transfer =
+            |amount| token::transfer(id, &k.account, &k.destination, &k.delegate, &[], amount);
+
+        // Spends signed by the owner leave the delegation alone
+        let tracker = replay(
+            TokenProgram::Token,
+            &[
+                approve.clone(),
+                token::transfer(id, &k.account, &k.destination, &k.owner, &[], 30).unwrap(),
+            ],
+        );
+        assert_eq!(
+            tracker.get(&k.account),
+            Some(&state(delegated(k.delegate, 100), None))
+        );
+
+        let tracker = replay(
+            TokenProgram::Token,
+            &[
+                approve.clone(),
+                delegate_transfer(30).unwrap(),
+                token::burn_checked(id, &k.account, &k.mint, &k.delegate, &[], 20, 6).unwrap(),
+            ],
+        );
+        assert_eq!(
+            tracker.get(&k.account),
+            Some(&state(delegated(k.delegate, 50), None))
+        );
+
+        // The program drops the delegate once its allowance is used up
+        let tracker = replay(
+            TokenProgram::Token,
+            &[
+                approve,
+                delegate_transfer(60).unwrap(),
+                token::transfer_checked(
+                    id,
+                    &k.account,
+                    &k.mint,
+                    &k.destination,
+                    &k.delegate,
+                    &[],
+                    40,
+                    6,
+                )
+                .unwrap(),
+            ],
+        );
+        assert_eq!(
+            tracker.get(&k.account),
+            Some(&state(
+                Some(Delegation {
+                    delegate: None,
+                    amount: 0
+                }),
+                None
+            ))
+        );
+    }
+
+    #[test]
+    fn untouched_accounts_stay_unknown() {
+        let k = keys();
+        let tracker = replay(
+            TokenProgram::Token,
+            &[token::transfer(
+                &spl_token::ID,
+                &k.account,
+                &k.destination,
+                &k.delegate,
+                &[],
+                5,
+            )
+            .unwrap()],
+        );
+        assert_eq!(tracker.get(&k.account), None);
+        assert_eq!(tracker.get(&k.destination), None);
+        assert_eq!(tracker.get(&k.mint), None);
+    }
+}
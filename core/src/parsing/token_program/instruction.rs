@@ -0,0 +1,529 @@
+use solana_pubkey::Pubkey;
+use spl_token_2022::extension::{
+    confidential_mint_burn::instruction::ConfidentialMintBurnInstruction,
+    confidential_transfer::instruction::ConfidentialTransferInstruction,
+    confidential_transfer_fee::instruction::ConfidentialTransferFeeInstruction,
+    transfer_fee::instruction::TransferFeeInstruction,
+};
+use spl_token_2022::instruction::{decode_instruction_type, AuthorityType, TokenInstruction};
+
+use crate::models::token_accounts::TokenAccountChangeKind;
+use crate::transaction::transaction::CompiledInstruction;
+
+/// The token programs whose instructions we decode. Token-2022 shares the
+/// instruction encoding of the original program for tags 0..=24 and only
+/// adds instructions on top, so both are unpacked with the Token-2022 decoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenProgram {
+    Token,
+    Token2022,
+}
+
+impl TokenProgram {
+    pub const ALL: [TokenProgram; 2] = [TokenProgram::Token, TokenProgram::Token2022];
+
+    pub fn from_id(program_id: &Pubkey) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|program| program.id() == *program_id)
+    }
+
+    pub fn id(&self) -> Pubkey {
+        match self {
+            TokenProgram::Token => spl_token::ID,
+            TokenProgram::Token2022 => spl_token_2022::ID,
+        }
+    }
+}
+
+/// A token account touched by a token program instruction. All fields are
+/// indices into the transaction's account keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AffectedTokenAccount {
+    pub account_index: usize,
+    /// Mint of the account, when the instruction layout names it
+    pub mint_index: Option<usize>,
+    /// Owner, delegate or close authority signing for the account, when the
+    /// instruction layout names one
+    pub authority_index: Option<usize>,
+    /// Set when the signing authority may be someone other than the owner: a
+    /// delegate (transfers and burns) or the close authority (CloseAccount and
+    /// changing the close authority)
+    pub signer_may_not_be_owner: bool,
+}
+
+#[derive(Debug)]
+pub struct DecodedTokenInstruction<'a> {
+    pub instruction: TokenInstruction<'a>,
+    /// Set when `instruction` is a `TransferFeeExtension` instruction
+    pub transfer_fee_instruction: Option<TransferFeeInstruction>,
+    pub affected_accounts: Vec<AffectedTokenAccount>,
+}
+
+/// Account indices and amounts of a Transfer, TransferChecked or
+/// TransferCheckedWithFee instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedTransfer {
+    pub source_index: usize,
+    pub destination_index: usize,
+    pub authority_index: usize,
+    pub mint_index: Option<usize>,
+    pub amount: u64,
+    /// Only the checked variants carry decimals
+    pub decimals: Option<u8>,
+    pub fee: Option<u64>,
+}
+
+impl DecodedTokenInstruction<'_> {
+    pub fn transfer(&self) -> Option<DecodedTransfer> {
+        let (amount, decimals, fee) = match self.instruction {
+            #[allow(deprecated)]
+            TokenInstruction::Transfer { amount } => (amount, None, None),
+            TokenInstruction::TransferChecked { amount, decimals } => {
+                (amount, Some(decimals), None)
+            }
+            TokenInstruction::TransferFeeExtension => match self.transfer_fee_instruction? {
+                TransferFeeInstruction::TransferCheckedWithFee {
+                    amount,
+                    decimals,
+                    fee,
+                } => (amount, Some(decimals), Some(fee)),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let [source, destination] = self.affected_accounts.as_slice() else {
+            return None;
+        };
+
+        Some(DecodedTransfer {
+            source_index: source.account_index,
+            destination_index: destination.account_index,
+            authority_index: source.authority_index?,
+            mint_index: source.mint_index,
+            amount,
+            decimals,
+            fee,
+        })
+    }
+
+    /// Token account and owner set by an InitializeAccount{,2,3} instruction
+    pub fn initialized_owner(&self, account_keys: &[Pubkey]) -> Option<(Pubkey, Pubkey)> {
+        let initialized = self.affected_accounts.first()?;
+        let account = *account_keys.get(initialized.account_index)?;
+        let owner = match self.instruction {
+            TokenInstruction::InitializeAccount => {
+                *account_keys.get(initialized.authority_index?)?
+            }
+            TokenInstruction::InitializeAccount2 { owner }
+            | TokenInstruction::InitializeAccount3 { owner } => owner,
+            _ => return None,
+        };
+        Some((account, owner))
+    }
+
+    /// Whether this instruction creates or closes its first affected account
+    pub fn lifecycle_event(&self) -> Option<TokenAccountChangeKind> {
+        match self.instruction {
+            TokenInstruction::InitializeAccount
+            | TokenInstruction::InitializeAccount2 { .. }
+            | TokenInstruction::InitializeAccount3 { .. } => Some(TokenAccountChangeKind::Created),
+            TokenInstruction::CloseAccount => Some(TokenAccountChangeKind::Closed),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes a token program instruction and maps its account layout to the
+/// token accounts it modifies. Returns `None` when the data can't be unpacked
+/// or the instruction references fewer accounts than its layout requires.
+pub fn decode_token_instruction(ix: &CompiledInstruction) -> Option<DecodedTokenInstruction<'_>> {
+    let instruction = TokenInstruction::unpack(&ix.data).ok()?;
+    let account = |position: usize| ix.accounts.get(position).map(|&index| index as usize);
+
+    let affected = |account_position: usize,
+                    mint_position: Option<usize>,
+                    authority_position: Option<usize>|
+     -> Option<AffectedTokenAccount> {
+        Some(AffectedTokenAccount {
+            account_index: account(account_position)?,
+            mint_index: match mint_position {
+                Some(position) => Some(account(position)?),
+                None => None,
+            },
+            authority_index: match authority_position {
+                Some(position) => Some(account(position)?),
+                None => None,
+            },
+            signer_may_not_be_owner: false,
+        })
+    };
+    let not_owner_signed = |affected: AffectedTokenAccount| AffectedTokenAccount {
+        signer_may_not_be_owner: true,
+        ..affected
+    };
+
+    // Extension instructions carry their own sub-instruction tag after the
+    // token instruction tag
+    let extension_data = ix.data.get(1..).unwrap_or_default();
+    let mut transfer_fee_instruction = None;
+
+    let affected_accounts = match &instruction {
+        TokenInstruction::InitializeAccount => vec![affected(0, Some(1), Some(2))?],
+        TokenInstruction::InitializeAccount2 { .. }
+        | TokenInstruction::InitializeAccount3 { .. } => vec![affected(0, Some(1), None)?],
+        // Deprecated in Token-2022 but still valid for both programs
+        #[allow(deprecated)]
+        TokenInstruction::Transfer { .. } => {
+            vec![
+                not_owner_signed(affected(0, None, Some(2))?),
+                affected(1, None, None)?,
+            ]
+        }
+        TokenInstruction::TransferChecked { .. } => vec![
+            not_owner_signed(affected(0, Some(1), Some(3))?),
+            affected(2, Some(1), None)?,
+        ],
+        TokenInstruction::Approve { .. } => vec![affected(0, None, Some(2))?],
+        TokenInstruction::ApproveChecked { .. } => vec![affected(0, Some(1), Some(3))?],
+        TokenInstruction::Revoke => vec![affected(0, None, Some(1))?],
+        TokenInstruction::SetAuthority { authority_type, .. } => match authority_type {
+            AuthorityType::AccountOwner => vec![affected(0, None, Some(1))?],
+            AuthorityType::CloseAccount => vec![not_owner_signed(affected(0, None, Some(1))?)],
+            // Every other authority lives on the mint
+            _ => Vec::new(),
+        },
+        TokenInstruction::MintTo { .. } | TokenInstruction::MintToChecked { .. } => {
+            vec![affected(1, Some(0), None)?]
+        }
+        TokenInstruction::Burn { .. } | TokenInstruction::BurnChecked { .. } => {
+            vec![not_owner_signed(affected(0, Some(1), Some(2))?)]
+        }
+        TokenInstruction::CloseAccount => vec![not_owner_signed(affected(0, None, Some(2))?)],
+        // The signer here is the mint's freeze authority, not the account owner
+        TokenInstruction::FreezeAccount | TokenInstruction::ThawAccount => {
+            vec![affected(0, Some(1), None)?]
+        }
+        TokenInstruction::SyncNative | TokenInstruction::InitializeImmutableOwner => {
+            vec![affected(0, None, None)?]
+        }
+        TokenInstruction::Reallocate { .. }
+        | TokenInstruction::MemoTransferExtension
+        | TokenInstruction::CpiGuardExtension => {
+            let owner_position = match instruction {
+                TokenInstruction::Reallocate { .. } => 3,
+                _ => 1,
+            };
+            vec![affected(0, None, Some(owner_position))?]
+        }
+        TokenInstruction::TransferFeeExtension => {
+            let fee_instruction = TransferFeeInstruction::unpack(extension_data).ok()?;
+            transfer_fee_instruction = Some(fee_instruction);
+            match fee_instruction {
+                TransferFeeInstruction::TransferCheckedWithFee { .. } => vec![
+                    not_owner_signed(affected(0, Some(1), Some(3))?),
+                    affected(2, Some(1), None)?,
+                ],
+                // Withheld fees only move into the destination's public balance;
+                // the harvested sources keep their amount
+                TransferFeeInstruction::WithdrawWithheldTokensFromMint
+                | TransferFeeInstruction::WithdrawWithheldTokensFromAccounts { .. } => {
+                    vec![affected(1, Some(0), None)?]
+                }
+                TransferFeeInstruction::InitializeTransferFeeConfig { .. }
+                | TransferFeeInstruction::HarvestWithheldTokensToMint
+                | TransferFeeInstruction::SetTransferFee { .. } => Vec::new(),
+            }
+        }
+        TokenInstruction::ConfidentialTransferExtension => {
+            match decode_instruction_type(extension_data).ok()? {
+                ConfidentialTransferInstruction::Transfer
+                | ConfidentialTransferInstruction::TransferWithFee => {
+                    vec![affected(0, Some(1), None)?, affected(2, Some(1), None)?]
+                }
+                ConfidentialTransferInstruction::Deposit => vec![affected(0, Some(1), Some(2))?],
+                ConfidentialTransferInstruction::ConfigureAccount
+                | ConfidentialTransferInstruction::ConfigureAccountWithRegistry
+                | ConfidentialTransferInstruction::ApproveAccount
+                | ConfidentialTransferInstruction::Withdraw => vec![affected(0, Some(1), None)?],
+                ConfidentialTransferInstruction::ApplyPendingBalance
+                | ConfidentialTransferInstruction::EnableConfidentialCredits
+                | ConfidentialTransferInstruction::DisableConfidentialCredits
+                | ConfidentialTransferInstruction::EnableNonConfidentialCredits
+                | ConfidentialTransferInstruction::DisableNonConfidentialCredits => {
+                    vec![affected(0, None, Some(1))?]
+                }
+                ConfidentialTransferInstruction::EmptyAccount => vec![affected(0, None, None)?],
+                ConfidentialTransferInstruction::InitializeMint
+                | ConfidentialTransferInstruction::UpdateMint => Vec::new(),
+            }
+        }
+        TokenInstruction::ConfidentialTransferFeeExtension => {
+            match decode_instruction_type(extension_data).ok()? {
+                ConfidentialTransferFeeInstruction::WithdrawWithheldTokensFromMint
+                | ConfidentialTransferFeeInstruction::WithdrawWithheldTokensFromAccounts => {
+                    vec![affected(1, Some(0), None)?]
+                }
+                ConfidentialTransferFeeInstruction::InitializeConfidentialTransferFeeConfig
+                | ConfidentialTransferFeeInstruction::HarvestWithheldTokensToMint
+                | ConfidentialTransferFeeInstruction::EnableHarvestToMint
+                | ConfidentialTransferFeeInstruction::DisableHarvestToMint => Vec::new(),
+            }
+        }
+        TokenInstruction::ConfidentialMintBurnExtension => {
+            match decode_instruction_type(extension_data).ok()? {
+                ConfidentialMintBurnInstruction::Mint | ConfidentialMintBurnInstruction::Burn => {
+                    vec![affected(0, Some(1), None)?]
+                }
+                ConfidentialMintBurnInstruction::InitializeMint
+                | ConfidentialMintBurnInstruction::RotateSupplyElGamalPubkey
+                | ConfidentialMintBurnInstruction::UpdateDecryptableSupply => Vec::new(),
+            }
+        }
+        // Mint and multisig setup, mint-level extensions and read-only helpers.
+        // WithdrawExcessLamports only moves lamports, never token amounts.
+        TokenInstruction::InitializeMint { .. }
+        | TokenInstruction::InitializeMint2 { .. }
+        | TokenInstruction::InitializeMultisig { .. }
+        | TokenInstruction::InitializeMultisig2 { .. }
+        | TokenInstruction::GetAccountDataSize { .. }
+        | TokenInstruction::AmountToUiAmount { .. }
+        | TokenInstruction::UiAmountToAmount { .. }
+        | TokenInstruction::InitializeMintCloseAuthority { .. }
+        | TokenInstruction::DefaultAccountStateExtension
+        | TokenInstruction::CreateNativeMint
+        | TokenInstruction::InitializeNonTransferableMint
+        | TokenInstruction::InterestBearingMintExtension
+        | TokenInstruction::InitializePermanentDelegate { .. }
+        | TokenInstruction::TransferHookExtension
+        | TokenInstruction::WithdrawExcessLamports
+        | TokenInstruction::MetadataPointerExtension
+        | TokenInstruction::GroupPointerExtension
+        | TokenInstruction::GroupMemberPointerExtension
+        | TokenInstruction::ScaledUiAmountExtension
+        | TokenInstruction::PausableExtension => Vec::new(),
+    };
+
+    Some(DecodedTokenInstruction {
+        instruction,
+        transfer_fee_instruction,
+        affected_accounts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::Instruction;
+    use spl_token::instruction::{self as token, AuthorityType as TokenAuthorityType};
+    use spl_token_2022::extension::transfer_fee::instruction::transfer_checked_with_fee;
+
+    use super::*;
+
+    /// (account, mint, authority, signer_may_not_be_owner) by key
+    type Layout = (Pubkey, Option<Pubkey>, Option<Pubkey>, bool);
+
+    /// Decodes `ix` with its accounts compiled in order
+    fn layout(ix: &Instruction) -> Vec<Layout> {
+        let keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        let compiled = CompiledInstruction {
+            program_id_index: keys.len() as u32,
+            accounts: (0..keys.len() as u8).collect(),
+            data: ix.data.clone(),
+        };
+        decode_token_instruction(&compiled)
+            .expect("undecodable instruction")
+            .affected_accounts
+            .iter()
+            .map(|affected| {
+                (
+                    keys[affected.account_index],
+                    affected.mint_index.map(|index| keys[index]),
+                    affected.authority_index.map(|index| keys[index]),
+                    affected.signer_may_not_be_owner,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn account_layouts() {
+        let [account, destination, mint, owner, other] = [(); 5].map(|_| Pubkey::new_unique());
+        let id = &spl_token::ID;
+
+        let cases: Vec<(&str, Instruction, Vec<Layout>)> = vec![
+            (
+                "InitializeAccount",
+                token::initialize_account(id, &account, &mint, &owner).unwrap(),
+                vec![(account, Some(mint), Some(owner), false)],
+            ),
+            (
+                "InitializeAccount3",
+                token::initialize_account3(id, &account, &mint, &owner).unwrap(),
+                vec![(account, Some(mint), None, false)],
+            ),
+            (
+                "Transfer",
+                token::transfer(id, &account, &destination, &owner, &[], 5).unwrap(),
+                vec![
+                    (account, None, Some(owner), true),
+                    (destination, None, None, false),
+                ],
+            ),
+            (
+                "TransferChecked",
+                token::transfer_checked(id, &account, &mint, &destination, &owner, &[], 5, 6)
+                    .unwrap(),
+                vec![
+                    (account, Some(mint), Some(owner), true),
+                    (destination, Some(mint), None, false),
+                ],
+            ),
+            (
+                "TransferCheckedWithFee",
+                transfer_checked_with_fee(
+                    &spl_token_2022::ID,
+                    &account,
+                    &mint,
+                    &destination,
+                    &owner,
+                    &[],
+                    5,
+                    6,
+                    1,
+                )
+                .unwrap(),
+                vec![
+                    (account, Some(mint), Some(owner), true),
+                    (destination, Some(mint), None, false),
+                ],
+            ),
+            (
+                "Approve",
+                token::approve(id, &account, &other, &owner, &[], 5).unwrap(),
+                vec![(account, None, Some(owner), false)],
+            ),
+            (
+                "Revoke",
+                token::revoke(id, &account, &owner, &[]).unwrap(),
+                vec![(account, None, Some(owner), false)],
+            ),
+            (
+                "MintTo",
+                token::mint_to(id, &mint, &account, &other, &[], 5).unwrap(),
+                vec![(account, Some(mint), None, false)],
+            ),
+            (
+                "MintToChecked",
+                token::mint_to_checked(id, &mint, &account, &other, &[], 5, 6).unwrap(),
+                vec![(account, Some(mint), None, false)],
+            ),
+            (
+                "Burn",
+                token::burn(id, &account, &mint, &owner, &[], 5).unwrap(),
+                vec![(account, Some(mint), Some(owner), true)],
+            ),
+            (
+                "BurnChecked",
+                token::burn_checked(id, &account, &mint, &owner, &[], 5, 6).unwrap(),
+                vec![(account, Some(mint), Some(owner), true)],
+            ),
+            (
+                "CloseAccount",
+                token::close_account(id, &account, &destination, &owner, &[]).unwrap(),
+                vec![(account, None, Some(owner), true)],
+            ),
+            (
+                "SetAuthority(AccountOwner)",
+                token::set_authority(
+                    id,
+                    &account,
+                    Some(&other),
+                    TokenAuthorityType::AccountOwner,
+                    &owner,
+                    &[],
+                )
+                .unwrap(),
+                vec![(account, None, Some(owner), false)],
+            ),
+            (
+                "SetAuthority(CloseAccount)",
+                token::set_authority(
+                    id,
+                    &account,
+                    Some(&other),
+                    TokenAuthorityType::CloseAccount,
+                    &owner,
+                    &[],
+                )
+                .unwrap(),
+                vec![(account, None, Some(owner), true)],
+            ),
+            (
+                "SetAuthority(MintTokens)",
+                token::set_authority(
+                    id,
+                    &mint,
+                    Some(&other),
+                    TokenAuthorityType::MintTokens,
+                    &owner,
+                    &[],
+                )
+                .unwrap(),
+                vec![],
+            ),
+            (
+                "FreezeAccount",
+                token::freeze_account(id, &account, &mint, &other, &[]).unwrap(),
+                vec![(account, Some(mint), None, false)],
+            ),
+            (
+                "ThawAccount",
+                token::thaw_account(id, &account, &mint, &other, &[]).unwrap(),
+                vec![(account, Some(mint), None, false)],
+            ),
+        ];
+
+        for (name, ix, expected) in cases {
+            assert_eq!(layout(&ix), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn transfer_amounts() {
+        let [account, destination, mint, owner] = [(); 4].map(|_| Pubkey::new_unique());
+        let ix = transfer_checked_with_fee(
+            &spl_token_2022::ID,
+            &account,
+            &mint,
+            &destination,
+            &owner,
+            &[],
+            500,
+            6,
+            7,
+        )
+        .unwrap();
+        let compiled = CompiledInstruction {
+            program_id_index: 4,
+            accounts: vec![0, 1, 2, 3],
+            data: ix.data,
+        };
+        let transfer = decode_token_instruction(&compiled)
+            .unwrap()
+            .transfer()
+            .unwrap();
+
+        assert_eq!(
+            transfer,
+            DecodedTransfer {
+                source_index: 0,
+                destination_index: 2,
+                authority_index: 3,
+                mint_index: Some(1),
+                amount: 500,
+                decimals: Some(6),
+                fee: Some(7),
+            }
+        );
+    }
+}
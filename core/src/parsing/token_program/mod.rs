@@ -1 +1,3 @@
+pub mod account_state;
+pub mod instruction;
 pub mod parser;
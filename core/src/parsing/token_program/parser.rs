@@ -1,14 +1,21 @@
-use crate::models::token_accounts::TokenAccountChange;
+use super::account_state::{AuthorityStateTracker, TokenAccountAuthorityState};
+use super::instruction::{
+    decode_token_instruction, AffectedTokenAccount, DecodedTransfer, TokenProgram,
+};
+use crate::models::token_accounts::{TokenAccountChange, TokenAccountChangeKind};
+use crate::models::token_transfers::{OwnerTransfer, TokenTransfer};
+use crate::models::types::TransferDirection;
 use crate::{
-    parsing::parser_trait::{ExtendQueueEntry, ParsingResult, ProgramParser},
-    redis::SmartAccountRedisClient,
-    transaction::transaction::{CompiledInstruction, TokenAmount},
+    parsing::error::ParseError,
+    parsing::parser_trait::{ExtendQueueEntry, ParsingResult, PendingParse, ProgramParser},
+    redis::{RelevanceAnswers, RelevanceQuery},
+    transaction::transaction::{CompiledInstruction, InstructionPath, TokenAmount, TokenBalance},
     QueueEntry,
 };
+use anyhow::anyhow;
 use async_trait::async_trait;
 use solana_pubkey::Pubkey;
-use spl_token::ID as TOKEN_PROGRAM_ID;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct TokenProgramParser {}
 
@@ -27,6 +34,9 @@ impl TokenProgramParser {
 #[derive(Debug)]
 pub struct TokenProgramParsingResult {
     pub token_account_changes: HashMap<Pubkey, TokenAccountChange>,
+    pub token_transfers: Vec<TokenTransfer>,
+    /// `transfer_index` points into `token_transfers`
+    pub owner_transfers: Vec<OwnerTransfer>,
 }
 
 impl Default for TokenProgramParsingResult {
@@ -39,6 +49,8 @@ impl TokenProgramParsingResult {
     pub fn new() -> Self {
         Self {
             token_account_changes: HashMap::new(),
+            token_transfers: Vec::new(),
+            owner_transfers: Vec::new(),
         }
     }
 }
@@ -48,114 +60,313 @@ impl ExtendQueueEntry for TokenProgramParsingResult {
         queue_entry
             .token_account_changes
             .extend(self.token_account_changes);
+
+        // Re-base owner transfer indices onto the transfers already queued
+        let transfer_offset = queue_entry.token_transfers.len();
+        queue_entry.token_transfers.extend(self.token_transfers);
+        queue_entry
+            .owner_transfers
+            .extend(self.owner_transfers.into_iter().map(|mut owner_transfer| {
+                owner_transfer.transfer_index += transfer_offset;
+                owner_transfer
+            }));
     }
 }
 
+fn token_amount(balance: &TokenBalance) -> &TokenAmount {
+    static EMPTY: TokenAmount = TokenAmount {
+        ui_amount: None,
+        decimals: 0,
+        amount: 0,
+        ui_amount_string: String::new(),
+    };
+    balance.ui_token_amount.as_ref().unwrap_or(&EMPTY)
+}
+
 // Helper function to create TokenAccountChange
+#[allow(clippy::too_many_arguments)]
 fn create_token_account_change(
     address: Pubkey,
     network: i32,
     owner: Pubkey,
     mint: Pubkey,
-    amount: u64,
+    pre_amount: u64,
+    post_amount: u64,
     decimals: i32,
     ui_amount: String,
-    delegate: Option<Pubkey>,
-    is_frozen: bool,
+    token_program: TokenProgram,
+    authority_state: TokenAccountAuthorityState,
     last_updated_signature: solana_signature::Signature,
     last_updated_slot: i64,
+    change_kind: TokenAccountChangeKind,
 ) -> TokenAccountChange {
     TokenAccountChange {
         address: address.to_string(),
         network,
         owner: owner.to_string(),
         mint: mint.to_string(),
-        amount: amount.to_string(),
+        amount: post_amount.to_string(),
+        pre_amount: pre_amount.to_string(),
+        amount_delta: (post_amount as i128 - pre_amount as i128).to_string(),
         decimals,
         ui_amount: ui_amount.to_string(),
-        delegate: delegate.map(|d| d.to_string()),
-        is_frozen,
+        token_program: token_program.id().to_string(),
+        delegate: authority_state
+            .delegation
+            .and_then(|delegation| delegation.delegate)
+            .map(|d| d.to_string()),
+        delegated_amount: authority_state
+            .delegation
+            .map(|delegation| delegation.amount.to_string()),
+        is_frozen: authority_state.is_frozen,
         last_updated_signature: last_updated_signature.to_string(),
         last_updated_slot,
+        change_kind,
+    }
+}
+
+/// Token program state carried from `prepare` to `finalize`
+pub struct PendingTokenParse {
+    account_keys: Vec<Pubkey>,
+    /// Delegate and frozen state as updated by the decoded instructions
+    authority_tracker: AuthorityStateTracker,
+    /// Last InitializeAccount* / CloseAccount seen for each account
+    lifecycle_events: HashMap<Pubkey, TokenAccountChangeKind>,
+    /// Last occurrence of every token account touched, with its program
+    touched_accounts: HashMap<Pubkey, (TokenProgram, AffectedTokenAccount)>,
+    /// Owners set by InitializeAccount* instructions in this transaction
+    initialized_owners: HashMap<Pubkey, Pubkey>,
+    transfers: Vec<(InstructionPath, TokenProgram, DecodedTransfer)>,
+}
+
+/// Token balances of a transaction keyed by account index
+struct Balances<'a> {
+    pre: HashMap<u32, &'a TokenBalance>,
+    post: HashMap<u32, &'a TokenBalance>,
+}
+
+impl<'a> Balances<'a> {
+    fn new(queue_entry: &'a QueueEntry) -> Self {
+        let meta = &queue_entry.transaction.meta;
+        let by_index = |balances: &'a [TokenBalance]| {
+            balances
+                .iter()
+                .map(|balance| (balance.account_index, balance))
+                .collect()
+        };
+        Self {
+            pre: by_index(&meta.pre_token_balances),
+            post: by_index(&meta.post_token_balances),
+        }
+    }
+}
+
+impl PendingTokenParse {
+    /// Owner known from this transaction alone: the post balance reflects the
+    /// final owner, otherwise prefer what this transaction initialized, then
+    /// the pre balance
+    fn known_owner(
+        &self,
+        balances: &Balances,
+        address: &Pubkey,
+        affected: &AffectedTokenAccount,
+    ) -> Option<Pubkey> {
+        let index = affected.account_index as u32;
+        balances
+            .post
+            .get(&index)
+            .map(|balance| balance.owner)
+            .or_else(|| self.initialized_owners.get(address).copied())
+            .or_else(|| balances.pre.get(&index).map(|balance| balance.owner))
+    }
+
+    /// The signer stands in for the owner only when it must be the owner:
+    /// attributing a transfer signed by a delegate, or a close signed by the
+    /// close authority, to that signer would decide the account's relevance on
+    /// the wrong wallet
+    fn authority(&self, affected: &AffectedTokenAccount) -> Option<Pubkey> {
+        if affected.signer_may_not_be_owner {
+            return None;
+        }
+        affected
+            .authority_index
+            .and_then(|index| self.account_keys.get(index).copied())
     }
 }
 
 #[async_trait]
 impl ProgramParser for TokenProgramParser {
-    async fn parse_transaction(
+    fn name(&self) -> &'static str {
+        "token"
+    }
+
+    fn program_ids(&self) -> Vec<Pubkey> {
+        TokenProgram::ALL.map(|program| program.id()).to_vec()
+    }
+
+    fn prepare(
         &self,
         queue_entry: &QueueEntry,
-        network: i32,
-        redis_client: &SmartAccountRedisClient,
+        _network: i32,
         instructions: &HashMap<Pubkey, Vec<CompiledInstruction>>,
-    ) -> Result<Option<ParsingResult>, anyhow::Error> {
-        let account_keys = queue_entry.transaction.get_account_keys();
-        let post_token_balances = queue_entry.transaction.meta.post_token_balances.clone();
+        query: &mut RelevanceQuery,
+    ) -> Result<PendingParse, ParseError> {
+        if !TokenProgram::ALL
+            .iter()
+            .any(|program| instructions.contains_key(&program.id()))
+        {
+            return Ok(PendingParse::Irrelevant);
+        }
 
-        // Get all token program instructions
-        let all_token_program_instructions = match instructions.get(&TOKEN_PROGRAM_ID) {
-            Some(instructions) => instructions,
-            None => return Ok(None),
-        };
+        let account_keys = queue_entry.transaction.get_account_keys();
 
-        let mut result = TokenProgramParsingResult::new();
+        // Token program instructions in execution order, inner instructions included
+        let all_token_program_instructions = queue_entry
+            .transaction
+            .get_instructions_with_path_by_programs(&TokenProgram::ALL.map(|program| program.id()));
 
-        // Collect all owners to check for batch filtering. Saves us multiple redis calls per txn.
-        let mut all_owners_to_check = Vec::new();
+        let mut authority_tracker = AuthorityStateTracker::new();
+        let mut lifecycle_events = HashMap::new();
+        let mut touched_accounts = HashMap::new();
+        let mut initialized_owners = HashMap::new();
+        let mut transfers = Vec::new();
 
-        for ix in all_token_program_instructions {
-            // Skip if we don't have enough accounts
-            if ix.accounts.len() < 2 {
+        for (path, program_id, ix) in &all_token_program_instructions {
+            let Some(token_program) = TokenProgram::from_id(program_id) else {
+                continue;
+            };
+            let Some(decoded) = decode_token_instruction(ix) else {
+                tracing::debug!("Skipping undecodable token program instruction");
                 continue;
+            };
+
+            authority_tracker.apply(token_program, ix, &decoded, &account_keys);
+
+            if let Some(transfer) = decoded.transfer() {
+                transfers.push((*path, token_program, transfer));
             }
 
-            // Assume account 0 is the token account
-            let token_account_index = ix.accounts[0] as usize;
-            if token_account_index >= account_keys.len() {
-                continue;
+            if let Some((account, owner)) = decoded.initialized_owner(&account_keys) {
+                initialized_owners.insert(account, owner);
             }
 
-            let token_account_address = account_keys[token_account_index];
+            if let (Some(event), Some(affected)) =
+                (decoded.lifecycle_event(), decoded.affected_accounts.first())
+            {
+                if let Some(&address) = account_keys.get(affected.account_index) {
+                    lifecycle_events.insert(address, event);
+                }
+            }
 
-            // Assume account 1 is the owner/mint (simplified assumption)
-            let owner_index = ix.accounts[1] as usize;
-            if owner_index >= account_keys.len() {
-                continue;
+            for affected in &decoded.affected_accounts {
+                if let Some(&address) = account_keys.get(affected.account_index) {
+                    touched_accounts.insert(address, (token_program, *affected));
+                }
             }
+        }
 
-            let owner_address = account_keys[owner_index];
+        let pending = PendingTokenParse {
+            account_keys,
+            authority_tracker,
+            lifecycle_events,
+            touched_accounts,
+            initialized_owners,
+            transfers,
+        };
 
-            // Try to find post balance for this account
-            let maybe_post_balance = post_token_balances
-                .iter()
-                .find(|balance| balance.account_index == token_account_index as u32);
-
-            let (amount, decimals, ui_amount, owner, mint) =
-                if let Some(balance) = maybe_post_balance {
-                    let balances = match &balance.ui_token_amount {
-                        Some(token_amount) => token_amount,
-                        None => &TokenAmount::default(),
-                    };
-                    (
-                        balances.amount,
-                        balances.decimals as i32,
-                        balances.ui_amount_string.clone(),
-                        balance.owner,
-                        balance.mint,
-                    )
-                } else {
-                    // Default values if no balance found
-                    (
-                        0,
-                        0,
-                        "0".to_string(),
-                        owner_address,
-                        owner_address,
-                    )
-                };
-
-            // Add owner to check list for batch processing
-            all_owners_to_check.push(owner);
+        // A change is kept when either its owner or the token account itself
+        // (e.g. a vault owned by an untracked PDA) is relevant. Accounts this
+        // transaction says nothing about fall back to owners learned from
+        // earlier transactions, then to the signing authority when it must be
+        // the owner.
+        let balances = Balances::new(queue_entry);
+        for (address, (_, affected)) in &pending.touched_accounts {
+            query.check_token_account(*address);
+            match pending.known_owner(&balances, address, affected) {
+                Some(owner) => query.check_owner(owner),
+                None => {
+                    query.resolve_owner(*address);
+                    if let Some(authority) = pending.authority(affected) {
+                        query.check_owner(authority);
+                    }
+                }
+            }
+        }
+
+        Ok(PendingParse::Token(Box::new(pending)))
+    }
+
+    fn finalize(
+        &self,
+        queue_entry: &QueueEntry,
+        network: i32,
+        pending: PendingParse,
+        answers: &RelevanceAnswers,
+        learned_owners: &mut Vec<(Pubkey, Pubkey)>,
+    ) -> Result<Option<ParsingResult>, ParseError> {
+        let pending = match pending {
+            PendingParse::Token(pending) => *pending,
+            PendingParse::Irrelevant => return Ok(None),
+            _ => return Err(anyhow!("token parser given another parser's state").into()),
+        };
+        let account_keys = &pending.account_keys;
+        let balances = Balances::new(queue_entry);
+
+        let mut result = TokenProgramParsingResult::new();
+
+        // Owner, mint and decimals resolved for each affected account
+        let mut resolved_accounts: HashMap<Pubkey, (Pubkey, Pubkey, i32)> = HashMap::new();
+
+        for (token_account_address, (token_program, affected)) in &pending.touched_accounts {
+            let token_account_address = *token_account_address;
+
+            // An account only in pre was closed, one only in post was created
+            let pre_balance = balances.pre.get(&(affected.account_index as u32));
+            let post_balance = balances.post.get(&(affected.account_index as u32));
+
+            // Accounts with no owner from this transaction, earlier
+            // transactions or an owner-only signing authority can't be attributed
+            let Some(owner) = pending
+                .known_owner(&balances, &token_account_address, affected)
+                .or_else(|| answers.indexed_owner(&token_account_address))
+                .or_else(|| pending.authority(affected))
+            else {
+                continue;
+            };
+            let balance = post_balance.or(pre_balance);
+            let mint = balance
+                .map(|balance| balance.mint)
+                .or_else(|| {
+                    affected
+                        .mint_index
+                        .and_then(|index| account_keys.get(index).copied())
+                })
+                .unwrap_or_default();
+            let decimals = balance
+                .and_then(|balance| balance.ui_token_amount.as_ref())
+                .map_or(0, |token_amount| token_amount.decimals as i32);
+
+            // Instructions are authoritative; balance presence covers accounts
+            // created or closed by programs we don't decode
+            let change_kind = match pending.lifecycle_events.get(&token_account_address) {
+                Some(event) => *event,
+                None => match (pre_balance, post_balance) {
+                    (None, Some(_)) => TokenAccountChangeKind::Created,
+                    (Some(_), None) => TokenAccountChangeKind::Closed,
+                    _ => TokenAccountChangeKind::Updated,
+                },
+            };
+
+            let pre_amount = pre_balance.map_or(0, |balance| token_amount(balance).amount);
+            let (post_amount, ui_amount) = match post_balance {
+                Some(balance) => {
+                    let token_amount = token_amount(balance);
+                    (token_amount.amount, token_amount.ui_amount_string.clone())
+                }
+                None => (0, "0".to_string()),
+            };
+
+            resolved_accounts.insert(token_account_address, (owner, mint, decimals));
 
             // Create change entry
             let entry = create_token_account_change(
@@ -163,13 +374,19 @@ impl ProgramParser for TokenProgramParser {
                 network,
                 owner,
                 mint,
-                amount,
+                pre_amount,
+                post_amount,
                 decimals,
                 ui_amount,
-                None,  // No delegate for simplified version
-                false, // Not frozen for simplified version
+                *token_program,
+                pending
+                    .authority_tracker
+                    .get(&token_account_address)
+                    .copied()
+                    .unwrap_or_default(),
                 queue_entry.signature,
                 queue_entry.slot as i64,
+                change_kind,
             );
 
             result
@@ -177,29 +394,100 @@ impl ProgramParser for TokenProgramParser {
                 .insert(token_account_address, entry);
         }
 
-        // Batch filter token account changes based on owner relevance
-        if !all_owners_to_check.is_empty() {
-            // Use batch check function for better performance
-            let owner_relevance = redis_client
-                .batch_check_token_account_owners(&all_owners_to_check)
-                .await
-                .unwrap_or_default();
+        let relevant_owners: HashSet<Pubkey> = resolved_accounts
+            .values()
+            .map(|(owner, _, _)| *owner)
+            .filter(|owner| answers.is_relevant_owner(owner))
+            .collect();
+        let relevant_accounts: HashSet<Pubkey> = resolved_accounts
+            .iter()
+            .filter(|(account, (owner, _, _))| {
+                answers.is_relevant_token_account(account) || relevant_owners.contains(owner)
+            })
+            .map(|(account, _)| *account)
+            .collect();
+        result
+            .token_account_changes
+            .retain(|address, _| relevant_accounts.contains(address));
 
-            let mut filtered_changes = HashMap::new();
-            for (address, change) in result.token_account_changes {
-                // Parse the owner string back to Pubkey for checking
-                if let Ok(owner_pubkey) = change.owner.parse::<Pubkey>() {
-                    // Find the index of this owner in the all_owners_to_check list
-                    if let Some(owner_index) =
-                        all_owners_to_check.iter().position(|&x| x == owner_pubkey)
-                    {
-                        if owner_relevance.get(owner_index).copied().unwrap_or(false) {
-                            filtered_changes.insert(address, change);
-                        }
-                    }
-                }
+        // Remember which relevant owner each new account belongs to, so later
+        // transactions touching it without a balance are still attributed
+        learned_owners.extend(
+            pending
+                .initialized_owners
+                .into_iter()
+                .filter(|(_, owner)| relevant_owners.contains(owner)),
+        );
+
+        // Keep transfers with at least one relevant side, with a direction for the
+        // owner of each relevant side
+        for (path, token_program, transfer) in pending.transfers {
+            let key = |index: usize| account_keys.get(index).copied();
+            let (Some(source), Some(destination), Some(authority)) = (
+                key(transfer.source_index),
+                key(transfer.destination_index),
+                key(transfer.authority_index),
+            ) else {
+                continue;
+            };
+            let source_account = resolved_accounts.get(&source);
+            let destination_account = resolved_accounts.get(&destination);
+            let source_owner = source_account.map(|(owner, _, _)| *owner);
+            let destination_owner = destination_account.map(|(owner, _, _)| *owner);
+
+            let transfer_index = result.token_transfers.len();
+            let mut owner_transfers = Vec::new();
+            if let Some(owner) = source_owner.filter(|_| relevant_accounts.contains(&source)) {
+                owner_transfers.push(OwnerTransfer {
+                    owner: owner.to_string(),
+                    direction: TransferDirection::Outflow,
+                    transfer_index,
+                });
+            }
+            if let Some(owner) =
+                destination_owner.filter(|_| relevant_accounts.contains(&destination))
+            {
+                owner_transfers.push(OwnerTransfer {
+                    owner: owner.to_string(),
+                    direction: TransferDirection::Inflow,
+                    transfer_index,
+                });
+            }
+            if owner_transfers.is_empty() {
+                continue;
             }
-            result.token_account_changes = filtered_changes;
+
+            // Unchecked transfers don't name the mint or decimals, the balances do
+            let (mint, decimals) = match source_account.or(destination_account) {
+                Some((_, mint, decimals)) => (
+                    transfer.mint_index.and_then(key).unwrap_or(*mint),
+                    transfer
+                        .decimals
+                        .map_or(*decimals, |decimals| decimals as i32),
+                ),
+                None => (
+                    transfer.mint_index.and_then(key).unwrap_or_default(),
+                    transfer.decimals.unwrap_or_default() as i32,
+                ),
+            };
+
+            result.token_transfers.push(TokenTransfer {
+                network,
+                signature: queue_entry.signature.to_string(),
+                slot: queue_entry.slot as i64,
+                instruction_path: path.to_string(),
+                token_program: token_program.id().to_string(),
+                source: source.to_string(),
+                source_owner: source_owner.map(|owner| owner.to_string()),
+                destination: destination.to_string(),
+                destination_owner: destination_owner.map(|owner| owner.to_string()),
+                authority: authority.to_string(),
+                mint: mint.to_string(),
+                amount: transfer.amount.to_string(),
+                decimals,
+                fee: transfer.fee.map(|fee| fee.to_string()),
+            });
+            result.owner_transfers.extend(owner_transfers);
         }
 
         if result.token_account_changes.is_empty() {
@@ -209,3 +497,318 @@ impl ProgramParser for TokenProgramParser {
         Ok(Some(ParsingResult::Token(result)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use spl_token::instruction as token;
+
+    use super::*;
+    use crate::parsing::test_support::{parse, relevant_owners, TestTransaction, NETWORK};
+    use crate::redis::{LocalRedisEmulator, RelevanceStore, RelevantAccountCache};
+
+    fn token_result(result: Option<ParsingResult>) -> TokenProgramParsingResult {
+        match result {
+            Some(ParsingResult::Token(result)) => result,
+            None => TokenProgramParsingResult::new(),
+            Some(_) => panic!("token parser returned another parser's result"),
+        }
+    }
+
+    #[test]
+    fn authority_state_only_from_this_transaction() {
+        let [payer, owner, recipient, mint, source, destination, delegate] =
+            [(); 7].map(|_| Pubkey::new_unique());
+        let id = &spl_token::ID;
+        let transaction = TestTransaction::new(
+            &payer,
+            &[
+                token::approve(id, &source, &delegate, &owner, &[], 50).unwrap(),
+                token::transfer(id, &source, &destination, &owner, &[], 10).unwrap(),
+            ],
+        )
+        .token_balance(&source, &mint, &owner, Some(100), Some(90))
+        .token_balance(&destination, &mint, &recipient, Some(0), Some(10))
+        .build();
+
+        let (result, _) = parse(
+            &TokenProgramParser::new(),
+            &relevant_owners(&[owner, recipient]),
+            transaction,
+        );
+        let changes = token_result(result).token_account_changes;
+
+        let source = &changes[&source];
+        assert_eq!(source.delegate, Some(delegate.to_string()));
+        assert_eq!(source.delegated_amount, Some("50".to_string()));
+        assert_eq!(source.is_frozen, None);
+
+        // Only received tokens: nothing in the transaction says anything about
+        // its delegate or frozen flag
+        let destination = &changes[&destination];
+        assert_eq!(destination.delegate, None);
+        assert_eq!(destination.delegated_amount, None);
+        assert_eq!(destination.is_frozen, None);
+    }
+
+    #[test]
+    fn amounts_and_signed_delta() {
+        let [payer, owner, recipient, mint, source, destination, created, closed] =
+            [(); 8].map(|_| Pubkey::new_unique());
+        let id = &spl_token::ID;
+        let transaction = TestTransaction::new(
+            &payer,
+            &[
+                token::transfer(id, &source, &destination, &owner, &[], 30).unwrap(),
+                token::initialize_account3(id, &created, &mint, &owner).unwrap(),
+                token::mint_to(id, &mint, &created, &payer, &[], 40).unwrap(),
+                token::burn(id, &closed, &mint, &owner, &[], 25).unwrap(),
+                token::close_account(id, &closed, &payer, &owner, &[]).unwrap(),
+            ],
+        )
+        .token_balance(&source, &mint, &owner, Some(100), Some(70))
+        .token_balance(&destination, &mint, &recipient, Some(5), Some(35))
+        .token_balance(&created, &mint, &owner, None, Some(40))
+        .token_balance(&closed, &mint, &owner, Some(25), None)
+        .build();
+
+        let (result, _) = parse(
+            &TokenProgramParser::new(),
+            &relevant_owners(&[owner, recipient]),
+            transaction,
+        );
+        let changes = token_result(result).token_account_changes;
+        let amounts = |account: &Pubkey| {
+            let change = &changes[account];
+            (
+                change.pre_amount.as_str(),
+                change.amount.as_str(),
+                change.amount_delta.as_str(),
+            )
+        };
+
+        assert_eq!(amounts(&source), ("100", "70", "-30"));
+        assert_eq!(amounts(&destination), ("5", "35", "30"));
+        assert_eq!(amounts(&created), ("0", "40", "40"));
+        assert_eq!(amounts(&closed), ("25", "0", "-25"));
+    }
+
+    #[test]
+    fn lifecycle_events_decide_change_kind() {
+        let [payer, owner, mint, recreated, temporary, source, destination] =
+            [(); 7].map(|_| Pubkey::new_unique());
+        let id = &spl_token::ID;
+        let transaction = TestTransaction::new(
+            &payer,
+            &[
+                // Balances before and after, but the account is new
+                token::close_account(id, &recreated, &payer, &owner, &[]).unwrap(),
+                token::initialize_account3(id, &recreated, &mint, &owner).unwrap(),
+                // No balance on either side, but the account was closed last
+                token::initialize_account3(id, &temporary, &mint, &owner).unwrap(),
+                token::close_account(id, &temporary, &payer, &owner, &[]).unwrap(),
+                token::transfer(id, &source, &destination, &owner, &[], 5).unwrap(),
+            ],
+        )
+        .token_balance(&recreated, &mint, &owner, Some(0), Some(0))
+        .token_balance(&source, &mint, &owner, Some(10), Some(5))
+        .token_balance(&destination, &mint, &owner, Some(0), Some(5))
+        .build();
+
+        let (result, _) = parse(
+            &TokenProgramParser::new(),
+            &relevant_owners(&[owner]),
+            transaction,
+        );
+        let changes = token_result(result).token_account_changes;
+
+        assert_eq!(
+            changes[&recreated].change_kind,
+            TokenAccountChangeKind::Created
+        );
+        assert_eq!(
+            changes[&temporary].change_kind,
+            TokenAccountChangeKind::Closed
+        );
+        assert_eq!(
+            changes[&source].change_kind,
+            TokenAccountChangeKind::Updated
+        );
+        assert_eq!(
+            changes[&destination].change_kind,
+            TokenAccountChangeKind::Updated
+        );
+    }
+
+    #[test]
+    fn transfers_and_directions() {
+        let [payer, alice, bob, stranger, mint] = [(); 5].map(|_| Pubkey::new_unique());
+        let [alice_account, bob_account, stranger_account, other_stranger_account] =
+            [(); 4].map(|_| Pubkey::new_unique());
+        let id = &spl_token::ID;
+        let transaction = TestTransaction::new(
+            &payer,
+            &[
+                // Both sides relevant
+                token::transfer(id, &alice_account, &bob_account, &alice, &[], 10).unwrap(),
+                // Only the source is relevant
+                token::transfer_checked(
+                    id,
+                    &bob_account,
+                    &mint,
+                    &stranger_account,
+                    &bob,
+                    &[],
+                    4,
+                    6,
+                )
+                .unwrap(),
+                // No relevant side
+                token::transfer(
+                    id,
+                    &stranger_account,
+                    &other_stranger_account,
+                    &stranger,
+                    &[],
+                    1,
+                )
+                .unwrap(),
+                // Only the destination is relevant
+                spl_token_2022::extension::transfer_fee::instruction::transfer_checked_with_fee(
+                    &spl_token_2022::ID,
+                    &stranger_account,
+                    &mint,
+                    &alice_account,
+                    &stranger,
+                    &[],
+                    3,
+                    6,
+                    1,
+                )
+                .unwrap(),
+            ],
+        )
+        .token_balance(&alice_account, &mint, &alice, Some(20), Some(13))
+        .token_balance(&bob_account, &mint, &bob, Some(0), Some(6))
+        .token_balance(&stranger_account, &mint, &stranger, Some(0), Some(0))
+        .token_balance(&other_stranger_account, &mint, &stranger, Some(0), Some(1))
+        .build();
+
+        let (result, _) = parse(
+            &TokenProgramParser::new(),
+            &relevant_owners(&[alice, bob]),
+            transaction,
+        );
+        let result = token_result(result);
+
+        let transfers: Vec<_> = result
+            .token_transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.instruction_path.as_str(),
+                    transfer.amount.as_str(),
+                    transfer.fee.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            transfers,
+            vec![("0", "10", None), ("1", "4", None), ("3", "3", Some("1"))]
+        );
+        assert_eq!(
+            result.token_transfers[1].destination_owner,
+            Some(stranger.to_string())
+        );
+
+        let owner_transfer = |owner: &Pubkey, direction, transfer_index| OwnerTransfer {
+            owner: owner.to_string(),
+            direction,
+            transfer_index,
+        };
+        assert_eq!(
+            result.owner_transfers,
+            vec![
+                owner_transfer(&alice, TransferDirection::Outflow, 0),
+                owner_transfer(&bob, TransferDirection::Inflow, 0),
+                owner_transfer(&bob, TransferDirection::Outflow, 1),
+                owner_transfer(&alice, TransferDirection::Inflow, 2),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn initialized_accounts_of_relevant_owners_are_indexed() {
+        let [payer, owner, stranger, mint] = [(); 4].map(|_| Pubkey::new_unique());
+        let [first, second, third, strangers] = [(); 4].map(|_| Pubkey::new_unique());
+        let id = &spl_token::ID;
+        let transaction = TestTransaction::new(
+            &payer,
+            &[
+                token::initialize_account(id, &first, &mint, &owner).unwrap(),
+                token::initialize_account2(id, &second, &mint, &owner).unwrap(),
+                token::initialize_account3(id, &third, &mint, &owner).unwrap(),
+                token::initialize_account3(id, &strangers, &mint, &stranger).unwrap(),
+            ],
+        )
+        .build();
+
+        let (_, mut learned_owners) = parse(
+            &TokenProgramParser::new(),
+            &relevant_owners(&[owner]),
+            transaction.clone(),
+        );
+        learned_owners.sort();
+        let mut expected = vec![(first, owner), (second, owner), (third, owner)];
+        expected.sort();
+        assert_eq!(learned_owners, expected);
+
+        // Parsing against a store records them in its owner index
+        let store = LocalRedisEmulator::new();
+        store.add_owners(NETWORK, &[owner]).await.unwrap();
+        let instructions = transaction.get_instructions_by_program_id();
+        let queue_entry = QueueEntry::new(NETWORK, transaction.signature, transaction);
+        TokenProgramParser::new()
+            .parse_transaction(&queue_entry, NETWORK, &store, &instructions)
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .batch_get_token_account_owners(NETWORK, &[first, second, third, strangers])
+                .await
+                .unwrap(),
+            vec![Some(owner), Some(owner), Some(owner), None]
+        );
+    }
+
+    #[test]
+    fn vaults_are_kept_without_a_relevant_owner() {
+        let [payer, pda, stranger, mint, vault, strangers] = [(); 6].map(|_| Pubkey::new_unique());
+        let transaction = TestTransaction::new(
+            &payer,
+            &[token::transfer(&spl_token::ID, &vault, &strangers, &pda, &[], 5).unwrap()],
+        )
+        .token_balance(&vault, &mint, &pda, Some(50), Some(45))
+        .token_balance(&strangers, &mint, &stranger, Some(0), Some(5))
+        .build();
+
+        let mut relevance = RelevantAccountCache::default();
+        relevance.add_token_accounts(NETWORK, &[vault]);
+        let (result, _) = parse(&TokenProgramParser::new(), &relevance, transaction.clone());
+        let result = token_result(result);
+        assert_eq!(
+            result.token_account_changes.keys().collect::<Vec<_>>(),
+            vec![&vault]
+        );
+        assert_eq!(result.token_transfers.len(), 1);
+        assert_eq!(
+            result.owner_transfers[0].direction,
+            TransferDirection::Outflow
+        );
+
+        // A vault registered on another network doesn't count
+        let mut relevance = RelevantAccountCache::default();
+        relevance.add_token_accounts(NETWORK + 1, &[vault]);
+        let (result, _) = parse(&TokenProgramParser::new(), &relevance, transaction);
+        assert!(result.is_none());
+    }
+}
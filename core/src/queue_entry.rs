@@ -1,17 +1,20 @@
 use std::collections::HashMap;
 
+use crate::models::associated_token_accounts::AssociatedTokenAccount;
+use crate::models::lamport_changes::LamportChange;
+use crate::models::sol_transfers::SolTransfer;
 use crate::models::token_accounts::TokenAccountChange;
+use crate::models::token_transfers::{OwnerTransfer, TokenTransfer};
 use chrono::NaiveDateTime;
 use solana_pubkey::Pubkey;
 use solana_signature::Signature;
 
 use crate::transaction::transaction::UnifiedTransaction;
 
-use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde::de::Deserializer;
 use serde::Deserialize;
-use std::fmt;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct QueueEntry {
     pub signature: Signature,
     pub network: i32,
@@ -20,6 +23,20 @@ pub struct QueueEntry {
     #[serde(deserialize_with = "deserialize_block_time")]
     pub block_time: i64,
     pub token_account_changes: HashMap<Pubkey, TokenAccountChange>,
+    #[serde(default)]
+    pub token_transfers: Vec<TokenTransfer>,
+    #[serde(default)]
+    pub owner_transfers: Vec<OwnerTransfer>,
+    /// SOL balance changes of relevant wallets moved by System Program
+    /// instructions
+    #[serde(default)]
+    pub lamport_changes: HashMap<Pubkey, LamportChange>,
+    #[serde(default)]
+    pub sol_transfers: Vec<SolTransfer>,
+    /// Associated token accounts of relevant wallets touched by the ATA
+    /// program
+    #[serde(default)]
+    pub associated_token_accounts: Vec<AssociatedTokenAccount>,
 }
 
 fn deserialize_block_time<'de, D>(deserializer: D) -> Result<i64, D::Error>
@@ -88,6 +105,11 @@ impl Default for QueueEntry {
             slot: 0,
             block_time: 0,
             token_account_changes: HashMap::new(),
+            token_transfers: Vec::new(),
+            owner_transfers: Vec::new(),
+            lamport_changes: HashMap::new(),
+            sol_transfers: Vec::new(),
+            associated_token_accounts: Vec::new(),
         }
     }
 }
@@ -102,10 +124,17 @@ impl QueueEntry {
             slot,
             block_time: 0,
             token_account_changes: HashMap::new(),
+            token_transfers: Vec::new(),
+            owner_transfers: Vec::new(),
+            lamport_changes: HashMap::new(),
+            sol_transfers: Vec::new(),
+            associated_token_accounts: Vec::new(),
         }
     }
 
     pub fn contains_changes(&self) -> bool {
         !self.token_account_changes.is_empty()
+            || !self.lamport_changes.is_empty()
+            || !self.associated_token_accounts.is_empty()
     }
 }
@@ -0,0 +1,379 @@
+//! Latency and failure injection for the emulator.
+//!
+//! Every simulated round trip samples a latency from the configured model,
+//! adds a per-key cost, and may then fail with an injected error, a timeout or
+//! a dropped connection.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Distribution a round trip's base latency is drawn from
+#[derive(Debug, Clone, PartialEq)]
+pub enum LatencyModel {
+    Fixed(Duration),
+    /// Clamped at zero
+    Normal {
+        mean: Duration,
+        std_dev: Duration,
+    },
+    /// Quantile function given as (percentile, latency) points, e.g.
+    /// p50=400us,p99=3ms,p99.9=20ms. Interpolated linearly between points and
+    /// flat beyond the first and last ones.
+    Percentiles(Vec<(f64, Duration)>),
+}
+
+impl Default for LatencyModel {
+    fn default() -> Self {
+        LatencyModel::Fixed(Duration::from_micros(500))
+    }
+}
+
+impl LatencyModel {
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        match self {
+            LatencyModel::Fixed(latency) => *latency,
+            LatencyModel::Normal { mean, std_dev } => {
+                // Box-Muller
+                let u1: f64 = rng.random_range(f64::EPSILON..1.0);
+                let u2: f64 = rng.random();
+                let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+                saturating_secs_f64(mean.as_secs_f64() + z * std_dev.as_secs_f64())
+            }
+            LatencyModel::Percentiles(points) => {
+                let quantile = rng.random::<f64>() * 100.0;
+                interpolate(points, quantile)
+            }
+        }
+    }
+}
+
+/// Clamps negative and NaN seconds to zero and overflowing ones to
+/// `Duration::MAX`
+fn saturating_secs_f64(seconds: f64) -> Duration {
+    if seconds.is_nan() || seconds <= 0.0 {
+        return Duration::ZERO;
+    }
+    Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX)
+}
+
+fn interpolate(points: &[(f64, Duration)], quantile: f64) -> Duration {
+    let Some(&(first_percentile, first_latency)) = points.first() else {
+        return Duration::ZERO;
+    };
+    if quantile <= first_percentile {
+        return first_latency;
+    }
+    for window in points.windows(2) {
+        let (low, low_latency) = window[0];
+        let (high, high_latency) = window[1];
+        if quantile <= high {
+            let fraction = (quantile - low) / (high - low);
+            let low_secs = low_latency.as_secs_f64();
+            return saturating_secs_f64(
+                low_secs + fraction * (high_latency.as_secs_f64() - low_secs),
+            );
+        }
+    }
+    points[points.len() - 1].1
+}
+
+/// Parses `fixed:500us`, `normal:500us,100us` or
+/// `percentiles:p50=400us,p99=3ms,p99.9=20ms`
+impl FromStr for LatencyModel {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (kind, params) = value.split_once(':').unwrap_or((value, ""));
+        match kind {
+            "fixed" => Ok(LatencyModel::Fixed(parse_duration(params)?)),
+            "normal" => {
+                let (mean, std_dev) = params
+                    .split_once(',')
+                    .ok_or_else(|| anyhow!("expected normal:<mean>,<std_dev>"))?;
+                Ok(LatencyModel::Normal {
+                    mean: parse_duration(mean)?,
+                    std_dev: parse_duration(std_dev)?,
+                })
+            }
+            "percentiles" => {
+                let mut points = params
+                    .split(',')
+                    .map(|point| {
+                        let (percentile, latency) = point
+                            .split_once('=')
+                            .ok_or_else(|| anyhow!("expected p<percentile>=<latency>"))?;
+                        let percentile: f64 = percentile
+                            .trim_start_matches('p')
+                            .parse()
+                            .with_context(|| format!("invalid percentile {:?}", percentile))?;
+                        if !(0.0..=100.0).contains(&percentile) {
+                            bail!("percentile {} out of range", percentile);
+                        }
+                        Ok((percentile, parse_duration(latency)?))
+                    })
+                    .collect::<Result<Vec<_>, anyhow::Error>>()?;
+                points.sort_by(|a, b| a.0.total_cmp(&b.0));
+                points.dedup_by(|a, b| a.0 == b.0);
+                Ok(LatencyModel::Percentiles(points))
+            }
+            other => bail!(
+                "unknown latency model {:?}, expected fixed, normal or percentiles",
+                other
+            ),
+        }
+    }
+}
+
+/// Parses durations like `500us`, `1.5ms`, `2s` or `100ns`
+pub fn parse_duration(value: &str) -> Result<Duration, anyhow::Error> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .ok_or_else(|| anyhow!("duration {:?} is missing a unit", value))?;
+    let (amount, unit) = value.split_at(split);
+    let amount: f64 = amount
+        .parse()
+        .with_context(|| format!("invalid duration {:?}", value))?;
+    let seconds = match unit {
+        "ns" => amount / 1e9,
+        "us" | "µs" => amount / 1e6,
+        "ms" => amount / 1e3,
+        "s" => amount,
+        other => bail!("unknown duration unit {:?}", other),
+    };
+    Duration::try_from_secs_f64(seconds)
+        .with_context(|| format!("duration {:?} out of range", value))
+}
+
+/// Latency and failure behaviour of the emulator. The default matches the
+/// original fixed 0.5ms round trip with no failures.
+#[derive(Debug, Clone, Default)]
+pub struct SimulationConfig {
+    pub latency: LatencyModel,
+    /// Added per key in the request, on top of the base latency
+    pub per_key_cost: Duration,
+    /// Probability that a round trip fails with an error reply
+    pub error_rate: f64,
+    /// Probability that the connection drops mid-request
+    pub drop_rate: f64,
+    /// Client-side timeout; slower round trips fail after waiting this long
+    pub timeout: Option<Duration>,
+    /// Seeds the sampler for reproducible runs
+    pub seed: Option<u64>,
+}
+
+/// Failure injected into a simulated round trip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulatedFault {
+    Error,
+    Timeout(Duration),
+    ConnectionDropped,
+}
+
+impl fmt::Display for SimulatedFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulatedFault::Error => write!(f, "injected redis error"),
+            SimulatedFault::Timeout(timeout) => {
+                write!(f, "redis request timed out after {:?}", timeout)
+            }
+            SimulatedFault::ConnectionDropped => write!(f, "redis connection dropped"),
+        }
+    }
+}
+
+impl std::error::Error for SimulatedFault {}
+
+/// Outcome of one sampled round trip: how long to wait, then whether it fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct RoundTrip {
+    pub wait: Duration,
+    pub fault: Option<SimulatedFault>,
+}
+
+#[derive(Debug)]
+pub(super) struct Simulator {
+    config: SimulationConfig,
+    rng: StdRng,
+}
+
+impl Simulator {
+    pub fn new(config: SimulationConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+        Self { config, rng }
+    }
+
+    pub fn config(&self) -> &SimulationConfig {
+        &self.config
+    }
+
+    pub fn sample(&mut self, keys: usize) -> RoundTrip {
+        let key_cost = self
+            .config
+            .per_key_cost
+            .saturating_mul(u32::try_from(keys).unwrap_or(u32::MAX));
+        let latency = self
+            .config
+            .latency
+            .sample(&mut self.rng)
+            .saturating_add(key_cost);
+
+        if self.config.drop_rate > 0.0 && self.rng.random_bool(self.config.drop_rate.min(1.0)) {
+            // The drop happens somewhere during the exchange
+            let wait = latency.mul_f64(self.rng.random());
+            return RoundTrip {
+                wait,
+                fault: Some(SimulatedFault::ConnectionDropped),
+            };
+        }
+        if let Some(timeout) = self.config.timeout {
+            if latency > timeout {
+                return RoundTrip {
+                    wait: timeout,
+                    fault: Some(SimulatedFault::Timeout(timeout)),
+                };
+            }
+        }
+        let fault = (self.config.error_rate > 0.0
+            && self.rng.random_bool(self.config.error_rate.min(1.0)))
+        .then_some(SimulatedFault::Error);
+
+        RoundTrip {
+            wait: latency,
+            fault,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn durations() {
+        for (value, expected) in [
+            ("100ns", Duration::from_nanos(100)),
+            ("500us", Duration::from_micros(500)),
+            ("500µs", Duration::from_micros(500)),
+            ("1.5ms", Duration::from_micros(1500)),
+            (" 2s ", Duration::from_secs(2)),
+            ("0ms", Duration::ZERO),
+        ] {
+            assert_eq!(parse_duration(value).unwrap(), expected, "{}", value);
+        }
+
+        for value in [
+            "",
+            "500",
+            "ms",
+            "-1ms",
+            "1.2.3ms",
+            "5m",
+            "1e3ms",
+            "1e30s",
+            "99999999999999999999999s",
+        ] {
+            assert!(parse_duration(value).is_err(), "{:?} parsed", value);
+        }
+    }
+
+    #[test]
+    fn latency_models() {
+        assert_eq!(
+            "fixed:500us".parse::<LatencyModel>().unwrap(),
+            LatencyModel::Fixed(Duration::from_micros(500))
+        );
+        assert_eq!(
+            "normal:2ms,500us".parse::<LatencyModel>().unwrap(),
+            LatencyModel::Normal {
+                mean: ms(2),
+                std_dev: Duration::from_micros(500),
+            }
+        );
+        // Points are sorted by percentile and duplicates dropped
+        assert_eq!(
+            "percentiles:p99=3ms,p50=400us,p99.9=20ms,p50=1ms"
+                .parse::<LatencyModel>()
+                .unwrap(),
+            LatencyModel::Percentiles(vec![
+                (50.0, Duration::from_micros(400)),
+                (99.0, ms(3)),
+                (99.9, ms(20)),
+            ])
+        );
+
+        for value in [
+            "fixed",
+            "fixed:-1ms",
+            "normal:2ms",
+            "normal:2ms,-1ms",
+            "percentiles:p50",
+            "percentiles:p101=1ms",
+            "percentiles:p-1=1ms",
+            "percentiles:p50=-1ms",
+            "percentiles:pfifty=1ms",
+            "uniform:1ms,2ms",
+        ] {
+            assert!(value.parse::<LatencyModel>().is_err(), "{:?} parsed", value);
+        }
+    }
+
+    #[test]
+    fn percentiles_are_interpolated() {
+        let points = [(50.0, ms(1)), (100.0, ms(3))];
+        assert_eq!(interpolate(&points, 10.0), ms(1));
+        assert_eq!(interpolate(&points, 75.0), ms(2));
+        assert_eq!(interpolate(&points, 100.0), ms(3));
+        assert_eq!(interpolate(&[], 50.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn seeded_simulators_are_deterministic() {
+        let config = |seed| SimulationConfig {
+            latency: LatencyModel::Normal {
+                mean: ms(1),
+                std_dev: ms(1),
+            },
+            per_key_cost: Duration::from_micros(10),
+            error_rate: 0.2,
+            drop_rate: 0.1,
+            timeout: Some(ms(2)),
+            seed: Some(seed),
+        };
+        let samples = |seed| {
+            let mut simulator = Simulator::new(config(seed));
+            (0..200)
+                .map(|keys| simulator.sample(keys))
+                .collect::<Vec<_>>()
+        };
+
+        let first = samples(7);
+        assert_eq!(first, samples(7));
+        assert_ne!(first, samples(8));
+        // Every kind of outcome shows up, so the whole sampler is covered
+        assert!(first.iter().any(|trip| trip.fault.is_none()));
+        for fault in [
+            SimulatedFault::Error,
+            SimulatedFault::Timeout(ms(2)),
+            SimulatedFault::ConnectionDropped,
+        ] {
+            assert!(
+                first.iter().any(|trip| trip.fault == Some(fault)),
+                "{}",
+                fault
+            );
+        }
+    }
+}
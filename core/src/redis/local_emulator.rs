@@ -1,23 +1,47 @@
-use crate::queue_entry::QueueEntry;
+use super::latency::{SimulatedFault, SimulationConfig, Simulator};
+use super::population::{Population, PopulationConfig};
+use super::store::{OwnerPage, RelevanceAnswers, RelevanceQuery, RelevanceStore};
 use async_trait::async_trait;
-use fxhash::FxHashSet;
+use fxhash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
 use solana_pubkey::Pubkey;
 use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::broadcast;
 use tokio::time::sleep;
 use tracing::info;
 
+/// Messages buffered per subscriber before slow subscribers start lagging
+const PUBSUB_CAPACITY: usize = 4096;
+
+/// Channel every owner set change is published on, in version order
+pub const OWNER_UPDATES_CHANNEL: &str = "relevance:token_account_owners:updates";
+
 /// Local Redis emulator that simulates the performance characteristics of real Redis
 /// without requiring actual Redis infrastructure
+///
+/// Clones share the same storage, like multiple connections to one Redis server.
 #[derive(Debug, Clone)]
 pub struct LocalRedisEmulator {
     // Simulated storage
-    relevant_account_cache: RelevantAccountCache,
+    relevant_account_cache: Arc<RwLock<RelevantAccountCache>>,
+    pubsub: Arc<PubSub>,
+    simulator: Arc<Mutex<Simulator>>,
 }
 
+/// Relevance data, every set kept separately per network id so a devnet
+/// wallet or vault is never relevant on mainnet
 #[derive(Debug, Clone)]
 pub struct RelevantAccountCache {
-    token_account_owners: Option<FxHashSet<Pubkey>>,
+    token_account_owners: FxHashMap<i32, FxHashSet<Pubkey>>,
+    /// Bumped by every change to `token_account_owners`, on any network
+    owner_set_version: u64,
+    /// Token accounts relevant in their own right, typically vaults whose owner
+    /// is a PDA we don't track
+    token_accounts: FxHashMap<i32, FxHashSet<Pubkey>>,
+    /// Token account -> owner for accounts of relevant owners, learned from
+    /// InitializeAccount instructions
+    token_account_owner_index: FxHashMap<i32, FxHashMap<Pubkey, Pubkey>>,
 }
 
 impl Default for RelevantAccountCache {
@@ -29,53 +53,659 @@ impl Default for RelevantAccountCache {
 impl RelevantAccountCache {
     fn new() -> Self {
         Self {
-            token_account_owners: None,
+            token_account_owners: FxHashMap::default(),
+            owner_set_version: 0,
+            token_accounts: FxHashMap::default(),
+            token_account_owner_index: FxHashMap::default(),
+        }
+    }
+
+    pub fn check_owners(&self, network: i32, owners: &[Pubkey]) -> Vec<bool> {
+        match self.token_account_owners.get(&network) {
+            Some(token_owners) => owners
+                .iter()
+                .map(|owner| token_owners.contains(owner))
+                .collect(),
+            // If no owners are loaded for the network, return all false (no matches)
+            None => vec![false; owners.len()],
+        }
+    }
+
+    pub fn owner_count(&self, network: i32) -> usize {
+        self.token_account_owners
+            .get(&network)
+            .map_or(0, |token_owners| token_owners.len())
+    }
+
+    /// One page of a network's owners, SSCAN style: the cursor is an offset
+    /// into the set's iteration order, so owners added or removed between
+    /// pages may be missed or repeated.
+    ///
+    /// Reaching the offset walks the set from the start, so a page costs
+    /// O(cursor + count) and a full scan is quadratic in the set size. That is
+    /// fine for the occasional admin listing this serves; use `check_owners`
+    /// on hot paths.
+    pub fn list_owners(&self, network: i32, cursor: u64, count: usize) -> OwnerPage {
+        let Some(token_owners) = self.token_account_owners.get(&network) else {
+            return OwnerPage::default();
+        };
+        let start = cursor as usize;
+        let owners: Vec<Pubkey> = token_owners
+            .iter()
+            .skip(start)
+            .take(count)
+            .copied()
+            .collect();
+        let end = start + owners.len();
+        OwnerPage {
+            owners,
+            next_cursor: (end < token_owners.len()).then_some(end as u64),
+        }
+    }
+
+    /// Replaces a network's owner set wholesale
+    pub fn replace_owners(&mut self, network: i32, owners: FxHashSet<Pubkey>) {
+        self.token_account_owners.insert(network, owners);
+    }
+
+    pub fn owner_set_version(&self) -> u64 {
+        self.owner_set_version
+    }
+
+    /// Copy of just the owner set and its version, for seeding a replica
+    pub fn owner_set_snapshot(&self) -> RelevantAccountCache {
+        RelevantAccountCache {
+            token_account_owners: self.token_account_owners.clone(),
+            owner_set_version: self.owner_set_version,
+            ..RelevantAccountCache::new()
+        }
+    }
+
+    /// Applies an update published by the primary and adopts its version
+    pub fn apply_owner_set_update(&mut self, update: &OwnerSetUpdate) {
+        match update.kind {
+            OwnerSetUpdateKind::Added => {
+                self.add_owners(update.network, &update.owners);
+            }
+            OwnerSetUpdateKind::Removed => {
+                self.remove_owners(update.network, &update.owners);
+            }
+            // Carries no owners; replicas resync from a snapshot instead
+            OwnerSetUpdateKind::Reset => {}
+        }
+        self.owner_set_version = update.version;
+    }
+
+    pub fn check_token_accounts(&self, network: i32, token_accounts: &[Pubkey]) -> Vec<bool> {
+        match self.token_accounts.get(&network) {
+            Some(vaults) => token_accounts
+                .iter()
+                .map(|account| vaults.contains(account))
+                .collect(),
+            None => vec![false; token_accounts.len()],
+        }
+    }
+
+    pub fn get_token_account_owners(
+        &self,
+        network: i32,
+        token_accounts: &[Pubkey],
+    ) -> Vec<Option<Pubkey>> {
+        let Some(owner_index) = self.token_account_owner_index.get(&network) else {
+            return vec![None; token_accounts.len()];
+        };
+        token_accounts
+            .iter()
+            .map(|account| owner_index.get(account).copied())
+            .collect()
+    }
+
+    /// Returns how many mappings were newly added, like HSET
+    pub fn store_token_account_owners(
+        &mut self,
+        network: i32,
+        mappings: &[(Pubkey, Pubkey)],
+    ) -> usize {
+        let owner_index = self.token_account_owner_index.entry(network).or_default();
+        mappings
+            .iter()
+            .filter(|(account, owner)| owner_index.insert(*account, *owner).is_none())
+            .count()
+    }
+
+    /// Answers a whole query, including the relevance of owners found through
+    /// the index
+    pub fn answer(&self, network: i32, query: &RelevanceQuery) -> RelevanceAnswers {
+        let mut answers = RelevanceAnswers::from_lookups(
+            query,
+            self.owner_set_version(),
+            self.check_owners(network, query.owners()),
+            self.check_token_accounts(network, query.token_accounts()),
+            self.get_token_account_owners(network, query.indexed_accounts()),
+        );
+        let unchecked = answers.unchecked_indexed_owners(query);
+        answers.add_owner_relevance(&unchecked, self.check_owners(network, &unchecked));
+        answers
+    }
+
+    /// Returns how many owners were newly added, like SADD
+    pub fn add_owners(&mut self, network: i32, owners: &[Pubkey]) -> usize {
+        let token_owners = self.token_account_owners.entry(network).or_default();
+        owners
+            .iter()
+            .filter(|owner| token_owners.insert(**owner))
+            .count()
+    }
+
+    /// Returns how many owners were actually removed, like SREM. Owner index
+    /// entries are left in place; readers re-check the owner against the set.
+    pub fn remove_owners(&mut self, network: i32, owners: &[Pubkey]) -> usize {
+        match self.token_account_owners.get_mut(&network) {
+            Some(token_owners) => owners
+                .iter()
+                .filter(|owner| token_owners.remove(owner))
+                .count(),
+            None => 0,
+        }
+    }
+
+    /// Replaces a network's vault set wholesale
+    pub fn replace_token_accounts(&mut self, network: i32, token_accounts: FxHashSet<Pubkey>) {
+        self.token_accounts.insert(network, token_accounts);
+    }
+
+    pub fn add_token_accounts(&mut self, network: i32, token_accounts: &[Pubkey]) -> usize {
+        let vaults = self.token_accounts.entry(network).or_default();
+        token_accounts
+            .iter()
+            .filter(|account| vaults.insert(**account))
+            .count()
+    }
+
+    pub fn remove_token_accounts(&mut self, network: i32, token_accounts: &[Pubkey]) -> usize {
+        match self.token_accounts.get_mut(&network) {
+            Some(vaults) => token_accounts
+                .iter()
+                .filter(|account| vaults.remove(account))
+                .count(),
+            None => 0,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OwnerSetUpdateKind {
+    Added,
+    Removed,
+    /// The whole set was replaced, e.g. by a bulk load
+    Reset,
+}
+
+/// Notification published on `OWNER_UPDATES_CHANNEL` after each owner set
+/// change. Versions are consecutive, so a gap means an update was missed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnerSetUpdate {
+    pub version: u64,
+    pub network: i32,
+    pub kind: OwnerSetUpdateKind,
+    pub owners: Vec<Pubkey>,
+}
+
+impl OwnerSetUpdate {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("owner set update serializes")
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Self, anyhow::Error> {
+        Ok(serde_json::from_slice(payload)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PubSubMessage {
+    pub channel: String,
+    pub payload: Vec<u8>,
+}
+
+/// In-process stand-in for Redis PUBLISH/SUBSCRIBE. Every subscriber sees every
+/// message and filters by channel itself.
+#[derive(Debug)]
+struct PubSub {
+    sender: broadcast::Sender<PubSubMessage>,
+    subscriber_counts: Mutex<HashMap<String, usize>>,
+}
+
+impl Default for LocalRedisEmulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LocalRedisEmulator {
     pub fn new() -> Self {
+        Self::with_simulation(SimulationConfig::default())
+    }
+
+    pub fn with_simulation(config: SimulationConfig) -> Self {
+        let (sender, _) = broadcast::channel(PUBSUB_CAPACITY);
         Self {
-            relevant_account_cache: RelevantAccountCache::new(),
+            relevant_account_cache: Arc::new(RwLock::new(RelevantAccountCache::new())),
+            pubsub: Arc::new(PubSub {
+                sender,
+                subscriber_counts: Mutex::new(HashMap::new()),
+            }),
+            simulator: Arc::new(Mutex::new(Simulator::new(config))),
         }
     }
 
+    /// Swaps the latency and failure model, re-seeding the sampler
+    pub fn set_simulation(&self, config: SimulationConfig) {
+        *self
+            .simulator
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Simulator::new(config);
+    }
+
+    pub fn simulation(&self) -> SimulationConfig {
+        self.simulator
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .config()
+            .clone()
+    }
+
+    pub(super) fn cache(&self) -> RwLockReadGuard<'_, RelevantAccountCache> {
+        self.relevant_account_cache
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(super) fn cache_mut(&self) -> RwLockWriteGuard<'_, RelevantAccountCache> {
+        self.relevant_account_cache
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Adds or removes owners, bumping the version and publishing the change
+    /// while still holding the write lock so notifications go out in version
+    /// order. Returns how many owners actually changed, like SADD/SREM.
+    pub(super) fn update_owners(
+        &self,
+        kind: OwnerSetUpdateKind,
+        network: i32,
+        owners: &[Pubkey],
+    ) -> usize {
+        let mut cache = self.cache_mut();
+        let changed = match kind {
+            OwnerSetUpdateKind::Added => cache.add_owners(network, owners),
+            OwnerSetUpdateKind::Removed => cache.remove_owners(network, owners),
+            OwnerSetUpdateKind::Reset => 0,
+        };
+        if changed > 0 {
+            cache.owner_set_version += 1;
+            let update = OwnerSetUpdate {
+                version: cache.owner_set_version,
+                network,
+                kind,
+                owners: owners.to_vec(),
+            };
+            self.publish(OWNER_UPDATES_CHANNEL, update.encode());
+        }
+        changed
+    }
+
+    /// Current owner set version, without simulated latency
+    pub fn owner_set_version(&self) -> u64 {
+        self.cache().owner_set_version
+    }
+
+    /// Consistent copy of the owner set and its version, without simulated
+    /// latency. Used to bulk-load replicas.
+    pub fn owner_set_snapshot(&self) -> RelevantAccountCache {
+        self.cache().owner_set_snapshot()
+    }
+
+    /// Copy of all relevance data, e.g. as a fallback for failed lookups
+    pub fn snapshot(&self) -> RelevantAccountCache {
+        self.cache().clone()
+    }
+
+    /// Waits out the latency of one request/response exchange touching `keys`
+    /// keys, then reports any injected failure
+    pub(super) async fn simulate_round_trip(&self, keys: usize) -> Result<(), SimulatedFault> {
+        let round_trip = self
+            .simulator
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .sample(keys);
+        sleep(round_trip.wait).await;
+        match round_trip.fault {
+            Some(fault) => Err(fault),
+            None => Ok(()),
+        }
+    }
+
+    /// Publishes to every subscriber of `channel`, returning how many
+    /// subscriptions there are, like PUBLISH
+    pub fn publish(&self, channel: &str, payload: impl Into<Vec<u8>>) -> usize {
+        let _ = self.pubsub.sender.send(PubSubMessage {
+            channel: channel.to_string(),
+            payload: payload.into(),
+        });
+        self.pubsub
+            .subscriber_counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(channel)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Receives messages on every channel. Callers that want PUBLISH's
+    /// subscriber count to include them register via `track_subscription`.
+    pub fn subscribe(&self) -> broadcast::Receiver<PubSubMessage> {
+        self.pubsub.sender.subscribe()
+    }
+
+    /// Adjusts the subscriber count reported by `publish` for `channel`
+    pub fn track_subscription(&self, channel: &str, subscribed: bool) {
+        let mut counts = self
+            .pubsub
+            .subscriber_counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let count = counts.entry(channel.to_string()).or_insert(0);
+        if subscribed {
+            *count += 1;
+        } else {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// Populate the emulator with realistic data sizes: 125k token owners and
+    /// 25k vaults of seeded random filler
+    pub fn populate_with_realistic_data(&mut self) {
+        self.populate(&PopulationConfig::default());
+    }
+
+    /// Replaces the network's owner and vault sets with the population
+    /// generated from `config`
+    pub fn populate(&mut self, config: &PopulationConfig) -> Population {
+        let population = config.generate();
+
+        let mut cache = self.cache_mut();
+        cache.replace_owners(config.network, population.owners.iter().copied().collect());
+        cache.replace_token_accounts(config.network, population.vaults.iter().copied().collect());
+        cache.owner_set_version += 1;
+        let update = OwnerSetUpdate {
+            version: cache.owner_set_version,
+            network: config.network,
+            kind: OwnerSetUpdateKind::Reset,
+            owners: Vec::new(),
+        };
+        self.publish(OWNER_UPDATES_CHANNEL, update.encode());
+        drop(cache);
+
+        info!(
+            "Populated Redis emulator with {} token owners on network {} ({} listed, {} from capture, {} filler) and {} vaults",
+            population.owners.len(),
+            config.network,
+            population.listed_owners,
+            population.sampled_capture_owners,
+            population.filler_owners,
+            population.vaults.len()
+        );
+        population
+    }
+}
+
+#[async_trait]
+impl RelevanceStore for LocalRedisEmulator {
     /// Batch check token account owners - emulates piping the queries into
     /// redis via a single call
-    pub async fn batch_check_token_account_owners(
+    async fn batch_check_token_account_owners(
         &self,
+        network: i32,
         owners: &[Pubkey],
     ) -> Result<Vec<bool>, anyhow::Error> {
-        // Simulate cache lookup time - 0.5ms for batch operations
-        sleep(Duration::from_micros(500)).await;
+        // Simulate cache lookup time - 0.5ms for batch operations by default
+        self.simulate_round_trip(owners.len()).await?;
 
-        match &self.relevant_account_cache.token_account_owners {
-            Some(token_owners) => Ok(owners
-                .iter()
-                .map(|owner| token_owners.contains(owner))
-                .collect()),
-            None => {
-                // If cache not loaded, return all false (no matches)
-                Ok(vec![false; owners.len()])
-            }
-        }
+        Ok(self.cache().check_owners(network, owners))
     }
 
-    /// Populate the emulator with realistic data sizes
-    pub fn populate_with_realistic_data(&mut self) {
-        // Generate 125k unique token account owners
-        let mut token_owners = FxHashSet::default();
-        for _ in 0..125_000 {
-            token_owners.insert(Pubkey::new_unique());
-        }
+    /// Whole query in one simulated round trip, like a server-side script
+    async fn batch_check(
+        &self,
+        network: i32,
+        query: &RelevanceQuery,
+    ) -> Result<RelevanceAnswers, anyhow::Error> {
+        self.simulate_round_trip(query.len()).await?;
 
-        // Update the cache with realistic data
-        self.relevant_account_cache.token_account_owners = Some(token_owners);
+        Ok(self.cache().answer(network, query))
+    }
 
-        info!("Populated Redis emulator with 125k token owners");
+    /// Batch check token account addresses against the vault set - emulates
+    /// piping the queries into redis via a single call
+    async fn batch_check_token_accounts(
+        &self,
+        network: i32,
+        token_accounts: &[Pubkey],
+    ) -> Result<Vec<bool>, anyhow::Error> {
+        self.simulate_round_trip(token_accounts.len()).await?;
+
+        Ok(self.cache().check_token_accounts(network, token_accounts))
+    }
+
+    /// Batch lookup of owners recorded for token accounts - emulates HMGET on
+    /// the owner index hash
+    async fn batch_get_token_account_owners(
+        &self,
+        network: i32,
+        token_accounts: &[Pubkey],
+    ) -> Result<Vec<Option<Pubkey>>, anyhow::Error> {
+        self.simulate_round_trip(token_accounts.len()).await?;
+
+        Ok(self
+            .cache()
+            .get_token_account_owners(network, token_accounts))
+    }
+
+    /// Record token account -> owner mappings - emulates HSET on the owner
+    /// index hash
+    async fn store_token_account_owners(
+        &self,
+        network: i32,
+        mappings: &[(Pubkey, Pubkey)],
+    ) -> Result<(), anyhow::Error> {
+        self.simulate_round_trip(mappings.len()).await?;
+
+        self.cache_mut()
+            .store_token_account_owners(network, mappings);
+        Ok(())
+    }
+
+    /// Emulates SADD on the network's owner set
+    async fn add_owners(&self, network: i32, owners: &[Pubkey]) -> Result<(), anyhow::Error> {
+        self.simulate_round_trip(owners.len()).await?;
+
+        self.update_owners(OwnerSetUpdateKind::Added, network, owners);
+        Ok(())
+    }
+
+    /// Emulates SREM on the network's owner set
+    async fn remove_owners(&self, network: i32, owners: &[Pubkey]) -> Result<(), anyhow::Error> {
+        self.simulate_round_trip(owners.len()).await?;
+
+        self.update_owners(OwnerSetUpdateKind::Removed, network, owners);
+        Ok(())
+    }
+
+    /// Emulates SSCAN on the network's owner set
+    async fn list_owners(
+        &self,
+        network: i32,
+        cursor: u64,
+        count: usize,
+    ) -> Result<OwnerPage, anyhow::Error> {
+        self.simulate_round_trip(count).await?;
+
+        Ok(self.cache().list_owners(network, cursor, count))
+    }
+
+    /// Emulates SCARD on the network's owner set
+    async fn owner_count(&self, network: i32) -> Result<u64, anyhow::Error> {
+        self.simulate_round_trip(1).await?;
+
+        Ok(self.cache().owner_count(network) as u64)
+    }
+
+    async fn owner_set_version(&self) -> Result<u64, anyhow::Error> {
+        self.simulate_round_trip(1).await?;
+
+        Ok(self.owner_set_version())
     }
 }
 
 // Type alias for backward compatibility
 pub type SmartAccountRedisClient = LocalRedisEmulator;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETWORK: i32 = 1;
+
+    fn owners(count: usize) -> Vec<Pubkey> {
+        (0..count).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    #[tokio::test]
+    async fn paging_through_all_owners() {
+        let emulator = LocalRedisEmulator::new();
+        let added = owners(25);
+        emulator.add_owners(NETWORK, &added).await.unwrap();
+
+        let mut listed = Vec::new();
+        let mut cursor = 0;
+        let mut pages = 0;
+        loop {
+            let page = emulator.list_owners(NETWORK, cursor, 10).await.unwrap();
+            assert!(page.owners.len() <= 10);
+            listed.extend(page.owners);
+            pages += 1;
+            match page.next_cursor {
+                Some(next) => cursor = next,
+                None => break,
+            }
+        }
+
+        assert_eq!(pages, 3);
+        assert_eq!(listed.len(), 25);
+        assert_eq!(
+            listed.into_iter().collect::<FxHashSet<_>>(),
+            added.into_iter().collect::<FxHashSet<_>>()
+        );
+
+        // Past the end and on networks without owners the page is empty
+        let page = emulator.list_owners(NETWORK, 25, 10).await.unwrap();
+        assert!(page.owners.is_empty() && page.next_cursor.is_none());
+        let page = emulator.list_owners(NETWORK + 1, 0, 10).await.unwrap();
+        assert!(page.owners.is_empty() && page.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn owner_count() {
+        let emulator = LocalRedisEmulator::new();
+        let added = owners(3);
+
+        assert_eq!(emulator.owner_count(NETWORK).await.unwrap(), 0);
+        emulator.add_owners(NETWORK, &added).await.unwrap();
+        emulator.add_owners(NETWORK, &added[..1]).await.unwrap();
+        assert_eq!(emulator.owner_count(NETWORK).await.unwrap(), 3);
+        emulator.remove_owners(NETWORK, &added[1..]).await.unwrap();
+        assert_eq!(emulator.owner_count(NETWORK).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn owner_updates_bump_the_version_and_publish() {
+        let emulator = LocalRedisEmulator::new();
+        let mut messages = emulator.subscribe();
+        let [first, second] = [(); 2].map(|_| Pubkey::new_unique());
+
+        let changes = [
+            emulator.update_owners(OwnerSetUpdateKind::Added, NETWORK, &[first, second]),
+            // No change: no version and no message
+            emulator.update_owners(OwnerSetUpdateKind::Added, NETWORK, &[first]),
+            emulator.update_owners(OwnerSetUpdateKind::Removed, NETWORK, &[first]),
+            emulator.update_owners(OwnerSetUpdateKind::Removed, NETWORK, &[first]),
+            emulator.update_owners(OwnerSetUpdateKind::Reset, NETWORK, &[second]),
+        ];
+        assert_eq!(changes, [2, 0, 1, 0, 0]);
+        assert_eq!(emulator.owner_set_version(), 2);
+        assert_eq!(
+            RelevanceStore::owner_set_version(&emulator).await.unwrap(),
+            2
+        );
+
+        let mut updates = Vec::new();
+        while let Ok(message) = messages.try_recv() {
+            assert_eq!(message.channel, OWNER_UPDATES_CHANNEL);
+            updates.push(OwnerSetUpdate::decode(&message.payload).unwrap());
+        }
+        assert_eq!(
+            updates,
+            [
+                OwnerSetUpdate {
+                    version: 1,
+                    network: NETWORK,
+                    kind: OwnerSetUpdateKind::Added,
+                    owners: vec![first, second],
+                },
+                OwnerSetUpdate {
+                    version: 2,
+                    network: NETWORK,
+                    kind: OwnerSetUpdateKind::Removed,
+                    owners: vec![first],
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn networks_are_isolated() {
+        let emulator = LocalRedisEmulator::new();
+        let [owner, vault, account] = [(); 3].map(|_| Pubkey::new_unique());
+        emulator.add_owners(NETWORK, &[owner]).await.unwrap();
+        emulator.cache_mut().add_token_accounts(NETWORK, &[vault]);
+        emulator
+            .store_token_account_owners(NETWORK, &[(account, owner)])
+            .await
+            .unwrap();
+
+        let mut query = RelevanceQuery::default();
+        query.check_owner(owner);
+        query.check_token_account(vault);
+        query.resolve_owner(account);
+
+        let answers = emulator.batch_check(NETWORK, &query).await.unwrap();
+        assert!(answers.is_relevant_owner(&owner));
+        assert!(answers.is_relevant_token_account(&vault));
+        assert_eq!(answers.indexed_owner(&account), Some(owner));
+
+        let other = NETWORK + 1;
+        let answers = emulator.batch_check(other, &query).await.unwrap();
+        assert!(!answers.is_relevant_owner(&owner));
+        assert!(!answers.is_relevant_token_account(&vault));
+        assert_eq!(answers.indexed_owner(&account), None);
+        assert_eq!(emulator.snapshot().answer(other, &query), answers);
+        assert_eq!(emulator.owner_count(other).await.unwrap(), 0);
+
+        // Removing on another network leaves this one alone
+        emulator.remove_owners(other, &[owner]).await.unwrap();
+        emulator.cache_mut().remove_token_accounts(other, &[vault]);
+        let answers = emulator.snapshot().answer(NETWORK, &query);
+        assert!(answers.is_relevant_owner(&owner));
+        assert!(answers.is_relevant_token_account(&vault));
+    }
+}
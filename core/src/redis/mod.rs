@@ -1,6 +1,26 @@
 // mod client;  // Disabled for parsing exercise
 // mod processor;  // Disabled for parsing exercise
+pub mod latency;
 mod local_emulator;
+mod policy;
+pub mod population;
+mod replica;
+pub mod resp;
+mod resp_client;
+mod resp_server;
+mod store;
 
 // Use local emulator for the parsing exercise
-pub use local_emulator::{RelevantAccountCache, SmartAccountRedisClient};
+pub use latency::{LatencyModel, SimulatedFault, SimulationConfig};
+pub use local_emulator::{
+    LocalRedisEmulator, OwnerSetUpdate, OwnerSetUpdateKind, PubSubMessage, RelevantAccountCache,
+    SmartAccountRedisClient, OWNER_UPDATES_CHANNEL,
+};
+pub use policy::{
+    LookupMetrics, LookupMetricsSnapshot, LookupPolicy, PolicyRelevanceStore, RelevanceLookupError,
+};
+pub use population::{Population, PopulationConfig};
+pub use replica::OwnerSetReplica;
+pub use resp_client::RespRedisClient;
+pub use resp_server::RespServerHandle;
+pub use store::{OwnerPage, RelevanceAnswers, RelevanceQuery, RelevanceStore};
@@ -0,0 +1,522 @@
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use solana_pubkey::Pubkey;
+use tokio::time::sleep;
+use tracing::warn;
+
+use super::local_emulator::RelevantAccountCache;
+use super::store::{OwnerPage, RelevanceAnswers, RelevanceQuery, RelevanceStore};
+
+/// What to do when a relevance store call fails
+#[derive(Debug, Clone, Default)]
+pub enum LookupPolicy {
+    /// Surface the error; the transaction fails
+    #[default]
+    Fail,
+    /// Retry with exponential backoff, then surface the error
+    Retry {
+        max_attempts: u32,
+        initial_backoff: Duration,
+        max_backoff: Duration,
+    },
+    /// Answer reads from a local snapshot of the store. Writes can't be
+    /// answered locally and still fail.
+    FallbackToSnapshot(Arc<RelevantAccountCache>),
+}
+
+/// Counters for relevance store calls made through a `PolicyRelevanceStore`
+#[derive(Debug, Default)]
+pub struct LookupMetrics {
+    calls: AtomicU64,
+    failed_attempts: AtomicU64,
+    retries: AtomicU64,
+    snapshot_fallbacks: AtomicU64,
+    errors: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LookupMetricsSnapshot {
+    pub calls: u64,
+    /// Attempts that failed, including ones later retried or answered locally
+    pub failed_attempts: u64,
+    pub retries: u64,
+    pub snapshot_fallbacks: u64,
+    /// Calls that returned an error to the caller
+    pub errors: u64,
+}
+
+impl LookupMetrics {
+    pub fn snapshot(&self) -> LookupMetricsSnapshot {
+        LookupMetricsSnapshot {
+            calls: self.calls.load(Ordering::Relaxed),
+            failed_attempts: self.failed_attempts.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            snapshot_fallbacks: self.snapshot_fallbacks.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+
+    fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Error returned once the policy has given up on a call
+#[derive(Debug)]
+pub struct RelevanceLookupError {
+    pub operation: &'static str,
+    pub attempts: u32,
+    pub source: anyhow::Error,
+}
+
+impl fmt::Display for RelevanceLookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failed after {} attempt(s): {:#}",
+            self.operation, self.attempts, self.source
+        )
+    }
+}
+
+impl std::error::Error for RelevanceLookupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Applies a `LookupPolicy` to every call on the wrapped store
+pub struct PolicyRelevanceStore {
+    inner: Box<dyn RelevanceStore>,
+    policy: LookupPolicy,
+    metrics: Arc<LookupMetrics>,
+}
+
+impl PolicyRelevanceStore {
+    pub fn new(inner: Box<dyn RelevanceStore>, policy: LookupPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            metrics: Arc::new(LookupMetrics::default()),
+        }
+    }
+
+    pub fn metrics(&self) -> Arc<LookupMetrics> {
+        self.metrics.clone()
+    }
+
+    async fn call<'a, T, Fut>(
+        &'a self,
+        operation: &'static str,
+        call: impl Fn(&'a dyn RelevanceStore) -> Fut,
+        fallback: Option<&(dyn Fn(&RelevantAccountCache) -> T + Sync)>,
+    ) -> Result<T, anyhow::Error>
+    where
+        Fut: Future<Output = Result<T, anyhow::Error>>,
+    {
+        LookupMetrics::increment(&self.metrics.calls);
+
+        let mut attempts = 0;
+        let mut backoff = match &self.policy {
+            LookupPolicy::Retry {
+                initial_backoff, ..
+            } => *initial_backoff,
+            _ => Duration::ZERO,
+        };
+        let source = loop {
+            attempts += 1;
+            let error = match call(self.inner.as_ref()).await {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            LookupMetrics::increment(&self.metrics.failed_attempts);
+
+            match &self.policy {
+                LookupPolicy::Retry {
+                    max_attempts,
+                    max_backoff,
+                    ..
+                } if attempts < *max_attempts => {
+                    warn!(
+                        "{} failed (attempt {}), retrying in {:?}: {:#}",
+                        operation, attempts, backoff, error
+                    );
+                    LookupMetrics::increment(&self.metrics.retries);
+                    sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2).min(*max_backoff);
+                }
+                LookupPolicy::FallbackToSnapshot(snapshot) => {
+                    if let Some(fallback) = fallback {
+                        warn!(
+                            "{} failed, answering from local snapshot: {:#}",
+                            operation, error
+                        );
+                        LookupMetrics::increment(&self.metrics.snapshot_fallbacks);
+                        return Ok(fallback(snapshot));
+                    }
+                    break error;
+                }
+                _ => break error,
+            }
+        };
+
+        LookupMetrics::increment(&self.metrics.errors);
+        Err(RelevanceLookupError {
+            operation,
+            attempts,
+            source,
+        }
+        .into())
+    }
+}
+
+#[async_trait]
+impl RelevanceStore for PolicyRelevanceStore {
+    async fn batch_check_token_account_owners(
+        &self,
+        network: i32,
+        owners: &[Pubkey],
+    ) -> Result<Vec<bool>, anyhow::Error> {
+        self.call(
+            "batch_check_token_account_owners",
+            |store| store.batch_check_token_account_owners(network, owners),
+            Some(&|snapshot| snapshot.check_owners(network, owners)),
+        )
+        .await
+    }
+
+    async fn batch_check(
+        &self,
+        network: i32,
+        query: &RelevanceQuery,
+    ) -> Result<RelevanceAnswers, anyhow::Error> {
+        self.call(
+            "batch_check",
+            |store| store.batch_check(network, query),
+            Some(&|snapshot| snapshot.answer(network, query)),
+        )
+        .await
+    }
+
+    async fn batch_check_token_accounts(
+        &self,
+        network: i32,
+        token_accounts: &[Pubkey],
+    ) -> Result<Vec<bool>, anyhow::Error> {
+        self.call(
+            "batch_check_token_accounts",
+            |store| store.batch_check_token_accounts(network, token_accounts),
+            Some(&|snapshot| snapshot.check_token_accounts(network, token_accounts)),
+        )
+        .await
+    }
+
+    async fn batch_get_token_account_owners(
+        &self,
+        network: i32,
+        token_accounts: &[Pubkey],
+    ) -> Result<Vec<Option<Pubkey>>, anyhow::Error> {
+        self.call(
+            "batch_get_token_account_owners",
+            |store| store.batch_get_token_account_owners(network, token_accounts),
+            Some(&|snapshot| snapshot.get_token_account_owners(network, token_accounts)),
+        )
+        .await
+    }
+
+    async fn store_token_account_owners(
+        &self,
+        network: i32,
+        mappings: &[(Pubkey, Pubkey)],
+    ) -> Result<(), anyhow::Error> {
+        self.call(
+            "store_token_account_owners",
+            |store| store.store_token_account_owners(network, mappings),
+            None,
+        )
+        .await
+    }
+
+    async fn add_owners(&self, network: i32, owners: &[Pubkey]) -> Result<(), anyhow::Error> {
+        self.call(
+            "add_owners",
+            |store| store.add_owners(network, owners),
+            None,
+        )
+        .await
+    }
+
+    async fn remove_owners(&self, network: i32, owners: &[Pubkey]) -> Result<(), anyhow::Error> {
+        self.call(
+            "remove_owners",
+            |store| store.remove_owners(network, owners),
+            None,
+        )
+        .await
+    }
+
+    async fn list_owners(
+        &self,
+        network: i32,
+        cursor: u64,
+        count: usize,
+    ) -> Result<OwnerPage, anyhow::Error> {
+        self.call(
+            "list_owners",
+            |store| store.list_owners(network, cursor, count),
+            Some(&|snapshot| snapshot.list_owners(network, cursor, count)),
+        )
+        .await
+    }
+
+    async fn owner_count(&self, network: i32) -> Result<u64, anyhow::Error> {
+        self.call(
+            "owner_count",
+            |store| store.owner_count(network),
+            Some(&|snapshot| snapshot.owner_count(network) as u64),
+        )
+        .await
+    }
+
+    async fn owner_set_version(&self) -> Result<u64, anyhow::Error> {
+        self.call(
+            "owner_set_version",
+            |store| store.owner_set_version(),
+            Some(&|snapshot| snapshot.owner_set_version()),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+
+    use super::*;
+    use crate::parsing::error::ParseError;
+    use crate::parsing::parser_trait::lookup_relevance;
+
+    const NETWORK: i32 = 1;
+
+    /// Fails its first `failures` calls, then answers with nothing relevant
+    struct FailingStore {
+        failures: u32,
+        calls: Arc<AtomicU32>,
+    }
+
+    impl FailingStore {
+        fn new(failures: u32) -> (Self, Arc<AtomicU32>) {
+            let calls = Arc::new(AtomicU32::new(0));
+            let store = Self {
+                failures,
+                calls: calls.clone(),
+            };
+            (store, calls)
+        }
+
+        fn attempt(&self) -> Result<(), anyhow::Error> {
+            let call = self.calls.fetch_add(1, Ordering::Relaxed) + 1;
+            if call <= self.failures {
+                anyhow::bail!("connection refused");
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl RelevanceStore for FailingStore {
+        async fn batch_check_token_account_owners(
+            &self,
+            _network: i32,
+            owners: &[Pubkey],
+        ) -> Result<Vec<bool>, anyhow::Error> {
+            self.attempt().map(|()| vec![false; owners.len()])
+        }
+
+        async fn batch_check(
+            &self,
+            _network: i32,
+            _query: &RelevanceQuery,
+        ) -> Result<RelevanceAnswers, anyhow::Error> {
+            self.attempt().map(|()| RelevanceAnswers::default())
+        }
+
+        async fn batch_check_token_accounts(
+            &self,
+            _network: i32,
+            token_accounts: &[Pubkey],
+        ) -> Result<Vec<bool>, anyhow::Error> {
+            self.attempt().map(|()| vec![false; token_accounts.len()])
+        }
+
+        async fn batch_get_token_account_owners(
+            &self,
+            _network: i32,
+            token_accounts: &[Pubkey],
+        ) -> Result<Vec<Option<Pubkey>>, anyhow::Error> {
+            self.attempt().map(|()| vec![None; token_accounts.len()])
+        }
+
+        async fn store_token_account_owners(
+            &self,
+            _network: i32,
+            _mappings: &[(Pubkey, Pubkey)],
+        ) -> Result<(), anyhow::Error> {
+            self.attempt()
+        }
+
+        async fn add_owners(&self, _network: i32, _owners: &[Pubkey]) -> Result<(), anyhow::Error> {
+            self.attempt()
+        }
+
+        async fn remove_owners(
+            &self,
+            _network: i32,
+            _owners: &[Pubkey],
+        ) -> Result<(), anyhow::Error> {
+            self.attempt()
+        }
+
+        async fn list_owners(
+            &self,
+            _network: i32,
+            _cursor: u64,
+            _count: usize,
+        ) -> Result<OwnerPage, anyhow::Error> {
+            self.attempt().map(|()| OwnerPage::default())
+        }
+
+        async fn owner_count(&self, _network: i32) -> Result<u64, anyhow::Error> {
+            self.attempt().map(|()| 0)
+        }
+
+        async fn owner_set_version(&self) -> Result<u64, anyhow::Error> {
+            self.attempt().map(|()| 0)
+        }
+    }
+
+    fn query(owner: Pubkey) -> RelevanceQuery {
+        let mut query = RelevanceQuery::default();
+        query.check_owner(owner);
+        query
+    }
+
+    fn retry(max_attempts: u32) -> LookupPolicy {
+        LookupPolicy::Retry {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        }
+    }
+
+    #[tokio::test]
+    async fn fail_surfaces_a_relevance_lookup_error() {
+        let (inner, calls) = FailingStore::new(u32::MAX);
+        let store = PolicyRelevanceStore::new(Box::new(inner), LookupPolicy::Fail);
+
+        let err = lookup_relevance(&store, NETWORK, &query(Pubkey::new_unique()))
+            .await
+            .unwrap_err();
+
+        let ParseError::RelevanceLookup { operation, source } = err else {
+            panic!("expected a relevance lookup error, got {}", err);
+        };
+        assert_eq!(operation, "batch_check");
+        let source = source.downcast_ref::<RelevanceLookupError>().unwrap();
+        assert_eq!((source.operation, source.attempts), ("batch_check", 1));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            store.metrics().snapshot(),
+            LookupMetricsSnapshot {
+                calls: 1,
+                failed_attempts: 1,
+                errors: 1,
+                ..LookupMetricsSnapshot::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_gives_up_after_max_attempts() {
+        let (inner, calls) = FailingStore::new(u32::MAX);
+        let store = PolicyRelevanceStore::new(Box::new(inner), retry(3));
+
+        let err = store.owner_count(NETWORK).await.unwrap_err();
+
+        let err = err.downcast_ref::<RelevanceLookupError>().unwrap();
+        assert_eq!((err.operation, err.attempts), ("owner_count", 3));
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+        assert_eq!(
+            store.metrics().snapshot(),
+            LookupMetricsSnapshot {
+                calls: 1,
+                failed_attempts: 3,
+                retries: 2,
+                errors: 1,
+                ..LookupMetricsSnapshot::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_succeeds_within_max_attempts() {
+        let (inner, calls) = FailingStore::new(2);
+        let store = PolicyRelevanceStore::new(Box::new(inner), retry(3));
+
+        assert_eq!(store.owner_count(NETWORK).await.unwrap(), 0);
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+        assert_eq!(
+            store.metrics().snapshot(),
+            LookupMetricsSnapshot {
+                calls: 1,
+                failed_attempts: 2,
+                retries: 2,
+                ..LookupMetricsSnapshot::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn fallback_answers_reads_from_the_snapshot() {
+        let [owner, vault, account] = [(); 3].map(|_| Pubkey::new_unique());
+        let mut snapshot = RelevantAccountCache::default();
+        snapshot.add_owners(NETWORK, &[owner]);
+        snapshot.add_token_accounts(NETWORK, &[vault]);
+        snapshot.store_token_account_owners(NETWORK, &[(account, owner)]);
+        let (inner, _) = FailingStore::new(u32::MAX);
+        let store = PolicyRelevanceStore::new(
+            Box::new(inner),
+            LookupPolicy::FallbackToSnapshot(Arc::new(snapshot)),
+        );
+
+        let mut query = query(owner);
+        query.check_token_account(vault);
+        query.resolve_owner(account);
+        let answers = lookup_relevance(&store, NETWORK, &query).await.unwrap();
+        assert!(answers.is_relevant_owner(&owner));
+        assert!(answers.is_relevant_token_account(&vault));
+        assert_eq!(answers.indexed_owner(&account), Some(owner));
+        assert_eq!(store.owner_count(NETWORK).await.unwrap(), 1);
+        assert_eq!(store.owner_count(NETWORK + 1).await.unwrap(), 0);
+
+        // Writes have no local answer
+        let err = store.add_owners(NETWORK, &[vault]).await.unwrap_err();
+        assert!(err.downcast_ref::<RelevanceLookupError>().is_some());
+
+        assert_eq!(
+            store.metrics().snapshot(),
+            LookupMetricsSnapshot {
+                calls: 4,
+                failed_attempts: 4,
+                snapshot_fallbacks: 3,
+                errors: 1,
+                ..LookupMetricsSnapshot::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use solana_pubkey::Pubkey;
+
+/// What to fill the emulator's owner and vault sets with
+#[derive(Debug, Clone)]
+pub struct PopulationConfig {
+    /// Network whose owner and vault sets are replaced
+    pub network: i32,
+    /// Total owner set size; random filler tops up whatever the other sources
+    /// provide
+    pub owner_count: usize,
+    pub vault_count: usize,
+    /// Owners always included, e.g. loaded with `load_pubkeys`
+    pub owners: Vec<Pubkey>,
+    /// Vaults always included
+    pub vaults: Vec<Pubkey>,
+    /// Real owners seen in the benchmarked capture
+    pub capture_owners: Vec<Pubkey>,
+    /// Fraction of `capture_owners` to include, so a known share of the
+    /// capture passes the relevance filter
+    pub capture_hit_ratio: f64,
+    /// Seeds both the capture sample and the filler
+    pub seed: u64,
+}
+
+impl Default for PopulationConfig {
+    fn default() -> Self {
+        Self {
+            network: 1,
+            owner_count: 125_000,
+            vault_count: 25_000,
+            owners: Vec::new(),
+            vaults: Vec::new(),
+            capture_owners: Vec::new(),
+            capture_hit_ratio: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+/// Contents generated from a `PopulationConfig`
+#[derive(Debug, Clone, Default)]
+pub struct Population {
+    pub owners: Vec<Pubkey>,
+    pub vaults: Vec<Pubkey>,
+    pub listed_owners: usize,
+    pub sampled_capture_owners: usize,
+    pub filler_owners: usize,
+}
+
+impl PopulationConfig {
+    /// Same config, same population
+    pub fn generate(&self) -> Population {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        let mut seen = HashSet::new();
+        let mut owners: Vec<Pubkey> = self
+            .owners
+            .iter()
+            .filter(|owner| seen.insert(**owner))
+            .copied()
+            .collect();
+        let listed_owners = owners.len();
+
+        // Sort before shuffling so the sample doesn't depend on capture order
+        let mut candidates: Vec<Pubkey> = self
+            .capture_owners
+            .iter()
+            .filter(|owner| !seen.contains(*owner))
+            .copied()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        candidates.sort();
+        candidates.shuffle(&mut rng);
+        let sample_size =
+            (candidates.len() as f64 * self.capture_hit_ratio.clamp(0.0, 1.0)).round() as usize;
+        candidates.truncate(sample_size);
+        seen.extend(candidates.iter().copied());
+        owners.extend(candidates);
+        let sampled_capture_owners = owners.len() - listed_owners;
+
+        let filler_owners = fill(&mut owners, &mut seen, self.owner_count, &mut rng);
+
+        let mut vaults: Vec<Pubkey> = self.vaults.clone();
+        let mut seen_vaults: HashSet<Pubkey> = vaults.iter().copied().collect();
+        fill(&mut vaults, &mut seen_vaults, self.vault_count, &mut rng);
+
+        Population {
+            owners,
+            vaults,
+            listed_owners,
+            sampled_capture_owners,
+            filler_owners,
+        }
+    }
+}
+
+/// Tops `keys` up to `count` with random keys, returning how many were added
+fn fill(
+    keys: &mut Vec<Pubkey>,
+    seen: &mut HashSet<Pubkey>,
+    count: usize,
+    rng: &mut StdRng,
+) -> usize {
+    let before = keys.len();
+    while keys.len() < count {
+        let key = Pubkey::new_from_array(rng.random());
+        if seen.insert(key) {
+            keys.push(key);
+        }
+    }
+    keys.len() - before
+}
+
+/// Reads base58 pubkeys from a JSON array of strings, a CSV whose first column
+/// holds the key (a header row is skipped), or one key per line. Blank lines
+/// and `#` comments are ignored.
+pub fn load_pubkeys(path: &Path) -> Result<Vec<Pubkey>, anyhow::Error> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    parse_pubkeys(&content).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+pub fn parse_pubkeys(content: &str) -> Result<Vec<Pubkey>, anyhow::Error> {
+    if content.trim_start().starts_with('[') {
+        let keys: Vec<String> = serde_json::from_str(content)?;
+        return keys
+            .iter()
+            .map(|key| {
+                key.parse()
+                    .with_context(|| format!("invalid pubkey {:?}", key))
+            })
+            .collect();
+    }
+
+    let mut keys = Vec::new();
+    let lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    for (index, line) in lines.enumerate() {
+        let field = line
+            .split(',')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .trim_matches('"');
+        match field.parse::<Pubkey>() {
+            Ok(key) => keys.push(key),
+            // CSV header
+            Err(_) if index == 0 => continue,
+            Err(_) => bail!("invalid pubkey {:?}", field),
+        }
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys<const N: usize>() -> [Pubkey; N] {
+        [(); N].map(|_| Pubkey::new_unique())
+    }
+
+    #[test]
+    fn pubkey_formats() {
+        let [first, second] = keys();
+
+        let json = format!("[\"{}\", \"{}\"]", first, second);
+        assert_eq!(parse_pubkeys(&json).unwrap(), [first, second]);
+
+        let csv = format!(
+            "owner,label\n\"{}\",treasury\n{}, hot wallet\n",
+            first, second
+        );
+        assert_eq!(parse_pubkeys(&csv).unwrap(), [first, second]);
+
+        let lines = format!("# owners\n\n  {}\n{}\n", first, second);
+        assert_eq!(parse_pubkeys(&lines).unwrap(), [first, second]);
+
+        assert!(parse_pubkeys("").unwrap().is_empty());
+        assert!(parse_pubkeys("[\"not a key\"]").is_err());
+        // Only the first row may be a header
+        assert!(parse_pubkeys(&format!("{}\nnot a key\n", first)).is_err());
+    }
+
+    #[test]
+    fn generate_is_reproducible() {
+        let [listed, captured, other_captured, vault] = keys();
+        let config = PopulationConfig {
+            network: 1,
+            owner_count: 50,
+            vault_count: 20,
+            owners: vec![listed, listed],
+            vaults: vec![vault],
+            capture_owners: vec![listed, captured, other_captured, captured],
+            capture_hit_ratio: 0.5,
+            seed: 42,
+        };
+
+        let population = config.generate();
+        assert_eq!(population.owners, config.generate().owners);
+        assert_eq!(population.vaults, config.generate().vaults);
+
+        assert_eq!(population.owners.len(), 50);
+        assert_eq!(population.owners.iter().collect::<HashSet<_>>().len(), 50);
+        assert_eq!(population.owners[0], listed);
+        assert!([captured, other_captured].contains(&population.owners[1]));
+        assert_eq!(
+            (
+                population.listed_owners,
+                population.sampled_capture_owners,
+                population.filler_owners
+            ),
+            (1, 1, 48)
+        );
+        assert_eq!(population.vaults.len(), 20);
+        assert_eq!(population.vaults[0], vault);
+
+        // Capture order doesn't change the sample
+        let mut reordered = config.clone();
+        reordered.capture_owners.reverse();
+        assert_eq!(reordered.generate().owners, population.owners);
+
+        let reseeded = PopulationConfig { seed: 43, ..config };
+        assert_ne!(reseeded.generate().owners, population.owners);
+    }
+}
@@ -0,0 +1,420 @@
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use solana_pubkey::Pubkey;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::local_emulator::{
+    LocalRedisEmulator, OwnerSetUpdate, OwnerSetUpdateKind, PubSubMessage, RelevantAccountCache,
+    OWNER_UPDATES_CHANNEL,
+};
+use super::store::{OwnerPage, RelevanceAnswers, RelevanceQuery, RelevanceStore};
+
+/// In-process copy of the relevant owner set, answering owner checks without a
+/// round trip.
+///
+/// Bulk-loaded from the emulator, then kept current by applying the updates it
+/// publishes on `OWNER_UPDATES_CHANNEL`. Updates carry consecutive versions;
+/// on a gap, a lagged subscription or a reset the replica reloads the snapshot,
+/// so its version only ever reflects a state the primary actually had. Owner
+/// reads are local; every other `RelevanceStore` call goes to the emulator.
+pub struct OwnerSetReplica {
+    upstream: LocalRedisEmulator,
+    cache: Arc<RwLock<RelevantAccountCache>>,
+    version: watch::Receiver<u64>,
+    task: JoinHandle<()>,
+}
+
+impl OwnerSetReplica {
+    /// Subscribes before taking the snapshot, so no update can fall between the
+    /// two
+    pub fn load(upstream: LocalRedisEmulator) -> Self {
+        let updates = upstream.subscribe();
+        let snapshot = upstream.owner_set_snapshot();
+        let (version_sender, version) = watch::channel(snapshot.owner_set_version());
+        let cache = Arc::new(RwLock::new(snapshot));
+
+        info!("Loaded owner set replica at version {}", *version.borrow());
+
+        let task = tokio::spawn(follow_updates(
+            upstream.clone(),
+            cache.clone(),
+            updates,
+            version_sender,
+        ));
+
+        Self {
+            upstream,
+            cache,
+            version,
+            task,
+        }
+    }
+
+    /// Owner set version this replica has applied
+    pub fn version(&self) -> u64 {
+        *self.version.borrow()
+    }
+
+    /// Waits until the replica has applied every update up to `version`, e.g.
+    /// the primary's version observed when a transaction arrived
+    pub async fn wait_for_version(&self, version: u64) -> Result<u64, anyhow::Error> {
+        let mut receiver = self.version.clone();
+        let applied = *receiver
+            .wait_for(|applied| *applied >= version)
+            .await
+            .map_err(|_| anyhow!("owner set replica stopped following updates"))?;
+        Ok(applied)
+    }
+
+    /// Checks owners once the replica is at least at `version`
+    pub async fn check_owners_at(
+        &self,
+        network: i32,
+        owners: &[Pubkey],
+        version: u64,
+    ) -> Result<Vec<bool>, anyhow::Error> {
+        self.wait_for_version(version).await?;
+        Ok(self.cache().check_owners(network, owners))
+    }
+
+    /// Waits for the replica to catch up with the primary's current version
+    async fn catch_up(&self) -> Result<(), anyhow::Error> {
+        self.wait_for_version(self.upstream.owner_set_version())
+            .await?;
+        Ok(())
+    }
+
+    fn cache(&self) -> RwLockReadGuard<'_, RelevantAccountCache> {
+        self.cache.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for OwnerSetReplica {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn write(cache: &RwLock<RelevantAccountCache>) -> RwLockWriteGuard<'_, RelevantAccountCache> {
+    cache.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+async fn follow_updates(
+    upstream: LocalRedisEmulator,
+    cache: Arc<RwLock<RelevantAccountCache>>,
+    mut updates: broadcast::Receiver<PubSubMessage>,
+    version: watch::Sender<u64>,
+) {
+    loop {
+        let message = match updates.recv().await {
+            Ok(message) => message,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!(
+                    "Owner set replica lagged by {} messages, reloading",
+                    skipped
+                );
+                resync(&upstream, &cache, &version);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if message.channel != OWNER_UPDATES_CHANNEL {
+            continue;
+        }
+        let update = match OwnerSetUpdate::decode(&message.payload) {
+            Ok(update) => update,
+            Err(err) => {
+                warn!("Undecodable owner set update, reloading: {}", err);
+                resync(&upstream, &cache, &version);
+                continue;
+            }
+        };
+
+        let current = *version.borrow();
+        if update.version <= current {
+            // Already part of the snapshot we loaded
+            continue;
+        }
+        if update.version != current + 1 || update.kind == OwnerSetUpdateKind::Reset {
+            resync(&upstream, &cache, &version);
+            continue;
+        }
+
+        write(&cache).apply_owner_set_update(&update);
+        version.send_replace(update.version);
+    }
+}
+
+fn resync(
+    upstream: &LocalRedisEmulator,
+    cache: &RwLock<RelevantAccountCache>,
+    version: &watch::Sender<u64>,
+) {
+    let snapshot = upstream.owner_set_snapshot();
+    let snapshot_version = snapshot.owner_set_version();
+    *write(cache) = snapshot;
+    version.send_replace(snapshot_version);
+}
+
+#[async_trait]
+impl RelevanceStore for OwnerSetReplica {
+    /// Answered locally from whatever version the replica has applied
+    async fn batch_check_token_account_owners(
+        &self,
+        network: i32,
+        owners: &[Pubkey],
+    ) -> Result<Vec<bool>, anyhow::Error> {
+        Ok(self.cache().check_owners(network, owners))
+    }
+
+    /// Vaults and the owner index come from upstream in one call; every owner
+    /// check, including owners found through the index, is answered locally
+    async fn batch_check(
+        &self,
+        network: i32,
+        query: &RelevanceQuery,
+    ) -> Result<RelevanceAnswers, anyhow::Error> {
+        let mut upstream_query = RelevanceQuery::default();
+        for token_account in query.token_accounts() {
+            upstream_query.check_token_account(*token_account);
+        }
+        for token_account in query.indexed_accounts() {
+            upstream_query.resolve_owner(*token_account);
+        }
+        let mut answers = if upstream_query.is_empty() {
+            RelevanceAnswers::default()
+        } else {
+            self.upstream.batch_check(network, &upstream_query).await?
+        };
+
+        let cache = self.cache();
+        let owners: Vec<Pubkey> = query
+            .owners()
+            .iter()
+            .copied()
+            .chain(answers.unchecked_indexed_owners(query))
+            .collect();
+        answers.relevant_owners.clear();
+        answers.add_owner_relevance(&owners, cache.check_owners(network, &owners));
+        answers.owner_set_version = cache.owner_set_version();
+        Ok(answers)
+    }
+
+    async fn batch_check_token_accounts(
+        &self,
+        network: i32,
+        token_accounts: &[Pubkey],
+    ) -> Result<Vec<bool>, anyhow::Error> {
+        self.upstream
+            .batch_check_token_accounts(network, token_accounts)
+            .await
+    }
+
+    async fn batch_get_token_account_owners(
+        &self,
+        network: i32,
+        token_accounts: &[Pubkey],
+    ) -> Result<Vec<Option<Pubkey>>, anyhow::Error> {
+        self.upstream
+            .batch_get_token_account_owners(network, token_accounts)
+            .await
+    }
+
+    async fn store_token_account_owners(
+        &self,
+        network: i32,
+        mappings: &[(Pubkey, Pubkey)],
+    ) -> Result<(), anyhow::Error> {
+        self.upstream
+            .store_token_account_owners(network, mappings)
+            .await
+    }
+
+    /// Writes go to the primary; returns once the replica has applied them
+    async fn add_owners(&self, network: i32, owners: &[Pubkey]) -> Result<(), anyhow::Error> {
+        self.upstream.add_owners(network, owners).await?;
+        self.catch_up().await
+    }
+
+    async fn remove_owners(&self, network: i32, owners: &[Pubkey]) -> Result<(), anyhow::Error> {
+        self.upstream.remove_owners(network, owners).await?;
+        self.catch_up().await
+    }
+
+    async fn list_owners(
+        &self,
+        network: i32,
+        cursor: u64,
+        count: usize,
+    ) -> Result<OwnerPage, anyhow::Error> {
+        Ok(self.cache().list_owners(network, cursor, count))
+    }
+
+    async fn owner_count(&self, network: i32) -> Result<u64, anyhow::Error> {
+        Ok(self.cache().owner_count(network) as u64)
+    }
+
+    async fn owner_set_version(&self) -> Result<u64, anyhow::Error> {
+        Ok(self.version())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::redis::PopulationConfig;
+
+    const NETWORK: i32 = 1;
+
+    /// Waits for the replica's version, failing instead of hanging if it
+    /// never gets there
+    async fn caught_up(replica: &OwnerSetReplica, version: u64) {
+        tokio::time::timeout(Duration::from_secs(1), replica.wait_for_version(version))
+            .await
+            .expect("replica caught up within a second")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn load_copies_the_owner_set_and_its_version() {
+        let emulator = LocalRedisEmulator::new();
+        let [owner, vault] = [(); 2].map(|_| Pubkey::new_unique());
+        emulator.add_owners(NETWORK, &[owner]).await.unwrap();
+        emulator.cache_mut().add_token_accounts(NETWORK, &[vault]);
+
+        let replica = OwnerSetReplica::load(emulator.clone());
+
+        assert_eq!(replica.version(), 1);
+        assert_eq!(replica.cache().check_owners(NETWORK, &[owner]), [true]);
+        assert_eq!(replica.cache().check_owners(NETWORK + 1, &[owner]), [false]);
+        // Only owners are replicated; vaults are still asked upstream
+        assert_eq!(
+            replica.cache().check_token_accounts(NETWORK, &[vault]),
+            [false]
+        );
+        assert_eq!(
+            replica
+                .batch_check_token_accounts(NETWORK, &[vault])
+                .await
+                .unwrap(),
+            [true]
+        );
+    }
+
+    #[tokio::test]
+    async fn applies_updates_in_order() {
+        let emulator = LocalRedisEmulator::new();
+        let replica = OwnerSetReplica::load(emulator.clone());
+        let [first, second] = [(); 2].map(|_| Pubkey::new_unique());
+
+        emulator
+            .add_owners(NETWORK, &[first, second])
+            .await
+            .unwrap();
+        emulator.remove_owners(NETWORK, &[first]).await.unwrap();
+        caught_up(&replica, 2).await;
+
+        assert_eq!(replica.version(), 2);
+        assert_eq!(
+            replica.cache().check_owners(NETWORK, &[first, second]),
+            [false, true]
+        );
+
+        // Writes through the replica return once it has applied them
+        replica.add_owners(NETWORK, &[first]).await.unwrap();
+        assert_eq!(replica.version(), 3);
+        assert_eq!(
+            replica
+                .batch_check_token_account_owners(NETWORK, &[first])
+                .await
+                .unwrap(),
+            [true]
+        );
+    }
+
+    #[tokio::test]
+    async fn resyncs_on_a_version_gap() {
+        let emulator = LocalRedisEmulator::new();
+        let replica = OwnerSetReplica::load(emulator.clone());
+        let [missed, next] = [(); 2].map(|_| Pubkey::new_unique());
+
+        // A change whose notification never arrives
+        emulator
+            .cache_mut()
+            .apply_owner_set_update(&OwnerSetUpdate {
+                version: 1,
+                network: NETWORK,
+                kind: OwnerSetUpdateKind::Added,
+                owners: vec![missed],
+            });
+        emulator.add_owners(NETWORK, &[next]).await.unwrap();
+        caught_up(&replica, 2).await;
+
+        // Applying version 2 alone would have lost the missed owner
+        assert_eq!(
+            replica.cache().check_owners(NETWORK, &[missed, next]),
+            [true, true]
+        );
+    }
+
+    #[tokio::test]
+    async fn resyncs_on_a_reset() {
+        let mut emulator = LocalRedisEmulator::new();
+        let replica = OwnerSetReplica::load(emulator.clone());
+        let stale = Pubkey::new_unique();
+        emulator.add_owners(NETWORK, &[stale]).await.unwrap();
+        caught_up(&replica, 1).await;
+
+        let population = emulator.populate(&PopulationConfig {
+            network: NETWORK,
+            owner_count: 10,
+            vault_count: 0,
+            ..PopulationConfig::default()
+        });
+        caught_up(&replica, 2).await;
+
+        assert_eq!(replica.cache().owner_count(NETWORK), 10);
+        assert_eq!(replica.cache().check_owners(NETWORK, &[stale]), [false]);
+        assert!(replica
+            .cache()
+            .check_owners(NETWORK, &population.owners)
+            .into_iter()
+            .all(|relevant| relevant));
+    }
+
+    #[tokio::test]
+    async fn wait_for_version_returns_once_reached() {
+        let emulator = LocalRedisEmulator::new();
+        let replica = OwnerSetReplica::load(emulator.clone());
+        let owner = Pubkey::new_unique();
+
+        // Already reached
+        assert_eq!(replica.wait_for_version(0).await.unwrap(), 0);
+
+        let waiting = replica.wait_for_version(1);
+        tokio::pin!(waiting);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), &mut waiting)
+                .await
+                .is_err(),
+            "returned before the version was reached"
+        );
+
+        emulator.add_owners(NETWORK, &[owner]).await.unwrap();
+        let applied = tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("returned once the version was reached")
+            .unwrap();
+        assert_eq!(applied, 1);
+        assert_eq!(
+            replica.check_owners_at(NETWORK, &[owner], 1).await.unwrap(),
+            [true]
+        );
+    }
+}
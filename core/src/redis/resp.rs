@@ -0,0 +1,178 @@
+//! Minimal RESP2 codec covering the subset of the protocol the relevance store
+//! speaks: simple strings, errors, integers, bulk strings and arrays.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+/// Longest bulk string accepted, Redis's default `proto-max-bulk-len`
+const MAX_BULK_LEN: i64 = 512 * 1024 * 1024;
+
+/// Most elements accepted in one array, Redis's limit for multibulk requests
+/// from unauthenticated clients. Commands and replies of the relevance store
+/// stay far below it.
+const MAX_ARRAY_LEN: i64 = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RespValue {
+    SimpleString(String),
+    Error(String),
+    Integer(i64),
+    /// `None` is the null bulk string
+    BulkString(Option<Vec<u8>>),
+    /// `None` is the null array
+    Array(Option<Vec<RespValue>>),
+}
+
+impl RespValue {
+    pub fn ok() -> Self {
+        RespValue::SimpleString("OK".to_string())
+    }
+
+    pub fn bulk(bytes: impl Into<Vec<u8>>) -> Self {
+        RespValue::BulkString(Some(bytes.into()))
+    }
+
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            RespValue::SimpleString(value) => {
+                out.push(b'+');
+                out.extend_from_slice(value.as_bytes());
+            }
+            RespValue::Error(message) => {
+                out.push(b'-');
+                out.extend_from_slice(message.as_bytes());
+            }
+            RespValue::Integer(value) => {
+                out.push(b':');
+                out.extend_from_slice(value.to_string().as_bytes());
+            }
+            RespValue::BulkString(None) => out.extend_from_slice(b"$-1"),
+            RespValue::BulkString(Some(bytes)) => {
+                out.push(b'$');
+                out.extend_from_slice(bytes.len().to_string().as_bytes());
+                out.extend_from_slice(b"\r\n");
+                out.extend_from_slice(bytes);
+            }
+            RespValue::Array(None) => out.extend_from_slice(b"*-1"),
+            RespValue::Array(Some(values)) => {
+                out.push(b'*');
+                out.extend_from_slice(values.len().to_string().as_bytes());
+                out.extend_from_slice(b"\r\n");
+                for value in values {
+                    value.encode(out);
+                }
+                // Elements terminate themselves
+                return;
+            }
+        }
+        out.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Encodes a command the way clients send them: an array of bulk strings
+pub fn encode_command<A: AsRef<[u8]>>(args: &[A], out: &mut Vec<u8>) {
+    out.push(b'*');
+    out.extend_from_slice(args.len().to_string().as_bytes());
+    out.extend_from_slice(b"\r\n");
+    for arg in args {
+        let arg = arg.as_ref();
+        out.push(b'$');
+        out.extend_from_slice(arg.len().to_string().as_bytes());
+        out.extend_from_slice(b"\r\n");
+        out.extend_from_slice(arg);
+        out.extend_from_slice(b"\r\n");
+    }
+}
+
+fn protocol_error(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line).await? == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with(b"\r\n") {
+        return Err(protocol_error("RESP line not terminated by CRLF"));
+    }
+    line.truncate(line.len() - 2);
+    Ok(Some(line))
+}
+
+fn parse_length(bytes: &[u8]) -> io::Result<i64> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|length| length.parse().ok())
+        .ok_or_else(|| protocol_error("invalid RESP length"))
+}
+
+/// Reads one value. Returns `Ok(None)` on a clean EOF before the first byte.
+pub fn read_value<'a, R: AsyncBufRead + Unpin + Send>(
+    reader: &'a mut R,
+) -> Pin<Box<dyn Future<Output = io::Result<Option<RespValue>>> + Send + 'a>> {
+    Box::pin(async move {
+        let Some(line) = read_line(reader).await? else {
+            return Ok(None);
+        };
+        let (&kind, rest) = line
+            .split_first()
+            .ok_or_else(|| protocol_error("empty RESP line"))?;
+        let text = || String::from_utf8_lossy(rest).into_owned();
+
+        let value = match kind {
+            b'+' => RespValue::SimpleString(text()),
+            b'-' => RespValue::Error(text()),
+            b':' => RespValue::Integer(parse_length(rest)?),
+            b'$' => match parse_length(rest)? {
+                -1 => RespValue::BulkString(None),
+                length if length > MAX_BULK_LEN => {
+                    return Err(protocol_error(format!(
+                        "bulk string of {} bytes exceeds {}",
+                        length, MAX_BULK_LEN
+                    )))
+                }
+                length if length >= 0 => {
+                    let mut bytes = vec![0; length as usize + 2];
+                    reader.read_exact(&mut bytes).await?;
+                    if !bytes.ends_with(b"\r\n") {
+                        return Err(protocol_error("bulk string not terminated by CRLF"));
+                    }
+                    bytes.truncate(length as usize);
+                    RespValue::BulkString(Some(bytes))
+                }
+                _ => return Err(protocol_error("negative bulk string length")),
+            },
+            b'*' => match parse_length(rest)? {
+                -1 => RespValue::Array(None),
+                length if length > MAX_ARRAY_LEN => {
+                    return Err(protocol_error(format!(
+                        "array of {} elements exceeds {}",
+                        length, MAX_ARRAY_LEN
+                    )))
+                }
+                length if length >= 0 => {
+                    let mut values = Vec::with_capacity(length as usize);
+                    for _ in 0..length {
+                        let value = read_value(reader)
+                            .await?
+                            .ok_or_else(|| protocol_error("EOF inside RESP array"))?;
+                        values.push(value);
+                    }
+                    RespValue::Array(Some(values))
+                }
+                _ => return Err(protocol_error("negative array length")),
+            },
+            other => {
+                return Err(protocol_error(format!(
+                    "unexpected RESP type byte {:?}",
+                    other as char
+                )))
+            }
+        };
+        Ok(Some(value))
+    })
+}
@@ -0,0 +1,693 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use async_trait::async_trait;
+use solana_pubkey::Pubkey;
+use tokio::io::{AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use super::local_emulator::{OwnerSetUpdateKind, OWNER_UPDATES_CHANNEL};
+use super::resp::{encode_command, read_value, RespValue};
+use super::store::{OwnerPage, RelevanceAnswers, RelevanceQuery, RelevanceStore};
+
+/// Counter bumped alongside every owner set change, by `OWNER_UPDATE_SCRIPT`
+/// or by the emulator's server on a plain SADD/SREM
+pub(super) const OWNER_SET_VERSION_KEY: &str = "relevance:token_account_owners:version";
+
+/// Adds or removes owners, bumps the version and publishes the update in one
+/// atomic step, like `LocalRedisEmulator::update_owners`.
+///
+/// KEYS: owner set, version counter. ARGV: SADD or SREM, channel, the
+/// `OwnerSetUpdate` JSON without its version, then the owners.
+pub(super) const OWNER_UPDATE_SCRIPT: &str = r#"
+local changed = redis.call(ARGV[1], KEYS[1], unpack(ARGV, 4))
+if changed > 0 then
+    local version = redis.call('INCR', KEYS[2])
+    redis.call('PUBLISH', ARGV[2], '{"version":' .. version .. ',' .. string.sub(ARGV[3], 2))
+end
+return changed
+"#;
+
+/// Recorded owner of each token account and whether that owner is relevant,
+/// so owners found through the index need no second round trip.
+///
+/// KEYS: owner index, owner set. ARGV: the token accounts. Replies with the
+/// owner (nil when unrecorded) and 0 or 1 for each account in turn.
+pub(super) const RESOLVE_OWNERS_SCRIPT: &str = r#"
+local reply = {}
+for i, account in ipairs(ARGV) do
+    local owner = redis.call('HGET', KEYS[1], account)
+    reply[2 * i - 1] = owner
+    reply[2 * i] = owner and redis.call('SISMEMBER', KEYS[2], owner) or 0
+end
+return reply
+"#;
+
+/// Owners per script call, well below Lua's `unpack` limit. Larger updates are
+/// split into several versions sent in one pipeline.
+const OWNER_UPDATE_CHUNK: usize = 1000;
+
+/// How long connecting or a pipelined round trip may take by default
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+type Command = Vec<Vec<u8>>;
+
+/// Relevance data kept under a per-network key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum RelevanceKey {
+    /// Set of relevant owners
+    Owners,
+    /// Set of relevant vault token accounts
+    Vaults,
+    /// Hash of token account -> owner
+    OwnerIndex,
+}
+
+impl RelevanceKey {
+    const ALL: [RelevanceKey; 3] = [
+        RelevanceKey::Owners,
+        RelevanceKey::Vaults,
+        RelevanceKey::OwnerIndex,
+    ];
+
+    fn suffix(self) -> &'static str {
+        match self {
+            RelevanceKey::Owners => "token_account_owners",
+            RelevanceKey::Vaults => "token_accounts",
+            RelevanceKey::OwnerIndex => "token_account_owner_index",
+        }
+    }
+
+    /// `relevance:{network}:{kind}`
+    pub(super) fn key(self, network: i32) -> String {
+        format!("relevance:{}:{}", network, self.suffix())
+    }
+
+    pub(super) fn parse(key: &[u8]) -> Option<(i32, RelevanceKey)> {
+        let (network, suffix) = std::str::from_utf8(key)
+            .ok()?
+            .strip_prefix("relevance:")?
+            .split_once(':')?;
+        let kind = Self::ALL.into_iter().find(|kind| kind.suffix() == suffix)?;
+        Some((network.parse().ok()?, kind))
+    }
+}
+
+/// Relevance store backed by a Redis server (6.2+ for SMISMEMBER).
+///
+/// Uses a single connection; each trait call is one pipelined round trip. The
+/// connection is dropped on any I/O or protocol error or timeout and
+/// re-established on the next call, so a hung server fails requests instead of
+/// blocking every caller.
+pub struct RespRedisClient {
+    address: String,
+    connection: Mutex<Option<BufStream<TcpStream>>>,
+    timeout: Duration,
+}
+
+impl RespRedisClient {
+    /// Connects to `host:port`, optionally given as a `redis://` URL
+    pub async fn connect(address: &str) -> Result<Self, anyhow::Error> {
+        let address = address
+            .strip_prefix("redis://")
+            .unwrap_or(address)
+            .trim_end_matches('/')
+            .to_string();
+        let stream = Self::open(&address, DEFAULT_REQUEST_TIMEOUT).await?;
+
+        Ok(Self {
+            address,
+            connection: Mutex::new(Some(stream)),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+        })
+    }
+
+    /// Bounds reconnects and round trips by `timeout` instead of
+    /// `DEFAULT_REQUEST_TIMEOUT`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn open(address: &str, timeout: Duration) -> Result<BufStream<TcpStream>, anyhow::Error> {
+        let stream = tokio::time::timeout(timeout, TcpStream::connect(address))
+            .await
+            .map_err(|_| anyhow!("connecting to redis at {} timed out", address))?
+            .with_context(|| format!("failed to connect to redis at {}", address))?;
+        stream.set_nodelay(true)?;
+        Ok(BufStream::new(stream))
+    }
+
+    /// Sends all commands in a single write and reads the replies in order, so
+    /// the whole batch costs one round trip
+    pub async fn pipeline(&self, commands: &[Command]) -> Result<Vec<RespValue>, anyhow::Error> {
+        let mut connection = self.connection.lock().await;
+        let stream = match connection.take() {
+            Some(stream) => stream,
+            None => Self::open(&self.address, self.timeout).await?,
+        };
+
+        // A timed out round trip drops the stream with it
+        let (stream, replies) = tokio::time::timeout(self.timeout, round_trip(stream, commands))
+            .await
+            .map_err(|_| anyhow!("redis request timed out after {:?}", self.timeout))??;
+
+        // Only reuse connections that are known to be in sync
+        *connection = Some(stream);
+        Ok(replies)
+    }
+
+    async fn query(&self, command: Command) -> Result<RespValue, anyhow::Error> {
+        let reply = self
+            .pipeline(std::slice::from_ref(&command))
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow!("missing redis reply"))?;
+        match reply {
+            RespValue::Error(message) => Err(anyhow!("redis error: {}", message)),
+            reply => Ok(reply),
+        }
+    }
+
+    async fn check_membership(
+        &self,
+        key: &str,
+        members: &[Pubkey],
+    ) -> Result<Vec<bool>, anyhow::Error> {
+        // SMISMEMBER rejects an empty member list
+        if members.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let reply = self
+            .query(command("SMISMEMBER", key, members.iter().map(pubkey_arg)))
+            .await?;
+        membership_reply(reply, members.len())
+    }
+
+    async fn update_owners(
+        &self,
+        kind: OwnerSetUpdateKind,
+        network: i32,
+        owners: &[Pubkey],
+    ) -> Result<(), anyhow::Error> {
+        let operation = match kind {
+            OwnerSetUpdateKind::Added => "SADD",
+            OwnerSetUpdateKind::Removed => "SREM",
+            OwnerSetUpdateKind::Reset => bail!("owner set resets aren't sent by clients"),
+        };
+        let commands = owners
+            .chunks(OWNER_UPDATE_CHUNK)
+            .map(|chunk| {
+                let update = serde_json::json!({
+                    "network": network,
+                    "kind": kind,
+                    "owners": chunk,
+                });
+                let mut command = vec![
+                    b"EVAL".to_vec(),
+                    OWNER_UPDATE_SCRIPT.as_bytes().to_vec(),
+                    b"2".to_vec(),
+                    RelevanceKey::Owners.key(network).into_bytes(),
+                    OWNER_SET_VERSION_KEY.as_bytes().to_vec(),
+                    operation.as_bytes().to_vec(),
+                    OWNER_UPDATES_CHANNEL.as_bytes().to_vec(),
+                    serde_json::to_vec(&update)?,
+                ];
+                command.extend(chunk.iter().map(pubkey_arg));
+                Ok(command)
+            })
+            .collect::<Result<Vec<Command>, anyhow::Error>>()?;
+        if commands.is_empty() {
+            return Ok(());
+        }
+
+        for reply in self.pipeline(&commands).await? {
+            match reply {
+                RespValue::Integer(_) => {}
+                RespValue::Error(message) => bail!("redis error: {}", message),
+                other => bail!("unexpected owner update reply: {:?}", other),
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn round_trip(
+    mut stream: BufStream<TcpStream>,
+    commands: &[Command],
+) -> Result<(BufStream<TcpStream>, Vec<RespValue>), anyhow::Error> {
+    let mut request = Vec::new();
+    for command in commands {
+        encode_command(command, &mut request);
+    }
+    stream.write_all(&request).await?;
+    stream.flush().await?;
+
+    let mut replies = Vec::with_capacity(commands.len());
+    for _ in commands {
+        let reply = read_value(&mut stream)
+            .await?
+            .ok_or_else(|| anyhow!("redis closed the connection"))?;
+        replies.push(reply);
+    }
+    Ok((stream, replies))
+}
+
+fn pubkey_arg(pubkey: &Pubkey) -> Vec<u8> {
+    pubkey.to_string().into_bytes()
+}
+
+fn command(name: &str, key: &str, args: impl IntoIterator<Item = Vec<u8>>) -> Command {
+    let mut command = vec![name.as_bytes().to_vec(), key.as_bytes().to_vec()];
+    command.extend(args);
+    command
+}
+
+fn membership_reply(reply: RespValue, expected_len: usize) -> Result<Vec<bool>, anyhow::Error> {
+    array_reply(reply, expected_len)?
+        .into_iter()
+        .map(|flag| match flag {
+            RespValue::Integer(flag) => Ok(flag == 1),
+            other => Err(anyhow!("unexpected SMISMEMBER element: {:?}", other)),
+        })
+        .collect()
+}
+
+fn owners_reply(
+    reply: RespValue,
+    expected_len: usize,
+) -> Result<Vec<Option<Pubkey>>, anyhow::Error> {
+    array_reply(reply, expected_len)?
+        .into_iter()
+        .map(|owner| match owner {
+            RespValue::BulkString(None) => Ok(None),
+            RespValue::BulkString(Some(bytes)) => {
+                let owner = std::str::from_utf8(&bytes)?.parse::<Pubkey>()?;
+                Ok(Some(owner))
+            }
+            other => Err(anyhow!("unexpected HMGET element: {:?}", other)),
+        })
+        .collect()
+}
+
+/// Pairs of owner and relevance flag, as replied by `RESOLVE_OWNERS_SCRIPT`
+fn resolved_owners_reply(
+    reply: RespValue,
+    expected_len: usize,
+) -> Result<Vec<(Option<Pubkey>, bool)>, anyhow::Error> {
+    let values = array_reply(reply, expected_len * 2)?;
+    let mut values = values.into_iter();
+    let mut resolved = Vec::with_capacity(expected_len);
+    while let (Some(owner), Some(flag)) = (values.next(), values.next()) {
+        let owner = match owner {
+            RespValue::BulkString(None) => None,
+            RespValue::BulkString(Some(bytes)) => Some(std::str::from_utf8(&bytes)?.parse()?),
+            other => bail!("unexpected resolved owner: {:?}", other),
+        };
+        let RespValue::Integer(flag) = flag else {
+            bail!("unexpected resolved owner flag: {:?}", flag);
+        };
+        resolved.push((owner, flag == 1));
+    }
+    Ok(resolved)
+}
+
+/// A missing version key reads as 0
+fn version_reply(reply: RespValue) -> Result<u64, anyhow::Error> {
+    match reply {
+        RespValue::BulkString(None) => Ok(0),
+        RespValue::BulkString(Some(bytes)) => Ok(std::str::from_utf8(&bytes)?.parse()?),
+        other => Err(anyhow!("unexpected GET reply: {:?}", other)),
+    }
+}
+
+fn array_reply(reply: RespValue, expected_len: usize) -> Result<Vec<RespValue>, anyhow::Error> {
+    match reply {
+        RespValue::Array(Some(values)) if values.len() == expected_len => Ok(values),
+        RespValue::Array(Some(values)) => bail!(
+            "expected {} reply elements, got {}",
+            expected_len,
+            values.len()
+        ),
+        other => bail!("expected an array reply, got {:?}", other),
+    }
+}
+
+#[async_trait]
+impl RelevanceStore for RespRedisClient {
+    async fn batch_check_token_account_owners(
+        &self,
+        network: i32,
+        owners: &[Pubkey],
+    ) -> Result<Vec<bool>, anyhow::Error> {
+        self.check_membership(&RelevanceKey::Owners.key(network), owners)
+            .await
+    }
+
+    /// Pipelines the version read, both membership checks and
+    /// `RESOLVE_OWNERS_SCRIPT` for the indexed accounts into one round trip
+    async fn batch_check(
+        &self,
+        network: i32,
+        query: &RelevanceQuery,
+    ) -> Result<RelevanceAnswers, anyhow::Error> {
+        // The version goes first so it is never newer than the lookups.
+        // SMISMEMBER rejects an empty member list, so empty lookups are skipped.
+        let mut commands = vec![command("GET", OWNER_SET_VERSION_KEY, [])];
+        for (key, members) in [
+            (RelevanceKey::Owners, query.owners()),
+            (RelevanceKey::Vaults, query.token_accounts()),
+        ] {
+            if !members.is_empty() {
+                commands.push(command(
+                    "SMISMEMBER",
+                    &key.key(network),
+                    members.iter().map(pubkey_arg),
+                ));
+            }
+        }
+        if !query.indexed_accounts().is_empty() {
+            let mut script = vec![
+                b"EVAL".to_vec(),
+                RESOLVE_OWNERS_SCRIPT.as_bytes().to_vec(),
+                b"2".to_vec(),
+                RelevanceKey::OwnerIndex.key(network).into_bytes(),
+                RelevanceKey::Owners.key(network).into_bytes(),
+            ];
+            script.extend(query.indexed_accounts().iter().map(pubkey_arg));
+            commands.push(script);
+        }
+        let mut replies = self.pipeline(&commands).await?.into_iter();
+        let owner_set_version = version_reply(
+            replies
+                .next()
+                .ok_or_else(|| anyhow!("missing pipeline reply"))?,
+        )?;
+        // Skipped lookups have no reply
+        let mut next_reply = |keys: &[Pubkey]| -> Result<Option<RespValue>, anyhow::Error> {
+            if keys.is_empty() {
+                return Ok(None);
+            }
+            replies
+                .next()
+                .map(Some)
+                .ok_or_else(|| anyhow!("missing pipeline reply"))
+        };
+
+        let owner_relevance = match next_reply(query.owners())? {
+            Some(reply) => membership_reply(reply, query.owners().len())?,
+            None => Vec::new(),
+        };
+        let token_account_relevance = match next_reply(query.token_accounts())? {
+            Some(reply) => membership_reply(reply, query.token_accounts().len())?,
+            None => Vec::new(),
+        };
+        let resolved = match next_reply(query.indexed_accounts())? {
+            Some(reply) => resolved_owners_reply(reply, query.indexed_accounts().len())?,
+            None => Vec::new(),
+        };
+        let indexed_owners = resolved.iter().map(|(owner, _)| *owner).collect();
+        let mut answers = RelevanceAnswers::from_lookups(
+            query,
+            owner_set_version,
+            owner_relevance,
+            token_account_relevance,
+            indexed_owners,
+        );
+        let (resolved_owners, resolved_relevance): (Vec<Pubkey>, Vec<bool>) = resolved
+            .into_iter()
+            .filter_map(|(owner, is_relevant)| Some((owner?, is_relevant)))
+            .unzip();
+        answers.add_owner_relevance(&resolved_owners, resolved_relevance);
+        Ok(answers)
+    }
+
+    async fn batch_check_token_accounts(
+        &self,
+        network: i32,
+        token_accounts: &[Pubkey],
+    ) -> Result<Vec<bool>, anyhow::Error> {
+        self.check_membership(&RelevanceKey::Vaults.key(network), token_accounts)
+            .await
+    }
+
+    async fn batch_get_token_account_owners(
+        &self,
+        network: i32,
+        token_accounts: &[Pubkey],
+    ) -> Result<Vec<Option<Pubkey>>, anyhow::Error> {
+        if token_accounts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let reply = self
+            .query(command(
+                "HMGET",
+                &RelevanceKey::OwnerIndex.key(network),
+                token_accounts.iter().map(pubkey_arg),
+            ))
+            .await?;
+        owners_reply(reply, token_accounts.len())
+    }
+
+    async fn store_token_account_owners(
+        &self,
+        network: i32,
+        mappings: &[(Pubkey, Pubkey)],
+    ) -> Result<(), anyhow::Error> {
+        if mappings.is_empty() {
+            return Ok(());
+        }
+
+        let fields = mappings
+            .iter()
+            .flat_map(|(account, owner)| [pubkey_arg(account), pubkey_arg(owner)]);
+        match self
+            .query(command(
+                "HSET",
+                &RelevanceKey::OwnerIndex.key(network),
+                fields,
+            ))
+            .await?
+        {
+            RespValue::Integer(_) => Ok(()),
+            other => Err(anyhow!("unexpected HSET reply: {:?}", other)),
+        }
+    }
+
+    async fn add_owners(&self, network: i32, owners: &[Pubkey]) -> Result<(), anyhow::Error> {
+        self.update_owners(OwnerSetUpdateKind::Added, network, owners)
+            .await
+    }
+
+    async fn remove_owners(&self, network: i32, owners: &[Pubkey]) -> Result<(), anyhow::Error> {
+        self.update_owners(OwnerSetUpdateKind::Removed, network, owners)
+            .await
+    }
+
+    async fn list_owners(
+        &self,
+        network: i32,
+        cursor: u64,
+        count: usize,
+    ) -> Result<OwnerPage, anyhow::Error> {
+        let reply = self
+            .query(command(
+                "SSCAN",
+                &RelevanceKey::Owners.key(network),
+                [
+                    cursor.to_string().into_bytes(),
+                    b"COUNT".to_vec(),
+                    count.to_string().into_bytes(),
+                ],
+            ))
+            .await?;
+        let mut reply = array_reply(reply, 2)?.into_iter();
+        let (Some(RespValue::BulkString(Some(next_cursor))), Some(members)) =
+            (reply.next(), reply.next())
+        else {
+            bail!("unexpected SSCAN reply");
+        };
+        let next_cursor: u64 = std::str::from_utf8(&next_cursor)?.parse()?;
+        let owners = match members {
+            RespValue::Array(Some(members)) => members
+                .into_iter()
+                .map(|member| match member {
+                    RespValue::BulkString(Some(bytes)) => {
+                        Ok(std::str::from_utf8(&bytes)?.parse::<Pubkey>()?)
+                    }
+                    other => Err(anyhow!("unexpected SSCAN element: {:?}", other)),
+                })
+                .collect::<Result<Vec<_>, anyhow::Error>>()?,
+            other => bail!("unexpected SSCAN members: {:?}", other),
+        };
+
+        Ok(OwnerPage {
+            owners,
+            // Redis signals the end of a scan with cursor 0
+            next_cursor: (next_cursor != 0).then_some(next_cursor),
+        })
+    }
+
+    async fn owner_count(&self, network: i32) -> Result<u64, anyhow::Error> {
+        match self
+            .query(command("SCARD", &RelevanceKey::Owners.key(network), []))
+            .await?
+        {
+            RespValue::Integer(count) => Ok(count as u64),
+            other => Err(anyhow!("unexpected SCARD reply: {:?}", other)),
+        }
+    }
+
+    async fn owner_set_version(&self) -> Result<u64, anyhow::Error> {
+        version_reply(
+            self.query(command("GET", OWNER_SET_VERSION_KEY, []))
+                .await?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis::latency::{LatencyModel, SimulationConfig};
+    use crate::redis::LocalRedisEmulator;
+
+    const NETWORK: i32 = 1;
+
+    #[tokio::test]
+    async fn lookup_replies_are_matched_to_their_keys() {
+        let emulator = LocalRedisEmulator::new();
+        let server = emulator.serve("127.0.0.1:0").await.unwrap();
+        let client = RespRedisClient::connect(&server.url()).await.unwrap();
+
+        let [owner, stranger, vault, owned, unowned, unknown] =
+            [(); 6].map(|_| Pubkey::new_unique());
+        client.add_owners(NETWORK, &[owner]).await.unwrap();
+        emulator.cache_mut().add_token_accounts(NETWORK, &[vault]);
+        emulator
+            .cache_mut()
+            .store_token_account_owners(NETWORK, &[(owned, owner), (unowned, stranger)]);
+
+        let mut query = RelevanceQuery::default();
+        for key in [stranger, owner] {
+            query.check_owner(key);
+        }
+        for key in [unknown, vault] {
+            query.check_token_account(key);
+        }
+        for key in [unknown, unowned, owned] {
+            query.resolve_owner(key);
+        }
+        let answers = client.batch_check(NETWORK, &query).await.unwrap();
+
+        assert_eq!(answers.owner_set_version, 1);
+        assert!(answers.is_relevant_owner(&owner));
+        assert!(!answers.is_relevant_owner(&stranger));
+        assert!(answers.is_relevant_token_account(&vault));
+        assert!(!answers.is_relevant_token_account(&unknown));
+        assert_eq!(answers.indexed_owner(&owned), Some(owner));
+        assert_eq!(answers.indexed_owner(&unowned), Some(stranger));
+        assert_eq!(answers.indexed_owner(&unknown), None);
+
+        // Lookups skipped for lack of keys don't shift the remaining replies
+        let mut query = RelevanceQuery::default();
+        query.resolve_owner(owned);
+        let answers = client.batch_check(NETWORK, &query).await.unwrap();
+        assert_eq!(answers.indexed_owner(&owned), Some(owner));
+        assert!(answers.is_relevant_owner(&owner));
+    }
+
+    #[tokio::test]
+    async fn error_replies() {
+        let emulator = LocalRedisEmulator::new();
+        let server = emulator.serve("127.0.0.1:0").await.unwrap();
+        let client = RespRedisClient::connect(&server.url()).await.unwrap();
+
+        let replies = client
+            .pipeline(&[
+                vec![b"PING".to_vec()],
+                vec![b"FLUSHALL".to_vec()],
+                vec![b"PING".to_vec()],
+            ])
+            .await
+            .unwrap();
+        assert_eq!(
+            replies,
+            [
+                RespValue::SimpleString("PONG".to_string()),
+                RespValue::Error("ERR unknown command 'FLUSHALL'".to_string()),
+                RespValue::SimpleString("PONG".to_string()),
+            ]
+        );
+
+        let err = client.query(vec![b"FLUSHALL".to_vec()]).await.unwrap_err();
+        assert!(err.to_string().contains("unknown command"), "{}", err);
+
+        // Injected errors fail the call and leave the connection usable
+        emulator.set_simulation(SimulationConfig {
+            error_rate: 1.0,
+            ..SimulationConfig::default()
+        });
+        let err = client.owner_count(NETWORK).await.unwrap_err();
+        assert!(err.to_string().contains("injected redis error"), "{}", err);
+        emulator.set_simulation(SimulationConfig::default());
+        assert_eq!(client.owner_count(NETWORK).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_a_dropped_connection() {
+        let emulator = LocalRedisEmulator::new();
+        let server = emulator.serve("127.0.0.1:0").await.unwrap();
+        let client = RespRedisClient::connect(&server.url()).await.unwrap();
+        let owner = Pubkey::new_unique();
+        client.add_owners(NETWORK, &[owner]).await.unwrap();
+
+        emulator.set_simulation(SimulationConfig {
+            drop_rate: 1.0,
+            ..SimulationConfig::default()
+        });
+        let err = client.owner_count(NETWORK).await.unwrap_err();
+        assert!(err.to_string().contains("closed the connection"), "{}", err);
+
+        emulator.set_simulation(SimulationConfig::default());
+        assert_eq!(client.owner_count(NETWORK).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn timed_out_replies_are_not_read_by_the_next_call() {
+        let emulator = LocalRedisEmulator::new();
+        let server = emulator.serve("127.0.0.1:0").await.unwrap();
+        let client = RespRedisClient::connect(&server.url())
+            .await
+            .unwrap()
+            .with_timeout(Duration::from_millis(100));
+        let owner = Pubkey::new_unique();
+        client.add_owners(NETWORK, &[owner]).await.unwrap();
+
+        emulator.set_simulation(SimulationConfig {
+            latency: LatencyModel::Fixed(Duration::from_millis(300)),
+            ..SimulationConfig::default()
+        });
+        let err = client
+            .batch_check_token_account_owners(NETWORK, &[owner])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+
+        // The SMISMEMBER reply arrives on the abandoned connection, so SCARD
+        // gets its own integer reply rather than that array
+        emulator.set_simulation(SimulationConfig::default());
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(client.owner_count(NETWORK).await.unwrap(), 1);
+        assert_eq!(
+            client
+                .batch_check_token_account_owners(NETWORK, &[owner])
+                .await
+                .unwrap(),
+            [true]
+        );
+    }
+}
@@ -0,0 +1,894 @@
+use std::collections::HashSet;
+use std::io;
+use std::net::SocketAddr;
+
+use anyhow::Context;
+use solana_pubkey::Pubkey;
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, warn};
+
+use super::latency::SimulatedFault;
+use super::local_emulator::{LocalRedisEmulator, PubSubMessage};
+use super::local_emulator::{OwnerSetUpdateKind, OWNER_UPDATES_CHANNEL};
+use super::resp::{read_value, RespValue};
+use super::resp_client::{
+    RelevanceKey, OWNER_SET_VERSION_KEY, OWNER_UPDATE_SCRIPT, RESOLVE_OWNERS_SCRIPT,
+};
+
+type Command = Vec<Vec<u8>>;
+
+/// Large enough that a typical pipeline lands in a single read and pays the
+/// simulated latency once
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Running RESP server; stops accepting and drops every connection when dropped
+pub struct RespServerHandle {
+    local_addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl RespServerHandle {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// `redis://` URL for clients
+    pub fn url(&self) -> String {
+        format!("redis://{}", self.local_addr)
+    }
+}
+
+impl Drop for RespServerHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl LocalRedisEmulator {
+    /// Exposes the emulator as a TCP server speaking the RESP subset used by
+    /// `RespRedisClient`: PING, SISMEMBER, SMISMEMBER, SADD, SREM, SSCAN, SCARD,
+    /// GET, HMGET, HSET, EVAL (the client's scripts only), PUBLISH, SUBSCRIBE
+    /// and UNSUBSCRIBE. Bind to port 0 for an ephemeral port.
+    ///
+    /// The simulated round trip latency is paid once per pipelined batch, so a
+    /// client sending N commands in one write waits as long as for one.
+    pub async fn serve(&self, address: &str) -> Result<RespServerHandle, anyhow::Error> {
+        let listener = TcpListener::bind(address)
+            .await
+            .with_context(|| format!("failed to bind RESP server to {}", address))?;
+        let local_addr = listener.local_addr()?;
+        let emulator = self.clone();
+
+        let task = tokio::spawn(async move {
+            // Owning the connections here means aborting the server drops them too
+            let mut connections = JoinSet::new();
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, peer)) => {
+                            let emulator = emulator.clone();
+                            connections.spawn(async move {
+                                if let Err(err) = handle_connection(emulator, stream).await {
+                                    debug!("RESP connection {} closed: {}", peer, err);
+                                }
+                            });
+                        }
+                        Err(err) => warn!("RESP server failed to accept: {}", err),
+                    },
+                    Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                }
+            }
+        });
+
+        Ok(RespServerHandle { local_addr, task })
+    }
+}
+
+/// Reads commands until the client hangs up, grouping whatever arrived in the
+/// same read (i.e. one pipeline) into a batch
+async fn read_batches(
+    mut reader: BufReader<OwnedReadHalf>,
+    batches: mpsc::Sender<io::Result<Vec<RespValue>>>,
+) {
+    loop {
+        let first = match read_value(&mut reader).await {
+            Ok(Some(first)) => first,
+            Ok(None) => return,
+            Err(err) => {
+                let _ = batches.send(Err(err)).await;
+                return;
+            }
+        };
+
+        let mut batch = vec![first];
+        while !reader.buffer().is_empty() {
+            match read_value(&mut reader).await {
+                Ok(Some(value)) => batch.push(value),
+                Ok(None) => break,
+                Err(err) => {
+                    let _ = batches.send(Err(err)).await;
+                    return;
+                }
+            }
+        }
+
+        if batches.send(Ok(batch)).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn handle_connection(
+    emulator: LocalRedisEmulator,
+    stream: TcpStream,
+) -> Result<(), anyhow::Error> {
+    stream.set_nodelay(true)?;
+    let (read_half, mut writer) = stream.into_split();
+
+    // Reading runs in its own task so a pub/sub message arriving mid-command
+    // can't interrupt a partially read value
+    let (batch_sender, mut batch_receiver) = mpsc::channel(16);
+    let reader = tokio::spawn(read_batches(
+        BufReader::with_capacity(READ_BUFFER_SIZE, read_half),
+        batch_sender,
+    ));
+
+    let mut connection = Connection {
+        emulator: emulator.clone(),
+        channels: HashSet::new(),
+        messages: None,
+    };
+    let result = async {
+        loop {
+            tokio::select! {
+                batch = batch_receiver.recv() => {
+                    // The reader hangs up once the client does
+                    let Some(batch) = batch else {
+                        return Ok(());
+                    };
+                    let batch = batch?;
+
+                    let keys = batch.iter().map(command_keys).sum();
+                    let mut out = Vec::new();
+                    match emulator.simulate_round_trip(keys).await {
+                        Ok(()) => {}
+                        // Hang up without replying, like a dead server would
+                        Err(SimulatedFault::ConnectionDropped) => return Ok(()),
+                        Err(fault) => {
+                            for _ in &batch {
+                                RespValue::Error(format!("ERR {}", fault)).encode(&mut out);
+                            }
+                            writer.write_all(&out).await?;
+                            continue;
+                        }
+                    }
+                    for command in batch {
+                        for reply in connection.execute(command) {
+                            reply.encode(&mut out);
+                        }
+                    }
+                    writer.write_all(&out).await?;
+                }
+                message = next_message(&mut connection.messages), if connection.messages.is_some() => match message {
+                    Ok(message) => {
+                        if let Some(push) = connection.push(message) {
+                            let mut out = Vec::new();
+                            push.encode(&mut out);
+                            writer.write_all(&out).await?;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("RESP subscriber lagged, dropped {} messages", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+            }
+        }
+    }
+    .await;
+
+    reader.abort();
+    for channel in connection.channels.drain() {
+        emulator.track_subscription(&channel, false);
+    }
+    result
+}
+
+/// Waits for the next published message; never resolves without a receiver
+async fn next_message(
+    messages: &mut Option<broadcast::Receiver<PubSubMessage>>,
+) -> Result<PubSubMessage, broadcast::error::RecvError> {
+    match messages {
+        Some(messages) => messages.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Per-connection state
+struct Connection {
+    emulator: LocalRedisEmulator,
+    channels: HashSet<String>,
+    /// Held only while subscribed to a channel, so messages published before
+    /// SUBSCRIBE are never delivered or buffered
+    messages: Option<broadcast::Receiver<PubSubMessage>>,
+}
+
+impl Connection {
+    /// Runs one command. SUBSCRIBE and UNSUBSCRIBE reply once per channel, so
+    /// this can produce more than one reply.
+    fn execute(&mut self, command: RespValue) -> Vec<RespValue> {
+        let command = match parse_command(command) {
+            Ok(command) => command,
+            Err(message) => return vec![RespValue::Error(message)],
+        };
+        let name = String::from_utf8_lossy(&command[0]).to_ascii_uppercase();
+        let args = &command[1..];
+
+        match name.as_str() {
+            "SUBSCRIBE" => self.subscribe(args),
+            "UNSUBSCRIBE" => self.unsubscribe(args),
+            _ => vec![self
+                .execute_simple(&name, args)
+                .unwrap_or_else(RespValue::Error)],
+        }
+    }
+
+    fn execute_simple(&self, name: &str, args: &[Vec<u8>]) -> Result<RespValue, String> {
+        match name {
+            "PING" => Ok(match args {
+                [] => RespValue::SimpleString("PONG".to_string()),
+                [message] => RespValue::bulk(message.clone()),
+                _ => return Err(wrong_arity(name)),
+            }),
+            "SISMEMBER" => {
+                let [key, member] = args else {
+                    return Err(wrong_arity(name));
+                };
+                let flags = self.check_membership(key, &[parse_pubkey(member)?])?;
+                Ok(RespValue::Integer(flags[0] as i64))
+            }
+            "SMISMEMBER" => {
+                let [key, members @ ..] = args else {
+                    return Err(wrong_arity(name));
+                };
+                if members.is_empty() {
+                    return Err(wrong_arity(name));
+                }
+                let flags = self.check_membership(key, &parse_pubkeys(members)?)?;
+                Ok(RespValue::Array(Some(
+                    flags
+                        .into_iter()
+                        .map(|flag| RespValue::Integer(flag as i64))
+                        .collect(),
+                )))
+            }
+            "SADD" | "SREM" => {
+                let [key, members @ ..] = args else {
+                    return Err(wrong_arity(name));
+                };
+                if members.is_empty() {
+                    return Err(wrong_arity(name));
+                }
+                let members = parse_pubkeys(members)?;
+                let changed = match (set_key(key)?, name) {
+                    // Owner set changes bump the version and notify subscribers
+                    (SetKey::TokenAccountOwners(network), "SADD") => {
+                        self.emulator
+                            .update_owners(OwnerSetUpdateKind::Added, network, &members)
+                    }
+                    (SetKey::TokenAccountOwners(network), _) => {
+                        self.emulator
+                            .update_owners(OwnerSetUpdateKind::Removed, network, &members)
+                    }
+                    (SetKey::TokenAccounts(network), "SADD") => self
+                        .emulator
+                        .cache_mut()
+                        .add_token_accounts(network, &members),
+                    (SetKey::TokenAccounts(network), _) => self
+                        .emulator
+                        .cache_mut()
+                        .remove_token_accounts(network, &members),
+                };
+                Ok(RespValue::Integer(changed as i64))
+            }
+            "SSCAN" => {
+                let [key, cursor, options @ ..] = args else {
+                    return Err(wrong_arity(name));
+                };
+                let network = owner_set_key(key)?;
+                let cursor: u64 = std::str::from_utf8(cursor)
+                    .ok()
+                    .and_then(|cursor| cursor.parse().ok())
+                    .ok_or_else(|| "ERR invalid cursor".to_string())?;
+                // Redis' default COUNT
+                let count = match options {
+                    [] => 10,
+                    [option, count] if option.eq_ignore_ascii_case(b"COUNT") => {
+                        std::str::from_utf8(count)
+                            .ok()
+                            .and_then(|count| count.parse().ok())
+                            .ok_or_else(|| "ERR value is not an integer".to_string())?
+                    }
+                    _ => return Err("ERR syntax error".to_string()),
+                };
+                let page = self.emulator.cache().list_owners(network, cursor, count);
+                Ok(RespValue::Array(Some(vec![
+                    RespValue::bulk(page.next_cursor.unwrap_or(0).to_string()),
+                    RespValue::Array(Some(
+                        page.owners
+                            .iter()
+                            .map(|owner| RespValue::bulk(owner.to_string()))
+                            .collect(),
+                    )),
+                ])))
+            }
+            "SCARD" => {
+                let [key] = args else {
+                    return Err(wrong_arity(name));
+                };
+                let network = owner_set_key(key)?;
+                let count = self.emulator.cache().owner_count(network);
+                Ok(RespValue::Integer(count as i64))
+            }
+            "GET" => {
+                let [key] = args else {
+                    return Err(wrong_arity(name));
+                };
+                if key.as_slice() != OWNER_SET_VERSION_KEY.as_bytes() {
+                    // Nothing else is stored as a plain string
+                    return Ok(RespValue::BulkString(None));
+                }
+                let version = self.emulator.owner_set_version();
+                Ok(RespValue::bulk(version.to_string()))
+            }
+            "HMGET" => {
+                let [key, fields @ ..] = args else {
+                    return Err(wrong_arity(name));
+                };
+                if fields.is_empty() {
+                    return Err(wrong_arity(name));
+                }
+                let network = hash_key(key)?;
+                let owners = self
+                    .emulator
+                    .cache()
+                    .get_token_account_owners(network, &parse_pubkeys(fields)?);
+                Ok(RespValue::Array(Some(
+                    owners
+                        .into_iter()
+                        .map(|owner| RespValue::BulkString(owner.map(|o| o.to_string().into())))
+                        .collect(),
+                )))
+            }
+            "HSET" => {
+                let [key, pairs @ ..] = args else {
+                    return Err(wrong_arity(name));
+                };
+                if pairs.is_empty() || pairs.len() % 2 != 0 {
+                    return Err(wrong_arity(name));
+                }
+                let network = hash_key(key)?;
+                let mappings = pairs
+                    .chunks_exact(2)
+                    .map(|pair| Ok((parse_pubkey(&pair[0])?, parse_pubkey(&pair[1])?)))
+                    .collect::<Result<Vec<_>, String>>()?;
+                let added = self
+                    .emulator
+                    .cache_mut()
+                    .store_token_account_owners(network, &mappings);
+                Ok(RespValue::Integer(added as i64))
+            }
+            // Only the client's scripts are known; they run against the
+            // emulator directly
+            "EVAL" => {
+                let [script, key_count, args @ ..] = args else {
+                    return Err(wrong_arity(name));
+                };
+                let key_count = std::str::from_utf8(key_count)
+                    .ok()
+                    .and_then(|count| count.parse::<usize>().ok())
+                    .filter(|count| *count <= args.len())
+                    .ok_or_else(|| "ERR invalid number of keys".to_string())?;
+                let (keys, args) = args.split_at(key_count);
+                if script.as_slice() == OWNER_UPDATE_SCRIPT.as_bytes() {
+                    self.eval_owner_update(keys, args)
+                } else if script.as_slice() == RESOLVE_OWNERS_SCRIPT.as_bytes() {
+                    self.eval_resolve_owners(keys, args)
+                } else {
+                    Err("NOSCRIPT only the relevance store's scripts are supported".to_string())
+                }
+            }
+            "PUBLISH" => {
+                let [channel, payload] = args else {
+                    return Err(wrong_arity(name));
+                };
+                let receivers = self
+                    .emulator
+                    .publish(&String::from_utf8_lossy(channel), payload.clone());
+                Ok(RespValue::Integer(receivers as i64))
+            }
+            _ => Err(format!("ERR unknown command '{}'", name)),
+        }
+    }
+
+    /// `OWNER_UPDATE_SCRIPT` as the emulator's own owner update, which
+    /// publishes the same payload
+    fn eval_owner_update(&self, keys: &[Vec<u8>], args: &[Vec<u8>]) -> Result<RespValue, String> {
+        let ([owner_set, version_key], [operation, channel, _update, owners @ ..]) = (keys, args)
+        else {
+            return Err(wrong_arity("EVAL"));
+        };
+        if version_key.as_slice() != OWNER_SET_VERSION_KEY.as_bytes()
+            || channel.as_slice() != OWNER_UPDATES_CHANNEL.as_bytes()
+        {
+            return Err("ERR unexpected owner update script arguments".to_string());
+        }
+        let kind = match operation.to_ascii_uppercase().as_slice() {
+            b"SADD" => OwnerSetUpdateKind::Added,
+            b"SREM" => OwnerSetUpdateKind::Removed,
+            _ => return Err("ERR unexpected owner update operation".to_string()),
+        };
+        if owners.is_empty() {
+            return Err(wrong_arity("SADD"));
+        }
+        let network = owner_set_key(owner_set)?;
+        let changed = self
+            .emulator
+            .update_owners(kind, network, &parse_pubkeys(owners)?);
+        Ok(RespValue::Integer(changed as i64))
+    }
+
+    /// `RESOLVE_OWNERS_SCRIPT`: each account's recorded owner and its relevance
+    fn eval_resolve_owners(
+        &self,
+        keys: &[Vec<u8>],
+        accounts: &[Vec<u8>],
+    ) -> Result<RespValue, String> {
+        let [owner_index, owner_set] = keys else {
+            return Err(wrong_arity("EVAL"));
+        };
+        let network = hash_key(owner_index)?;
+        if owner_set_key(owner_set)? != network {
+            return Err("ERR owner index and owner set of different networks".to_string());
+        }
+        let cache = self.emulator.cache();
+        let owners = cache.get_token_account_owners(network, &parse_pubkeys(accounts)?);
+        let mut reply = Vec::with_capacity(owners.len() * 2);
+        for owner in owners {
+            let is_relevant = owner.is_some_and(|owner| cache.check_owners(network, &[owner])[0]);
+            reply.push(RespValue::BulkString(owner.map(|o| o.to_string().into())));
+            reply.push(RespValue::Integer(is_relevant as i64));
+        }
+        Ok(RespValue::Array(Some(reply)))
+    }
+
+    fn check_membership(&self, key: &[u8], members: &[Pubkey]) -> Result<Vec<bool>, String> {
+        let cache = self.emulator.cache();
+        Ok(match set_key(key)? {
+            SetKey::TokenAccountOwners(network) => cache.check_owners(network, members),
+            SetKey::TokenAccounts(network) => cache.check_token_accounts(network, members),
+        })
+    }
+
+    fn subscribe(&mut self, channels: &[Vec<u8>]) -> Vec<RespValue> {
+        if channels.is_empty() {
+            return vec![RespValue::Error(wrong_arity("SUBSCRIBE"))];
+        }
+        if self.messages.is_none() {
+            self.messages = Some(self.emulator.subscribe());
+        }
+        channels
+            .iter()
+            .map(|channel| {
+                let channel = String::from_utf8_lossy(channel).into_owned();
+                if self.channels.insert(channel.clone()) {
+                    self.emulator.track_subscription(&channel, true);
+                }
+                self.subscription_reply("subscribe", channel)
+            })
+            .collect()
+    }
+
+    fn unsubscribe(&mut self, channels: &[Vec<u8>]) -> Vec<RespValue> {
+        let channels: Vec<String> = if channels.is_empty() {
+            self.channels.iter().cloned().collect()
+        } else {
+            channels
+                .iter()
+                .map(|channel| String::from_utf8_lossy(channel).into_owned())
+                .collect()
+        };
+
+        let replies = channels
+            .into_iter()
+            .map(|channel| {
+                if self.channels.remove(&channel) {
+                    self.emulator.track_subscription(&channel, false);
+                }
+                self.subscription_reply("unsubscribe", channel)
+            })
+            .collect();
+        if self.channels.is_empty() {
+            self.messages = None;
+        }
+        replies
+    }
+
+    fn subscription_reply(&self, kind: &str, channel: String) -> RespValue {
+        RespValue::Array(Some(vec![
+            RespValue::bulk(kind),
+            RespValue::bulk(channel),
+            RespValue::Integer(self.channels.len() as i64),
+        ]))
+    }
+
+    /// Message push for a subscribed channel, if this connection subscribed to it
+    fn push(&self, message: PubSubMessage) -> Option<RespValue> {
+        if !self.channels.contains(&message.channel) {
+            return None;
+        }
+        Some(RespValue::Array(Some(vec![
+            RespValue::bulk("message"),
+            RespValue::bulk(message.channel),
+            RespValue::bulk(message.payload),
+        ])))
+    }
+}
+
+enum SetKey {
+    TokenAccountOwners(i32),
+    TokenAccounts(i32),
+}
+
+fn set_key(key: &[u8]) -> Result<SetKey, String> {
+    match RelevanceKey::parse(key) {
+        Some((network, RelevanceKey::Owners)) => Ok(SetKey::TokenAccountOwners(network)),
+        Some((network, RelevanceKey::Vaults)) => Ok(SetKey::TokenAccounts(network)),
+        _ => Err(format!(
+            "ERR unknown set key '{}'",
+            String::from_utf8_lossy(key)
+        )),
+    }
+}
+
+/// Network of an owner set key; the only sets listed and counted
+fn owner_set_key(key: &[u8]) -> Result<i32, String> {
+    match set_key(key)? {
+        SetKey::TokenAccountOwners(network) => Ok(network),
+        SetKey::TokenAccounts(_) => Err("ERR only owner sets can be scanned".to_string()),
+    }
+}
+
+/// Network of an owner index key, the only hash
+fn hash_key(key: &[u8]) -> Result<i32, String> {
+    match RelevanceKey::parse(key) {
+        Some((network, RelevanceKey::OwnerIndex)) => Ok(network),
+        _ => Err(format!(
+            "ERR unknown hash key '{}'",
+            String::from_utf8_lossy(key)
+        )),
+    }
+}
+
+/// Arguments after the command name, or after an EVAL's script and key count,
+/// used for the per-key latency cost
+fn command_keys(command: &RespValue) -> usize {
+    match command {
+        RespValue::Array(Some(args)) => {
+            let is_eval = matches!(
+                args.first(),
+                Some(RespValue::BulkString(Some(name))) if name.eq_ignore_ascii_case(b"EVAL")
+            );
+            args.len().saturating_sub(if is_eval { 3 } else { 1 })
+        }
+        _ => 0,
+    }
+}
+
+fn parse_command(command: RespValue) -> Result<Command, String> {
+    let RespValue::Array(Some(args)) = command else {
+        return Err("ERR expected a command array".to_string());
+    };
+    if args.is_empty() {
+        return Err("ERR empty command".to_string());
+    }
+    args.into_iter()
+        .map(|arg| match arg {
+            RespValue::BulkString(Some(bytes)) => Ok(bytes),
+            _ => Err("ERR command arguments must be bulk strings".to_string()),
+        })
+        .collect()
+}
+
+fn parse_pubkey(bytes: &[u8]) -> Result<Pubkey, String> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| format!("ERR invalid pubkey '{}'", String::from_utf8_lossy(bytes)))
+}
+
+fn parse_pubkeys(members: &[Vec<u8>]) -> Result<Vec<Pubkey>, String> {
+    members.iter().map(|member| parse_pubkey(member)).collect()
+}
+
+fn wrong_arity(name: &str) -> String {
+    format!(
+        "ERR wrong number of arguments for '{}' command",
+        name.to_ascii_lowercase()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use tokio::io::BufStream;
+
+    use super::*;
+    use crate::redis::latency::{LatencyModel, SimulationConfig};
+    use crate::redis::resp::encode_command;
+    use crate::redis::{OwnerSetUpdate, RelevanceQuery, RelevanceStore, RespRedisClient};
+
+    const NETWORK: i32 = 1;
+
+    /// Plain connection for commands the client doesn't send, like SUBSCRIBE
+    struct RawConnection(BufStream<TcpStream>);
+
+    impl RawConnection {
+        async fn connect(server: &RespServerHandle) -> Self {
+            let stream = TcpStream::connect(server.local_addr()).await.unwrap();
+            Self(BufStream::new(stream))
+        }
+
+        async fn send(&mut self, command: &[&str]) {
+            let mut out = Vec::new();
+            encode_command(command, &mut out);
+            self.0.write_all(&out).await.unwrap();
+            self.0.flush().await.unwrap();
+        }
+
+        async fn read(&mut self) -> RespValue {
+            tokio::time::timeout(Duration::from_secs(1), read_value(&mut self.0))
+                .await
+                .expect("reply within a second")
+                .unwrap()
+                .expect("connection open")
+        }
+    }
+
+    fn array(values: impl IntoIterator<Item = RespValue>) -> RespValue {
+        RespValue::Array(Some(values.into_iter().collect()))
+    }
+
+    #[tokio::test]
+    async fn membership_checks() {
+        let emulator = LocalRedisEmulator::new();
+        let server = emulator.serve("127.0.0.1:0").await.unwrap();
+        let client = RespRedisClient::connect(&server.url()).await.unwrap();
+        let [owner, vault, stranger] = [(); 3].map(|_| Pubkey::new_unique());
+        emulator.cache_mut().add_owners(NETWORK, &[owner]);
+        emulator.cache_mut().add_token_accounts(NETWORK, &[vault]);
+
+        let owners_key = RelevanceKey::Owners.key(NETWORK);
+        let vaults_key = RelevanceKey::Vaults.key(NETWORK);
+        let replies = client
+            .pipeline(&[
+                vec![
+                    b"SISMEMBER".to_vec(),
+                    owners_key.clone().into_bytes(),
+                    owner.to_string().into_bytes(),
+                ],
+                vec![
+                    b"SISMEMBER".to_vec(),
+                    vaults_key.into_bytes(),
+                    owner.to_string().into_bytes(),
+                ],
+                vec![
+                    b"SMISMEMBER".to_vec(),
+                    owners_key.into_bytes(),
+                    stranger.to_string().into_bytes(),
+                    owner.to_string().into_bytes(),
+                ],
+            ])
+            .await
+            .unwrap();
+        assert_eq!(
+            replies,
+            [
+                RespValue::Integer(1),
+                RespValue::Integer(0),
+                array([RespValue::Integer(0), RespValue::Integer(1)]),
+            ]
+        );
+
+        let mut query = RelevanceQuery::default();
+        query.check_owner(owner);
+        query.check_owner(stranger);
+        query.check_token_account(vault);
+        query.check_token_account(stranger);
+        let answers = client.batch_check(NETWORK, &query).await.unwrap();
+        assert!(answers.is_relevant_owner(&owner));
+        assert!(!answers.is_relevant_owner(&stranger));
+        assert!(answers.is_relevant_token_account(&vault));
+        assert!(!answers.is_relevant_token_account(&stranger));
+
+        // Other networks' sets are separate keys
+        let answers = client.batch_check(NETWORK + 1, &query).await.unwrap();
+        assert!(answers.relevant_owners.is_empty());
+        assert!(answers.relevant_token_accounts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn owner_update_script_bumps_the_version_and_publishes() {
+        let emulator = LocalRedisEmulator::new();
+        let server = emulator.serve("127.0.0.1:0").await.unwrap();
+        let client = RespRedisClient::connect(&server.url()).await.unwrap();
+        let mut messages = emulator.subscribe();
+        let [first, second] = [(); 2].map(|_| Pubkey::new_unique());
+
+        client.add_owners(NETWORK, &[first, second]).await.unwrap();
+        // Nothing changes, so no version and no message
+        client.add_owners(NETWORK, &[first]).await.unwrap();
+        client.remove_owners(NETWORK, &[first]).await.unwrap();
+
+        assert_eq!(client.owner_set_version().await.unwrap(), 2);
+        assert_eq!(
+            emulator.cache().check_owners(NETWORK, &[first, second]),
+            [false, true]
+        );
+
+        let mut updates = Vec::new();
+        while let Ok(message) = messages.try_recv() {
+            assert_eq!(message.channel, OWNER_UPDATES_CHANNEL);
+            updates.push(OwnerSetUpdate::decode(&message.payload).unwrap());
+        }
+        assert_eq!(
+            updates,
+            [
+                OwnerSetUpdate {
+                    version: 1,
+                    network: NETWORK,
+                    kind: OwnerSetUpdateKind::Added,
+                    owners: vec![first, second],
+                },
+                OwnerSetUpdate {
+                    version: 2,
+                    network: NETWORK,
+                    kind: OwnerSetUpdateKind::Removed,
+                    owners: vec![first],
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribe_and_unsubscribe() {
+        let emulator = LocalRedisEmulator::new();
+        let server = emulator.serve("127.0.0.1:0").await.unwrap();
+        let client = RespRedisClient::connect(&server.url()).await.unwrap();
+        let mut subscriber = RawConnection::connect(&server).await;
+        let owner = Pubkey::new_unique();
+
+        subscriber
+            .send(&["SUBSCRIBE", OWNER_UPDATES_CHANNEL, "other"])
+            .await;
+        for (channel, count) in [(OWNER_UPDATES_CHANNEL, 1), ("other", 2)] {
+            assert_eq!(
+                subscriber.read().await,
+                array([
+                    RespValue::bulk("subscribe"),
+                    RespValue::bulk(channel),
+                    RespValue::Integer(count),
+                ])
+            );
+        }
+
+        client.add_owners(NETWORK, &[owner]).await.unwrap();
+        let RespValue::Array(Some(push)) = subscriber.read().await else {
+            panic!("expected a message push");
+        };
+        let [kind, channel, RespValue::BulkString(Some(payload))] = push.as_slice() else {
+            panic!("unexpected push {:?}", push);
+        };
+        assert_eq!(kind, &RespValue::bulk("message"));
+        assert_eq!(channel, &RespValue::bulk(OWNER_UPDATES_CHANNEL));
+        let update = OwnerSetUpdate::decode(payload).unwrap();
+        assert_eq!((update.version, update.owners), (1, vec![owner]));
+
+        // Without arguments every channel is left
+        subscriber.send(&["UNSUBSCRIBE"]).await;
+        let mut left = HashSet::new();
+        for remaining in [1, 0] {
+            let RespValue::Array(Some(reply)) = subscriber.read().await else {
+                panic!("expected an unsubscribe reply");
+            };
+            let [kind, RespValue::BulkString(Some(channel)), count] = reply.as_slice() else {
+                panic!("unexpected unsubscribe reply {:?}", reply);
+            };
+            assert_eq!(kind, &RespValue::bulk("unsubscribe"));
+            assert_eq!(count, &RespValue::Integer(remaining));
+            left.insert(String::from_utf8(channel.clone()).unwrap());
+        }
+        assert_eq!(
+            left,
+            HashSet::from([OWNER_UPDATES_CHANNEL.to_string(), "other".to_string()])
+        );
+
+        let replies = client
+            .pipeline(&[vec![
+                b"PUBLISH".to_vec(),
+                b"other".to_vec(),
+                b"payload".to_vec(),
+            ]])
+            .await
+            .unwrap();
+        assert_eq!(replies, [RespValue::Integer(0)]);
+
+        // The connection is back to normal commands and got no further pushes
+        subscriber.send(&["PING"]).await;
+        assert_eq!(
+            subscriber.read().await,
+            RespValue::SimpleString("PONG".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn pipelined_replies_keep_their_order() {
+        let emulator = LocalRedisEmulator::new();
+        let server = emulator.serve("127.0.0.1:0").await.unwrap();
+        let client = RespRedisClient::connect(&server.url()).await.unwrap();
+        let owner = Pubkey::new_unique();
+        let owners_key = RelevanceKey::Owners.key(NETWORK).into_bytes();
+        let member = owner.to_string().into_bytes();
+
+        let commands: Vec<Command> = (0..50)
+            .map(|i| vec![b"PING".to_vec(), i.to_string().into_bytes()])
+            .chain([
+                vec![b"SADD".to_vec(), owners_key.clone(), member.clone()],
+                vec![b"SISMEMBER".to_vec(), owners_key.clone(), member.clone()],
+                vec![b"SCARD".to_vec(), owners_key.clone()],
+                vec![b"SREM".to_vec(), owners_key.clone(), member.clone()],
+                vec![b"SISMEMBER".to_vec(), owners_key, member],
+                vec![b"GET".to_vec(), OWNER_SET_VERSION_KEY.as_bytes().to_vec()],
+            ])
+            .collect();
+        let replies = client.pipeline(&commands).await.unwrap();
+
+        let expected: Vec<RespValue> = (0..50)
+            .map(|i| RespValue::bulk(i.to_string()))
+            .chain([
+                RespValue::Integer(1),
+                RespValue::Integer(1),
+                RespValue::Integer(1),
+                RespValue::Integer(1),
+                RespValue::Integer(0),
+                RespValue::bulk("2"),
+            ])
+            .collect();
+        assert_eq!(replies, expected);
+    }
+
+    #[tokio::test]
+    async fn latency_is_paid_once_per_pipeline() {
+        let latency = Duration::from_millis(100);
+        let emulator = LocalRedisEmulator::with_simulation(SimulationConfig {
+            latency: LatencyModel::Fixed(latency),
+            ..SimulationConfig::default()
+        });
+        let server = emulator.serve("127.0.0.1:0").await.unwrap();
+        let client = RespRedisClient::connect(&server.url()).await.unwrap();
+
+        let commands = vec![vec![b"PING".to_vec()]; 10];
+        let started = Instant::now();
+        let replies = client.pipeline(&commands).await.unwrap();
+        let elapsed = started.elapsed();
+
+        assert_eq!(replies.len(), 10);
+        assert!(elapsed >= latency, "{:?}", elapsed);
+        assert!(elapsed < latency * 3, "{:?}", elapsed);
+    }
+}
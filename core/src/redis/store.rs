@@ -0,0 +1,215 @@
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use solana_pubkey::Pubkey;
+
+/// One page of a paged owner listing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OwnerPage {
+    pub owners: Vec<Pubkey>,
+    /// Pass back to `list_owners` for the next page; `None` once the listing is
+    /// complete
+    pub next_cursor: Option<u64>,
+}
+
+/// Everything the parsers of one transaction need looked up, answered in a
+/// single `RelevanceStore::batch_check`. Keys are deduplicated across parsers.
+#[derive(Debug, Clone, Default)]
+pub struct RelevanceQuery {
+    owners: Vec<Pubkey>,
+    token_accounts: Vec<Pubkey>,
+    indexed_accounts: Vec<Pubkey>,
+    seen_owners: HashSet<Pubkey>,
+    seen_token_accounts: HashSet<Pubkey>,
+    seen_indexed_accounts: HashSet<Pubkey>,
+}
+
+impl RelevanceQuery {
+    /// Is the owner in the relevant owner set
+    pub fn check_owner(&mut self, owner: Pubkey) {
+        if self.seen_owners.insert(owner) {
+            self.owners.push(owner);
+        }
+    }
+
+    /// Is the token account in the relevant account (vault) set
+    pub fn check_token_account(&mut self, token_account: Pubkey) {
+        if self.seen_token_accounts.insert(token_account) {
+            self.token_accounts.push(token_account);
+        }
+    }
+
+    /// Which owner was recorded for the token account, and is that owner
+    /// relevant
+    pub fn resolve_owner(&mut self, token_account: Pubkey) {
+        if self.seen_indexed_accounts.insert(token_account) {
+            self.indexed_accounts.push(token_account);
+        }
+    }
+
+    pub fn owners(&self) -> &[Pubkey] {
+        &self.owners
+    }
+
+    pub fn token_accounts(&self) -> &[Pubkey] {
+        &self.token_accounts
+    }
+
+    pub fn indexed_accounts(&self) -> &[Pubkey] {
+        &self.indexed_accounts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty() && self.token_accounts.is_empty() && self.indexed_accounts.is_empty()
+    }
+
+    /// Keys in the query, for latency accounting
+    pub fn len(&self) -> usize {
+        self.owners.len() + self.token_accounts.len() + self.indexed_accounts.len()
+    }
+}
+
+/// Answers to a `RelevanceQuery`. Keys that weren't asked about read as
+/// irrelevant or unrecorded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RelevanceAnswers {
+    /// Relevant owners among those checked and those found through the index
+    pub relevant_owners: HashSet<Pubkey>,
+    pub relevant_token_accounts: HashSet<Pubkey>,
+    /// Token account -> recorded owner
+    pub indexed_owners: HashMap<Pubkey, Pubkey>,
+    /// Owner set version read no later than the lookups, so the answers
+    /// reflect at least every owner change up to it
+    pub owner_set_version: u64,
+}
+
+impl RelevanceAnswers {
+    /// Zips per-key lookup replies against the query's keys
+    pub fn from_lookups(
+        query: &RelevanceQuery,
+        owner_set_version: u64,
+        owner_relevance: Vec<bool>,
+        token_account_relevance: Vec<bool>,
+        indexed_owners: Vec<Option<Pubkey>>,
+    ) -> Self {
+        let mut answers = Self {
+            owner_set_version,
+            indexed_owners: query
+                .indexed_accounts()
+                .iter()
+                .zip(indexed_owners)
+                .filter_map(|(account, owner)| owner.map(|owner| (*account, owner)))
+                .collect(),
+            ..Self::default()
+        };
+        answers.add_owner_relevance(query.owners(), owner_relevance);
+        answers.relevant_token_accounts = query
+            .token_accounts()
+            .iter()
+            .zip(token_account_relevance)
+            .filter(|(_, is_relevant)| *is_relevant)
+            .map(|(account, _)| *account)
+            .collect();
+        answers
+    }
+
+    /// Owners found through the index that the query didn't already check
+    pub fn unchecked_indexed_owners(&self, query: &RelevanceQuery) -> Vec<Pubkey> {
+        let checked: HashSet<&Pubkey> = query.owners().iter().collect();
+        let unchecked: HashSet<Pubkey> = self
+            .indexed_owners
+            .values()
+            .filter(|owner| !checked.contains(owner))
+            .copied()
+            .collect();
+        unchecked.into_iter().collect()
+    }
+
+    pub fn add_owner_relevance(&mut self, owners: &[Pubkey], relevance: Vec<bool>) {
+        self.relevant_owners.extend(
+            owners
+                .iter()
+                .zip(relevance)
+                .filter(|(_, is_relevant)| *is_relevant)
+                .map(|(owner, _)| *owner),
+        );
+    }
+
+    pub fn is_relevant_owner(&self, owner: &Pubkey) -> bool {
+        self.relevant_owners.contains(owner)
+    }
+
+    pub fn is_relevant_token_account(&self, token_account: &Pubkey) -> bool {
+        self.relevant_token_accounts.contains(token_account)
+    }
+
+    pub fn indexed_owner(&self, token_account: &Pubkey) -> Option<Pubkey> {
+        self.indexed_owners.get(token_account).copied()
+    }
+}
+
+/// Source of truth for which token accounts and owners are relevant.
+///
+/// Every method is a single round trip to the backing store, so parsers should
+/// batch everything they need into as few calls as possible. All relevance data
+/// is kept per network id (see `Cluster::to_network_id`), so a wallet
+/// registered on devnet isn't relevant on mainnet.
+#[async_trait]
+pub trait RelevanceStore: Send + Sync {
+    /// Whether each owner is in the network's relevant owner set
+    async fn batch_check_token_account_owners(
+        &self,
+        network: i32,
+        owners: &[Pubkey],
+    ) -> Result<Vec<bool>, anyhow::Error>;
+
+    /// Whether each token account is in the network's relevant account (vault)
+    /// set
+    async fn batch_check_token_accounts(
+        &self,
+        network: i32,
+        token_accounts: &[Pubkey],
+    ) -> Result<Vec<bool>, anyhow::Error>;
+
+    /// Owners previously recorded for each token account
+    async fn batch_get_token_account_owners(
+        &self,
+        network: i32,
+        token_accounts: &[Pubkey],
+    ) -> Result<Vec<Option<Pubkey>>, anyhow::Error>;
+
+    /// Answers everything in `query`, including the relevance of owners found
+    /// through the index, in one round trip to the backing store
+    async fn batch_check(
+        &self,
+        network: i32,
+        query: &RelevanceQuery,
+    ) -> Result<RelevanceAnswers, anyhow::Error>;
+
+    /// Record token account -> owner mappings for accounts of relevant owners
+    async fn store_token_account_owners(
+        &self,
+        network: i32,
+        mappings: &[(Pubkey, Pubkey)],
+    ) -> Result<(), anyhow::Error>;
+
+    /// Registers owners as relevant; takes effect for the next lookup
+    async fn add_owners(&self, network: i32, owners: &[Pubkey]) -> Result<(), anyhow::Error>;
+
+    async fn remove_owners(&self, network: i32, owners: &[Pubkey]) -> Result<(), anyhow::Error>;
+
+    /// Up to `count` owners starting at `cursor` (0 for the first page). Owners
+    /// added or removed while paging may be missed or repeated.
+    async fn list_owners(
+        &self,
+        network: i32,
+        cursor: u64,
+        count: usize,
+    ) -> Result<OwnerPage, anyhow::Error>;
+
+    async fn owner_count(&self, network: i32) -> Result<u64, anyhow::Error>;
+
+    /// Monotonic counter bumped by every owner set change. A reader whose view
+    /// is at least this version has seen every change made before the call.
+    async fn owner_set_version(&self) -> Result<u64, anyhow::Error>;
+}
@@ -0,0 +1,794 @@
+//! Conversions from the RPC encodings of `solana-transaction-status`
+//! (`getTransaction` / `getBlock` with json, jsonParsed, base58 or base64)
+//! into `UnifiedTransaction`.
+//!
+//! jsonParsed messages are rebuilt from their account list. Partially decoded
+//! instructions keep their accounts and data. Instructions the RPC node fully
+//! parsed have lost both: those of programs the parser registry decodes
+//! (token, token-2022, system, ATA) fail the conversion, so fetch such
+//! transactions with json or base64. Other programs' parsed instructions,
+//! e.g. spl-memo, keep their position but carry no accounts or data.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+use solana_pubkey::Pubkey;
+use solana_sdk::bs58;
+use solana_sdk::hash::Hash;
+use solana_sdk::transaction::TransactionVersion;
+use solana_signature::Signature;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::parse_accounts::ParsedAccountSource;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction,
+    EncodedTransactionWithStatusMeta, UiCompiledInstruction, UiConfirmedBlock, UiInnerInstructions,
+    UiInstruction, UiLoadedAddresses, UiMessage, UiParsedInstruction, UiParsedMessage,
+    UiRawMessage, UiTransactionStatusMeta, UiTransactionTokenBalance,
+};
+
+use crate::parsing::associated_token_account::instruction::associated_token_program_id;
+use crate::parsing::system_program::instruction::system_program_id;
+use crate::parsing::token_program::instruction::TokenProgram;
+
+use super::transaction::{
+    CompiledInstruction, InnerInstruction, InnerInstructions, Message, MessageAddressTableLookup,
+    MessageHeader, TokenAmount, TokenBalance, Transaction, TransactionStatusMeta,
+    UnifiedTransaction,
+};
+
+impl TryFrom<EncodedConfirmedTransactionWithStatusMeta> for UnifiedTransaction {
+    type Error = anyhow::Error;
+
+    /// A `getTransaction` response
+    fn try_from(encoded: EncodedConfirmedTransactionWithStatusMeta) -> Result<Self, Self::Error> {
+        let mut transaction = UnifiedTransaction::try_from(encoded.transaction)?;
+        transaction.slot = encoded.slot;
+        transaction.block_time = encoded.block_time;
+        Ok(transaction)
+    }
+}
+
+impl TryFrom<EncodedTransactionWithStatusMeta> for UnifiedTransaction {
+    type Error = anyhow::Error;
+
+    /// A transaction without its slot, as listed in a `getBlock` response.
+    /// The slot and block time are left at their defaults.
+    fn try_from(encoded: EncodedTransactionWithStatusMeta) -> Result<Self, Self::Error> {
+        let (mut transaction, parsed_keys) = decode_transaction(&encoded.transaction)?;
+        if let (Some(version), Some(message)) = (&encoded.version, &mut transaction.message) {
+            message.versioned = matches!(version, TransactionVersion::Number(_));
+        }
+        let signature = transaction
+            .signatures
+            .first()
+            .copied()
+            .ok_or_else(|| anyhow!("transaction has no signatures"))?;
+        let meta = match encoded.meta {
+            Some(meta) => {
+                // Parsed inner instructions name their accounts, which index
+                // into the message keys followed by the loaded addresses
+                let account_keys = match &parsed_keys {
+                    Some(keys) => keys.all.clone(),
+                    None => {
+                        let mut keys = transaction
+                            .message
+                            .as_ref()
+                            .map(|message| message.account_keys.clone())
+                            .unwrap_or_default();
+                        if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+                            let (writable, readonly) = parse_loaded_addresses(loaded)?;
+                            keys.extend(writable);
+                            keys.extend(readonly);
+                        }
+                        keys
+                    }
+                };
+                let mut meta = convert_meta(meta, Some(&account_keys))
+                    .with_context(|| format!("transaction {}", signature))?;
+                // jsonParsed lists the loaded addresses with the account keys
+                // rather than in the meta
+                if let Some(keys) = parsed_keys {
+                    meta.loaded_writable_addresses = keys.loaded_writable;
+                    meta.loaded_readonly_addresses = keys.loaded_readonly;
+                }
+                meta
+            }
+            None => TransactionStatusMeta::default(),
+        };
+
+        Ok(UnifiedTransaction {
+            signature,
+            slot: 0,
+            block_time: None,
+            transaction,
+            meta,
+            index: None,
+        })
+    }
+}
+
+impl TryFrom<UiTransactionStatusMeta> for TransactionStatusMeta {
+    type Error = anyhow::Error;
+
+    /// jsonParsed inner instructions name their accounts, which need the
+    /// transaction's account keys, and are rejected here; convert the whole
+    /// transaction instead.
+    fn try_from(meta: UiTransactionStatusMeta) -> Result<Self, Self::Error> {
+        convert_meta(meta, None)
+    }
+}
+
+impl UnifiedTransaction {
+    /// The transactions of a `getBlock` response in block order, with `index`
+    /// set to their position in the block
+    pub fn from_ui_block(slot: u64, block: UiConfirmedBlock) -> Result<Vec<Self>, anyhow::Error> {
+        let Some(transactions) = block.transactions else {
+            bail!("block {} was fetched without transaction details", slot);
+        };
+        transactions
+            .into_iter()
+            .enumerate()
+            .map(|(index, encoded)| {
+                let mut transaction = UnifiedTransaction::try_from(encoded)
+                    .with_context(|| format!("transaction {} of block {}", index, slot))?;
+                transaction.slot = slot;
+                transaction.block_time = block.block_time;
+                transaction.index = Some(index as u64);
+                Ok(transaction)
+            })
+            .collect()
+    }
+}
+
+/// The transaction and, for jsonParsed messages, every account key including
+/// those loaded from lookup tables
+fn decode_transaction(
+    encoded: &EncodedTransaction,
+) -> Result<(Transaction, Option<ParsedAccountKeys>), anyhow::Error> {
+    match encoded {
+        EncodedTransaction::LegacyBinary(_) | EncodedTransaction::Binary(..) => {
+            let versioned = encoded
+                .decode()
+                .ok_or_else(|| anyhow!("undecodable binary transaction"))?;
+            Ok((Transaction::from(&versioned), None))
+        }
+        EncodedTransaction::Json(ui_transaction) => {
+            let signatures = ui_transaction
+                .signatures
+                .iter()
+                .map(|signature| parse_signature(signature))
+                .collect::<Result<Vec<_>, _>>()?;
+            let (message, parsed_keys) = match &ui_transaction.message {
+                UiMessage::Raw(message) => (convert_raw_message(message)?, None),
+                UiMessage::Parsed(message) => {
+                    let (message, keys) = convert_parsed_message(message)?;
+                    (message, Some(keys))
+                }
+            };
+            Ok((
+                Transaction {
+                    signatures,
+                    message: Some(message),
+                },
+                parsed_keys,
+            ))
+        }
+        EncodedTransaction::Accounts(_) => {
+            bail!(
+                "transaction was fetched with the accounts detail level, which has no instructions"
+            )
+        }
+    }
+}
+
+fn convert_raw_message(message: &UiRawMessage) -> Result<Message, anyhow::Error> {
+    let address_table_lookups = message
+        .address_table_lookups
+        .iter()
+        .flatten()
+        .map(|lookup| {
+            Ok(MessageAddressTableLookup {
+                account_key: parse_pubkey(&lookup.account_key)?,
+                writable_indexes: lookup.writable_indexes.clone(),
+                readonly_indexes: lookup.readonly_indexes.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+    Ok(Message {
+        header: Some(MessageHeader {
+            num_required_signatures: message.header.num_required_signatures as u32,
+            num_readonly_signed_accounts: message.header.num_readonly_signed_accounts as u32,
+            num_readonly_unsigned_accounts: message.header.num_readonly_unsigned_accounts as u32,
+        }),
+        account_keys: parse_pubkeys(&message.account_keys)?,
+        recent_blockhash: parse_blockhash(&message.recent_blockhash)?,
+        instructions: message
+            .instructions
+            .iter()
+            .map(convert_compiled_instruction)
+            .collect::<Result<_, _>>()?,
+        // Only v0 messages carry lookups in the RPC encoding
+        versioned: message.address_table_lookups.is_some(),
+        address_table_lookups,
+    })
+}
+
+/// Account keys of a jsonParsed message
+struct ParsedAccountKeys {
+    /// Message keys followed by the loaded addresses
+    all: Vec<Pubkey>,
+    loaded_writable: Vec<Pubkey>,
+    loaded_readonly: Vec<Pubkey>,
+}
+
+/// jsonParsed lists every account with its signer and writable flags; the
+/// header is rebuilt from them and instructions are mapped back to indices
+fn convert_parsed_message(
+    message: &UiParsedMessage,
+) -> Result<(Message, ParsedAccountKeys), anyhow::Error> {
+    let account_keys = message
+        .account_keys
+        .iter()
+        .map(|account| parse_pubkey(&account.pubkey))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (loaded_accounts, static_accounts): (Vec<_>, Vec<_>) = message
+        .account_keys
+        .iter()
+        .zip(account_keys.iter().copied())
+        .partition(|(account, _)| matches!(account.source, Some(ParsedAccountSource::LookupTable)));
+    let (loaded_writable, loaded_readonly): (Vec<_>, Vec<_>) = loaded_accounts
+        .into_iter()
+        .partition(|(account, _)| account.writable);
+    let static_accounts: Vec<_> = static_accounts
+        .into_iter()
+        .map(|(account, _)| account)
+        .collect();
+
+    let header = MessageHeader {
+        num_required_signatures: static_accounts.iter().filter(|a| a.signer).count() as u32,
+        num_readonly_signed_accounts: static_accounts
+            .iter()
+            .filter(|a| a.signer && !a.writable)
+            .count() as u32,
+        num_readonly_unsigned_accounts: static_accounts
+            .iter()
+            .filter(|a| !a.signer && !a.writable)
+            .count() as u32,
+    };
+
+    let instructions = message
+        .instructions
+        .iter()
+        .enumerate()
+        .map(|(index, ix)| {
+            let inner = convert_ui_instruction(ix, Some(&account_keys))
+                .with_context(|| format!("instruction {}", index))?;
+            Ok(CompiledInstruction {
+                program_id_index: inner.program_id_index,
+                accounts: inner.accounts,
+                data: inner.data,
+            })
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+    let address_table_lookups = message
+        .address_table_lookups
+        .iter()
+        .flatten()
+        .map(|lookup| {
+            Ok(MessageAddressTableLookup {
+                account_key: parse_pubkey(&lookup.account_key)?,
+                writable_indexes: lookup.writable_indexes.clone(),
+                readonly_indexes: lookup.readonly_indexes.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+    let message = Message {
+        header: Some(header),
+        account_keys: account_keys[..static_accounts.len()].to_vec(),
+        recent_blockhash: parse_blockhash(&message.recent_blockhash)?,
+        instructions,
+        versioned: message.address_table_lookups.is_some(),
+        address_table_lookups,
+    };
+    let keys = ParsedAccountKeys {
+        all: account_keys,
+        loaded_writable: loaded_writable.into_iter().map(|(_, key)| key).collect(),
+        loaded_readonly: loaded_readonly.into_iter().map(|(_, key)| key).collect(),
+    };
+    Ok((message, keys))
+}
+
+fn convert_meta(
+    meta: UiTransactionStatusMeta,
+    account_keys: Option<&[Pubkey]>,
+) -> Result<TransactionStatusMeta, anyhow::Error> {
+    let inner_instructions = match meta.inner_instructions {
+        OptionSerializer::Some(inner_instructions) => inner_instructions
+            .iter()
+            .map(|inner| convert_inner_instructions(inner, account_keys))
+            .collect::<Result<_, _>>()?,
+        _ => Vec::new(),
+    };
+    let (loaded_writable_addresses, loaded_readonly_addresses) = match &meta.loaded_addresses {
+        OptionSerializer::Some(loaded) => parse_loaded_addresses(loaded)?,
+        _ => (Vec::new(), Vec::new()),
+    };
+
+    Ok(TransactionStatusMeta {
+        fee: meta.fee,
+        pre_balances: meta.pre_balances,
+        post_balances: meta.post_balances,
+        inner_instructions,
+        pre_token_balances: convert_token_balances(meta.pre_token_balances)?,
+        post_token_balances: convert_token_balances(meta.post_token_balances)?,
+        loaded_writable_addresses,
+        loaded_readonly_addresses,
+        compute_units_consumed: match meta.compute_units_consumed {
+            OptionSerializer::Some(units) => Some(units),
+            _ => None,
+        },
+    })
+}
+
+fn convert_inner_instructions(
+    inner: &UiInnerInstructions,
+    account_keys: Option<&[Pubkey]>,
+) -> Result<InnerInstructions, anyhow::Error> {
+    Ok(InnerInstructions {
+        index: inner.index as u32,
+        instructions: inner
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(position, ix)| {
+                convert_ui_instruction(ix, account_keys)
+                    .with_context(|| format!("inner instruction {}.{}", inner.index, position))
+            })
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// A compiled or partially decoded instruction. Partially decoded ones name
+/// their accounts, which are looked up in `account_keys`.
+fn convert_ui_instruction(
+    ix: &UiInstruction,
+    account_keys: Option<&[Pubkey]>,
+) -> Result<InnerInstruction, anyhow::Error> {
+    match ix {
+        UiInstruction::Compiled(ix) => {
+            let compiled = convert_compiled_instruction(ix)?;
+            Ok(InnerInstruction {
+                program_id_index: compiled.program_id_index,
+                accounts: compiled.accounts,
+                data: compiled.data,
+                stack_height: ix.stack_height,
+            })
+        }
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(ix)) => {
+            let account_keys = account_keys.ok_or_else(|| {
+                anyhow!("partially decoded instruction needs the transaction's account keys")
+            })?;
+            let key_index = |address: &str| -> Result<u8, anyhow::Error> {
+                index_of(account_keys, &parse_pubkey(address)?)
+            };
+            Ok(InnerInstruction {
+                program_id_index: key_index(&ix.program_id)? as u32,
+                accounts: ix
+                    .accounts
+                    .iter()
+                    .map(|account| key_index(account))
+                    .collect::<Result<_, _>>()?,
+                data: parse_instruction_data(&ix.data)?,
+                stack_height: ix.stack_height,
+            })
+        }
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(ix)) => {
+            let program_id = parse_pubkey(&ix.program_id)?;
+            if is_registry_program(&program_id) {
+                bail!(
+                    "{} instruction was fully parsed by the RPC node and has no raw data; fetch with json or base64 encoding",
+                    ix.program
+                );
+            }
+            let account_keys = account_keys.ok_or_else(|| {
+                anyhow!("parsed instruction needs the transaction's account keys")
+            })?;
+            // Kept so the instructions after it keep their paths
+            Ok(InnerInstruction {
+                program_id_index: index_of(account_keys, &program_id)? as u32,
+                accounts: Vec::new(),
+                data: Vec::new(),
+                stack_height: ix.stack_height,
+            })
+        }
+    }
+}
+
+fn index_of(account_keys: &[Pubkey], address: &Pubkey) -> Result<u8, anyhow::Error> {
+    account_keys
+        .iter()
+        .position(|key| key == address)
+        .and_then(|index| u8::try_from(index).ok())
+        .ok_or_else(|| anyhow!("account {} is not in the transaction", address))
+}
+
+/// Programs with a parser in the registry, whose fully parsed instructions
+/// can't be rebuilt
+fn is_registry_program(program_id: &Pubkey) -> bool {
+    TokenProgram::from_id(program_id).is_some()
+        || *program_id == system_program_id()
+        || *program_id == associated_token_program_id()
+}
+
+fn convert_compiled_instruction(
+    ix: &UiCompiledInstruction,
+) -> Result<CompiledInstruction, anyhow::Error> {
+    Ok(CompiledInstruction {
+        program_id_index: ix.program_id_index as u32,
+        accounts: ix.accounts.clone(),
+        data: parse_instruction_data(&ix.data)?,
+    })
+}
+
+/// Missing owners and program ids (nodes before v1.15) default to
+/// `Pubkey::default()`
+fn convert_token_balances(
+    balances: OptionSerializer<Vec<UiTransactionTokenBalance>>,
+) -> Result<Vec<TokenBalance>, anyhow::Error> {
+    let OptionSerializer::Some(balances) = balances else {
+        return Ok(Vec::new());
+    };
+    let optional_pubkey = |address: OptionSerializer<String>| match address {
+        OptionSerializer::Some(address) => parse_pubkey(&address),
+        _ => Ok(Pubkey::default()),
+    };
+    balances
+        .into_iter()
+        .map(|balance| {
+            let amount = &balance.ui_token_amount;
+            Ok(TokenBalance {
+                account_index: balance.account_index as u32,
+                mint: parse_pubkey(&balance.mint)?,
+                ui_token_amount: Some(TokenAmount {
+                    ui_amount: amount.ui_amount,
+                    decimals: amount.decimals as u32,
+                    amount: amount
+                        .amount
+                        .parse()
+                        .with_context(|| format!("token amount {:?}", amount.amount))?,
+                    ui_amount_string: amount.ui_amount_string.clone(),
+                }),
+                owner: optional_pubkey(balance.owner)?,
+                program_id: optional_pubkey(balance.program_id)?,
+            })
+        })
+        .collect()
+}
+
+fn parse_loaded_addresses(
+    loaded: &UiLoadedAddresses,
+) -> Result<(Vec<Pubkey>, Vec<Pubkey>), anyhow::Error> {
+    Ok((
+        parse_pubkeys(&loaded.writable)?,
+        parse_pubkeys(&loaded.readonly)?,
+    ))
+}
+
+pub(super) fn parse_pubkey(address: &str) -> Result<Pubkey, anyhow::Error> {
+    Pubkey::from_str(address).with_context(|| format!("invalid pubkey {:?}", address))
+}
+
+fn parse_pubkeys(addresses: &[String]) -> Result<Vec<Pubkey>, anyhow::Error> {
+    addresses
+        .iter()
+        .map(|address| parse_pubkey(address))
+        .collect()
+}
+
+fn parse_signature(signature: &str) -> Result<Signature, anyhow::Error> {
+    Signature::from_str(signature).with_context(|| format!("invalid signature {:?}", signature))
+}
+
+fn parse_blockhash(blockhash: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let hash =
+        Hash::from_str(blockhash).with_context(|| format!("invalid blockhash {:?}", blockhash))?;
+    Ok(hash.to_bytes().to_vec())
+}
+
+/// Instruction data is base58 in every json encoding
+fn parse_instruction_data(data: &str) -> Result<Vec<u8>, anyhow::Error> {
+    bs58::decode(data)
+        .into_vec()
+        .with_context(|| format!("invalid base58 instruction data {:?}", data))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+    use solana_sdk::message::{v0, AddressLookupTableAccount, VersionedMessage};
+    use solana_sdk::transaction::VersionedTransaction;
+    use solana_transaction_status::{
+        BlockEncodingOptions, ConfirmedBlock, ConfirmedTransactionWithStatusMeta,
+        TransactionDetails, TransactionWithStatusMeta, UiTransactionEncoding,
+        VersionedTransactionWithStatusMeta,
+    };
+
+    use super::*;
+
+    const SLOT: u64 = 42;
+    const BLOCK_TIME: i64 = 1_700_000_000;
+
+    const ENCODINGS: [UiTransactionEncoding; 4] = [
+        UiTransactionEncoding::Json,
+        UiTransactionEncoding::JsonParsed,
+        UiTransactionEncoding::Base64,
+        UiTransactionEncoding::Base58,
+    ];
+
+    fn memo_program_id() -> Pubkey {
+        "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr"
+            .parse()
+            .unwrap()
+    }
+
+    /// `instructions` paid by a fresh payer, as a v0 message loading every
+    /// non-signer, non-program account from a lookup table when `versioned`.
+    /// The meta has an inner instruction under the first instruction that
+    /// reuses its accounts, and token balances for its second account.
+    fn fixture(versioned: bool, instructions: &[Instruction]) -> UnifiedTransaction {
+        let payer = Pubkey::new_unique();
+        let blockhash = Hash::new_unique();
+        let programs: Vec<Pubkey> = instructions.iter().map(|ix| ix.program_id).collect();
+        let table_addresses: Vec<Pubkey> = instructions
+            .iter()
+            .flat_map(|ix| &ix.accounts)
+            .filter(|account| !account.is_signer && !programs.contains(&account.pubkey))
+            .map(|account| account.pubkey)
+            .collect();
+        let message = if versioned {
+            let table = AddressLookupTableAccount {
+                key: Pubkey::new_unique(),
+                addresses: table_addresses.clone(),
+            };
+            VersionedMessage::V0(
+                v0::Message::try_compile(&payer, instructions, &[table], blockhash).unwrap(),
+            )
+        } else {
+            VersionedMessage::Legacy(solana_sdk::message::Message::new_with_blockhash(
+                instructions,
+                Some(&payer),
+                &blockhash,
+            ))
+        };
+        let signatures = (0..message.header().num_required_signatures)
+            .map(|_| Signature::new_unique())
+            .collect();
+        let sdk_transaction = VersionedTransaction {
+            signatures,
+            message,
+        };
+        let mut transaction = UnifiedTransaction::from_versioned(
+            SLOT,
+            &sdk_transaction,
+            solana_transaction_status::TransactionStatusMeta::default(),
+        )
+        .unwrap();
+        transaction.block_time = Some(BLOCK_TIME);
+
+        let mut keys = transaction
+            .transaction
+            .message
+            .as_ref()
+            .unwrap()
+            .account_keys
+            .clone();
+        if let VersionedMessage::V0(message) = &sdk_transaction.message {
+            // Every lookup loads its writable indexes before its readonly ones
+            let table = &message.address_table_lookups[0];
+            let lookup = |indexes: &[u8]| -> Vec<Pubkey> {
+                indexes
+                    .iter()
+                    .map(|index| table_addresses[*index as usize])
+                    .collect()
+            };
+            transaction.meta.loaded_writable_addresses = lookup(&table.writable_indexes);
+            transaction.meta.loaded_readonly_addresses = lookup(&table.readonly_indexes);
+            keys.extend(transaction.meta.loaded_writable_addresses.clone());
+            keys.extend(transaction.meta.loaded_readonly_addresses.clone());
+        }
+
+        let first = &transaction
+            .transaction
+            .message
+            .as_ref()
+            .unwrap()
+            .instructions[0];
+        let account = first.accounts[1];
+        let token_balance = |amount: u64, ui_amount: f64| TokenBalance {
+            account_index: account as u32,
+            mint: Pubkey::new_unique(),
+            ui_token_amount: Some(TokenAmount {
+                ui_amount: Some(ui_amount),
+                decimals: 6,
+                amount,
+                ui_amount_string: ui_amount.to_string(),
+            }),
+            owner: Pubkey::new_unique(),
+            program_id: spl_token::ID,
+        };
+        transaction.meta = TransactionStatusMeta {
+            fee: 5000,
+            pre_balances: (0..keys.len() as u64).map(|i| 1_000 + i).collect(),
+            post_balances: (0..keys.len() as u64).map(|i| 2_000 + i).collect(),
+            inner_instructions: vec![InnerInstructions {
+                index: 0,
+                instructions: vec![InnerInstruction {
+                    program_id_index: first.program_id_index,
+                    accounts: first.accounts.iter().rev().copied().collect(),
+                    data: vec![7, 7],
+                    stack_height: Some(2),
+                }],
+            }],
+            pre_token_balances: vec![token_balance(1_500_000, 1.5)],
+            post_token_balances: vec![token_balance(500_000, 0.5)],
+            compute_units_consumed: Some(1234),
+            ..transaction.meta
+        };
+        transaction
+    }
+
+    /// A program no RPC node parses, so jsonParsed leaves it partially decoded
+    fn opaque_instruction() -> Instruction {
+        Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[3, 1, 2, 3],
+            vec![
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(Pubkey::new_unique(), false),
+            ],
+        )
+    }
+
+    fn with_meta(transaction: &UnifiedTransaction) -> TransactionWithStatusMeta {
+        let (transaction, meta) = transaction.to_versioned().unwrap();
+        TransactionWithStatusMeta::Complete(VersionedTransactionWithStatusMeta {
+            transaction,
+            meta,
+        })
+    }
+
+    /// What `getTransaction` returns for `transaction` in `encoding`, through
+    /// its JSON form
+    fn get_transaction(
+        transaction: &UnifiedTransaction,
+        encoding: UiTransactionEncoding,
+    ) -> EncodedConfirmedTransactionWithStatusMeta {
+        let encoded = ConfirmedTransactionWithStatusMeta {
+            slot: SLOT,
+            tx_with_meta: with_meta(transaction),
+            block_time: transaction.block_time,
+        }
+        .encode(encoding, Some(0))
+        .unwrap();
+        serde_json::from_str(&serde_json::to_string(&encoded).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn get_transaction_responses() {
+        for versioned in [false, true] {
+            let transaction = fixture(versioned, &[opaque_instruction()]);
+            assert_eq!(
+                transaction.meta.loaded_writable_addresses.len(),
+                if versioned { 2 } else { 0 }
+            );
+            for encoding in ENCODINGS {
+                let converted =
+                    UnifiedTransaction::try_from(get_transaction(&transaction, encoding))
+                        .unwrap_or_else(|err| panic!("{:?}: {:#}", encoding, err));
+                assert_eq!(
+                    converted, transaction,
+                    "{:?}, versioned {}",
+                    encoding, versioned
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn json_parsed_registry_programs_are_rejected() {
+        let [owner, source, destination] = [(); 3].map(|_| Pubkey::new_unique());
+        let transfer =
+            spl_token::instruction::transfer(&spl_token::ID, &source, &destination, &owner, &[], 1)
+                .unwrap();
+        let transaction = fixture(false, &[opaque_instruction(), transfer]);
+
+        let err = UnifiedTransaction::try_from(get_transaction(
+            &transaction,
+            UiTransactionEncoding::JsonParsed,
+        ))
+        .unwrap_err();
+        assert!(format!("{:#}", err).contains("spl-token"), "{:#}", err);
+
+        // The raw encodings carry the instruction as is
+        let converted = UnifiedTransaction::try_from(get_transaction(
+            &transaction,
+            UiTransactionEncoding::Json,
+        ))
+        .unwrap();
+        assert_eq!(converted, transaction);
+    }
+
+    #[test]
+    fn json_parsed_other_programs_keep_their_position() {
+        let memo = Instruction::new_with_bytes(memo_program_id(), b"hello", Vec::new());
+        let transaction = fixture(true, &[opaque_instruction(), memo, opaque_instruction()]);
+
+        let converted = UnifiedTransaction::try_from(get_transaction(
+            &transaction,
+            UiTransactionEncoding::JsonParsed,
+        ))
+        .unwrap();
+
+        // Only the memo's data is lost; its program is still named
+        let mut expected = transaction.clone();
+        let memo = &mut expected.transaction.message.as_mut().unwrap().instructions[1];
+        memo.data.clear();
+        assert_eq!(converted, expected);
+        let message = converted.transaction.message.as_ref().unwrap();
+        assert_eq!(
+            message.account_keys[message.instructions[1].program_id_index as usize],
+            memo_program_id()
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        let transactions = [
+            fixture(false, &[opaque_instruction()]),
+            fixture(true, &[opaque_instruction()]),
+        ];
+        let block = |transaction_details| {
+            let block = ConfirmedBlock {
+                previous_blockhash: Hash::new_unique().to_string(),
+                blockhash: Hash::new_unique().to_string(),
+                parent_slot: SLOT - 1,
+                transactions: transactions.iter().map(with_meta).collect(),
+                rewards: Vec::new(),
+                num_partitions: None,
+                block_time: Some(BLOCK_TIME),
+                block_height: Some(SLOT),
+            }
+            .encode_with_options(
+                UiTransactionEncoding::Base64,
+                BlockEncodingOptions {
+                    transaction_details,
+                    show_rewards: false,
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .unwrap();
+            serde_json::from_str(&serde_json::to_string(&block).unwrap()).unwrap()
+        };
+
+        let converted =
+            UnifiedTransaction::from_ui_block(SLOT, block(TransactionDetails::Full)).unwrap();
+        let expected: Vec<UnifiedTransaction> = transactions
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(index, transaction)| UnifiedTransaction {
+                index: Some(index as u64),
+                ..transaction
+            })
+            .collect();
+        assert_eq!(converted, expected);
+
+        assert!(
+            UnifiedTransaction::from_ui_block(SLOT, block(TransactionDetails::Signatures)).is_err()
+        );
+    }
+}
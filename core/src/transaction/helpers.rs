@@ -2,7 +2,9 @@ use std::collections::HashMap;
 
 use solana_pubkey::Pubkey;
 
-use super::transaction::{CompiledInstruction, InnerInstruction, UnifiedTransaction};
+use super::transaction::{
+    CompiledInstruction, InnerInstruction, InstructionPath, UnifiedTransaction,
+};
 
 impl UnifiedTransaction {
     /// Get the complete account keys from the transaction, including those from address table lookups
@@ -92,6 +94,61 @@ impl UnifiedTransaction {
         instructions_by_program
     }
 
+    /// Gets all instructions (including inner instructions) that call any of the
+    /// specified program IDs, in execution order and tagged with their program ID
+    /// and position in the transaction
+    pub fn get_instructions_with_path_by_programs(
+        &self,
+        program_ids: &[Pubkey],
+    ) -> Vec<(InstructionPath, Pubkey, CompiledInstruction)> {
+        let mut instructions = Vec::new();
+
+        let Some(message) = &self.transaction.message else {
+            return instructions;
+        };
+        let account_keys = self.get_account_keys();
+        let program_id_of = |program_id_index: u32| {
+            account_keys
+                .get(program_id_index as usize)
+                .filter(|program_id| program_ids.contains(program_id))
+                .copied()
+        };
+
+        for (ix_index, main_ix) in message.instructions.iter().enumerate() {
+            if let Some(program_id) = program_id_of(main_ix.program_id_index) {
+                let path = InstructionPath {
+                    outer_index: ix_index as u32,
+                    inner_index: None,
+                };
+                instructions.push((path, program_id, main_ix.clone()));
+            }
+
+            if let Some(inner_group) = self
+                .meta
+                .inner_instructions
+                .iter()
+                .find(|inner| inner.index == ix_index as u32)
+            {
+                for (inner_index, inner_ix) in inner_group.instructions.iter().enumerate() {
+                    if let Some(program_id) = program_id_of(inner_ix.program_id_index) {
+                        let path = InstructionPath {
+                            outer_index: ix_index as u32,
+                            inner_index: Some(inner_index as u32),
+                        };
+                        let compiled_inner = CompiledInstruction {
+                            program_id_index: inner_ix.program_id_index,
+                            accounts: inner_ix.accounts.clone(),
+                            data: inner_ix.data.clone(),
+                        };
+                        instructions.push((path, program_id, compiled_inner));
+                    }
+                }
+            }
+        }
+
+        instructions
+    }
+
     pub fn get_all_inner_instructions_by_programs(
         &self,
         program_ids: &[Pubkey],
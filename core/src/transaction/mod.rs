@@ -1,6 +1,9 @@
+pub mod encoded;
 pub mod helpers;
 #[allow(clippy::module_inception)]
 pub mod transaction;
+pub mod versioned;
+pub mod yellowstone;
 
 use std::fmt::Display;
 use std::str::FromStr;
@@ -30,6 +33,14 @@ impl Cluster {
             Cluster::Mainnet => 1,
         }
     }
+
+    pub fn from_network_id(network_id: i32) -> Option<Self> {
+        match network_id {
+            0 => Some(Cluster::Devnet),
+            1 => Some(Cluster::Mainnet),
+            _ => None,
+        }
+    }
 }
 
 impl Display for Cluster {
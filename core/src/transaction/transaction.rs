@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use solana_pubkey::Pubkey;
 use solana_signature::Signature;
+use std::fmt::Display;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UnifiedTransaction {
@@ -93,10 +94,29 @@ pub struct TokenBalance {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct TokenAmount {
-    pub ui_amount: f64,
+    /// `None` when the node didn't report one
+    pub ui_amount: Option<f64>,
     pub decimals: u32,
     pub amount: u64,
     pub ui_amount_string: String,
 }
 
+/// Position of an instruction within a transaction: the top-level instruction
+/// index and, for instructions invoked via CPI, the index within that
+/// instruction's inner instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InstructionPath {
+    pub outer_index: u32,
+    pub inner_index: Option<u32>,
+}
+
+impl Display for InstructionPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.inner_index {
+            Some(inner_index) => write!(f, "{}.{}", self.outer_index, inner_index),
+            None => write!(f, "{}", self.outer_index),
+        }
+    }
+}
+
 impl UnifiedTransaction {}
@@ -0,0 +1,459 @@
+//! Conversions between `UnifiedTransaction` and the SDK types Geyser plugins
+//! and ledger tools produce: `VersionedTransaction` with the
+//! `solana-transaction-status` `TransactionStatusMeta`.
+
+use anyhow::{anyhow, Context};
+use solana_account_decoder_client_types::token::UiTokenAmount;
+use solana_pubkey::Pubkey;
+use solana_sdk::hash::Hash;
+use solana_sdk::message::{
+    compiled_instruction::CompiledInstruction as SdkCompiledInstruction,
+    v0::{self, LoadedAddresses},
+    Message as LegacyMessage, MessageHeader as SdkMessageHeader, VersionedMessage,
+};
+use solana_sdk::transaction::VersionedTransaction;
+use solana_transaction_status as status;
+
+use super::encoded::parse_pubkey;
+use super::transaction::{
+    CompiledInstruction, InnerInstruction, InnerInstructions, Message, MessageAddressTableLookup,
+    MessageHeader, TokenAmount, TokenBalance, Transaction, TransactionStatusMeta,
+    UnifiedTransaction,
+};
+
+impl UnifiedTransaction {
+    /// A transaction as handed over by a Geyser plugin or read from the
+    /// ledger. The block time and index are left unset.
+    pub fn from_versioned(
+        slot: u64,
+        transaction: &VersionedTransaction,
+        meta: status::TransactionStatusMeta,
+    ) -> Result<Self, anyhow::Error> {
+        let signature = *transaction
+            .signatures
+            .first()
+            .ok_or_else(|| anyhow!("transaction has no signatures"))?;
+        let meta = TransactionStatusMeta::try_from(meta)
+            .with_context(|| format!("transaction {}", signature))?;
+        Ok(UnifiedTransaction {
+            signature,
+            slot,
+            block_time: None,
+            transaction: Transaction::from(transaction),
+            meta,
+            index: None,
+        })
+    }
+
+    /// Back to the SDK types. Only what `UnifiedTransaction` keeps survives:
+    /// the status is `Ok`, and logs, rewards and return data are empty.
+    pub fn to_versioned(
+        &self,
+    ) -> Result<(VersionedTransaction, status::TransactionStatusMeta), anyhow::Error> {
+        Ok((
+            VersionedTransaction::try_from(&self.transaction)?,
+            status::TransactionStatusMeta::try_from(&self.meta)?,
+        ))
+    }
+}
+
+impl From<&VersionedTransaction> for Transaction {
+    fn from(versioned: &VersionedTransaction) -> Self {
+        let (header, account_keys, recent_blockhash, instructions, lookups) =
+            match &versioned.message {
+                VersionedMessage::Legacy(message) => (
+                    message.header,
+                    &message.account_keys,
+                    message.recent_blockhash,
+                    &message.instructions,
+                    &[][..],
+                ),
+                VersionedMessage::V0(message) => (
+                    message.header,
+                    &message.account_keys,
+                    message.recent_blockhash,
+                    &message.instructions,
+                    &message.address_table_lookups[..],
+                ),
+            };
+
+        Transaction {
+            signatures: versioned.signatures.clone(),
+            message: Some(Message {
+                header: Some(MessageHeader {
+                    num_required_signatures: header.num_required_signatures as u32,
+                    num_readonly_signed_accounts: header.num_readonly_signed_accounts as u32,
+                    num_readonly_unsigned_accounts: header.num_readonly_unsigned_accounts as u32,
+                }),
+                account_keys: account_keys.clone(),
+                recent_blockhash: recent_blockhash.to_bytes().to_vec(),
+                instructions: instructions
+                    .iter()
+                    .map(|ix| CompiledInstruction {
+                        program_id_index: ix.program_id_index as u32,
+                        accounts: ix.accounts.clone(),
+                        data: ix.data.clone(),
+                    })
+                    .collect(),
+                versioned: matches!(versioned.message, VersionedMessage::V0(_)),
+                address_table_lookups: lookups
+                    .iter()
+                    .map(|lookup| MessageAddressTableLookup {
+                        account_key: lookup.account_key,
+                        writable_indexes: lookup.writable_indexes.clone(),
+                        readonly_indexes: lookup.readonly_indexes.clone(),
+                    })
+                    .collect(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<&Transaction> for VersionedTransaction {
+    type Error = anyhow::Error;
+
+    /// Fails for messages without a header and for indices or blockhashes
+    /// that do not fit the wire format
+    fn try_from(transaction: &Transaction) -> Result<Self, Self::Error> {
+        let message = transaction
+            .message
+            .as_ref()
+            .ok_or_else(|| anyhow!("transaction has no message"))?;
+        let header = message
+            .header
+            .as_ref()
+            .ok_or_else(|| anyhow!("message has no header"))?;
+        let header = SdkMessageHeader {
+            num_required_signatures: to_u8(header.num_required_signatures)?,
+            num_readonly_signed_accounts: to_u8(header.num_readonly_signed_accounts)?,
+            num_readonly_unsigned_accounts: to_u8(header.num_readonly_unsigned_accounts)?,
+        };
+        let recent_blockhash = <[u8; 32]>::try_from(message.recent_blockhash.as_slice())
+            .map(Hash::new_from_array)
+            .map_err(|_| {
+                anyhow!(
+                    "recent blockhash is {} bytes, expected 32",
+                    message.recent_blockhash.len()
+                )
+            })?;
+        let instructions = message
+            .instructions
+            .iter()
+            .map(|ix| {
+                Ok(SdkCompiledInstruction {
+                    program_id_index: to_u8(ix.program_id_index)?,
+                    accounts: ix.accounts.clone(),
+                    data: ix.data.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+        let message = if message.versioned {
+            VersionedMessage::V0(v0::Message {
+                header,
+                account_keys: message.account_keys.clone(),
+                recent_blockhash,
+                instructions,
+                address_table_lookups: message
+                    .address_table_lookups
+                    .iter()
+                    .map(|lookup| v0::MessageAddressTableLookup {
+                        account_key: lookup.account_key,
+                        writable_indexes: lookup.writable_indexes.clone(),
+                        readonly_indexes: lookup.readonly_indexes.clone(),
+                    })
+                    .collect(),
+            })
+        } else {
+            VersionedMessage::Legacy(LegacyMessage {
+                header,
+                account_keys: message.account_keys.clone(),
+                recent_blockhash,
+                instructions,
+            })
+        };
+
+        Ok(VersionedTransaction {
+            signatures: transaction.signatures.clone(),
+            message,
+        })
+    }
+}
+
+impl TryFrom<status::TransactionStatusMeta> for TransactionStatusMeta {
+    type Error = anyhow::Error;
+
+    /// The status, logs, rewards and return data are dropped. Token balances
+    /// without an owner or program id (nodes before v1.15) get
+    /// `Pubkey::default()`.
+    fn try_from(meta: status::TransactionStatusMeta) -> Result<Self, Self::Error> {
+        let inner_instructions = meta
+            .inner_instructions
+            .unwrap_or_default()
+            .into_iter()
+            .map(|inner| InnerInstructions {
+                index: inner.index as u32,
+                instructions: inner
+                    .instructions
+                    .into_iter()
+                    .map(|ix| InnerInstruction {
+                        program_id_index: ix.instruction.program_id_index as u32,
+                        accounts: ix.instruction.accounts,
+                        data: ix.instruction.data,
+                        stack_height: ix.stack_height,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(TransactionStatusMeta {
+            fee: meta.fee,
+            pre_balances: meta.pre_balances,
+            post_balances: meta.post_balances,
+            inner_instructions,
+            pre_token_balances: from_sdk_token_balances(meta.pre_token_balances)?,
+            post_token_balances: from_sdk_token_balances(meta.post_token_balances)?,
+            loaded_writable_addresses: meta.loaded_addresses.writable,
+            loaded_readonly_addresses: meta.loaded_addresses.readonly,
+            compute_units_consumed: meta.compute_units_consumed,
+        })
+    }
+}
+
+impl TryFrom<&TransactionStatusMeta> for status::TransactionStatusMeta {
+    type Error = anyhow::Error;
+
+    fn try_from(meta: &TransactionStatusMeta) -> Result<Self, Self::Error> {
+        let inner_instructions = meta
+            .inner_instructions
+            .iter()
+            .map(|inner| {
+                Ok(status::InnerInstructions {
+                    index: to_u8(inner.index)?,
+                    instructions: inner
+                        .instructions
+                        .iter()
+                        .map(|ix| {
+                            Ok(status::InnerInstruction {
+                                instruction: SdkCompiledInstruction {
+                                    program_id_index: to_u8(ix.program_id_index)?,
+                                    accounts: ix.accounts.clone(),
+                                    data: ix.data.clone(),
+                                },
+                                stack_height: ix.stack_height,
+                            })
+                        })
+                        .collect::<Result<_, anyhow::Error>>()?,
+                })
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+
+        Ok(status::TransactionStatusMeta {
+            status: Ok(()),
+            fee: meta.fee,
+            pre_balances: meta.pre_balances.clone(),
+            post_balances: meta.post_balances.clone(),
+            inner_instructions: Some(inner_instructions),
+            log_messages: None,
+            pre_token_balances: Some(to_sdk_token_balances(&meta.pre_token_balances)?),
+            post_token_balances: Some(to_sdk_token_balances(&meta.post_token_balances)?),
+            rewards: None,
+            loaded_addresses: LoadedAddresses {
+                writable: meta.loaded_writable_addresses.clone(),
+                readonly: meta.loaded_readonly_addresses.clone(),
+            },
+            return_data: None,
+            compute_units_consumed: meta.compute_units_consumed,
+            cost_units: None,
+        })
+    }
+}
+
+fn from_sdk_token_balances(
+    balances: Option<Vec<status::TransactionTokenBalance>>,
+) -> Result<Vec<TokenBalance>, anyhow::Error> {
+    let optional_pubkey = |address: &str| {
+        if address.is_empty() {
+            Ok(Pubkey::default())
+        } else {
+            parse_pubkey(address)
+        }
+    };
+    balances
+        .unwrap_or_default()
+        .into_iter()
+        .map(|balance| {
+            let amount = balance.ui_token_amount;
+            Ok(TokenBalance {
+                account_index: balance.account_index as u32,
+                mint: parse_pubkey(&balance.mint)?,
+                ui_token_amount: Some(TokenAmount {
+                    ui_amount: amount.ui_amount,
+                    decimals: amount.decimals as u32,
+                    amount: amount
+                        .amount
+                        .parse()
+                        .with_context(|| format!("token amount {:?}", amount.amount))?,
+                    ui_amount_string: amount.ui_amount_string,
+                }),
+                owner: optional_pubkey(&balance.owner)?,
+                program_id: optional_pubkey(&balance.program_id)?,
+            })
+        })
+        .collect()
+}
+
+/// `Pubkey::default()` owners and program ids go back to empty strings
+fn to_sdk_token_balances(
+    balances: &[TokenBalance],
+) -> Result<Vec<status::TransactionTokenBalance>, anyhow::Error> {
+    let optional_pubkey = |address: &Pubkey| {
+        if *address == Pubkey::default() {
+            String::new()
+        } else {
+            address.to_string()
+        }
+    };
+    balances
+        .iter()
+        .map(|balance| {
+            let amount = balance
+                .ui_token_amount
+                .as_ref()
+                .ok_or_else(|| anyhow!("token balance {} has no amount", balance.account_index))?;
+            Ok(status::TransactionTokenBalance {
+                account_index: to_u8(balance.account_index)?,
+                mint: balance.mint.to_string(),
+                ui_token_amount: UiTokenAmount {
+                    ui_amount: amount.ui_amount,
+                    decimals: to_u8(amount.decimals)?,
+                    amount: amount.amount.to_string(),
+                    ui_amount_string: amount.ui_amount_string.clone(),
+                },
+                owner: optional_pubkey(&balance.owner),
+                program_id: optional_pubkey(&balance.program_id),
+            })
+        })
+        .collect()
+}
+
+fn to_u8(value: u32) -> Result<u8, anyhow::Error> {
+    u8::try_from(value).map_err(|_| anyhow!("{} does not fit in a u8", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_signature::Signature;
+
+    use super::*;
+
+    fn token_balance(account_index: u32, mint: Pubkey, ui_amount: Option<f64>) -> TokenBalance {
+        TokenBalance {
+            account_index,
+            mint,
+            ui_token_amount: Some(TokenAmount {
+                ui_amount,
+                decimals: 6,
+                amount: 1_500_000,
+                ui_amount_string: "1.5".to_string(),
+            }),
+            owner: Pubkey::new_unique(),
+            program_id: spl_token::ID,
+        }
+    }
+
+    fn transaction(versioned: bool) -> UnifiedTransaction {
+        let account_keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let mint = Pubkey::new_unique();
+        let (address_table_lookups, loaded_writable_addresses, loaded_readonly_addresses) =
+            if versioned {
+                (
+                    vec![MessageAddressTableLookup {
+                        account_key: Pubkey::new_unique(),
+                        writable_indexes: vec![3, 1],
+                        readonly_indexes: vec![0],
+                    }],
+                    vec![Pubkey::new_unique(), Pubkey::new_unique()],
+                    vec![Pubkey::new_unique()],
+                )
+            } else {
+                (Vec::new(), Vec::new(), Vec::new())
+            };
+
+        UnifiedTransaction {
+            signature: Signature::from([7; 64]),
+            slot: 42,
+            block_time: None,
+            transaction: Transaction {
+                signatures: vec![Signature::from([7; 64])],
+                message: Some(Message {
+                    header: Some(MessageHeader {
+                        num_required_signatures: 1,
+                        num_readonly_signed_accounts: 0,
+                        num_readonly_unsigned_accounts: 1,
+                    }),
+                    account_keys,
+                    recent_blockhash: vec![9; 32],
+                    instructions: vec![CompiledInstruction {
+                        program_id_index: 3,
+                        accounts: vec![0, 1, 2],
+                        data: vec![3, 1, 2, 3],
+                    }],
+                    versioned,
+                    address_table_lookups,
+                }),
+            },
+            meta: TransactionStatusMeta {
+                fee: 5000,
+                pre_balances: vec![10, 20, 30, 40],
+                post_balances: vec![5, 20, 30, 40],
+                inner_instructions: vec![InnerInstructions {
+                    index: 0,
+                    instructions: vec![
+                        InnerInstruction {
+                            program_id_index: 3,
+                            accounts: vec![1, 2],
+                            data: vec![12],
+                            stack_height: Some(2),
+                        },
+                        InnerInstruction {
+                            program_id_index: 3,
+                            accounts: vec![2],
+                            data: Vec::new(),
+                            stack_height: None,
+                        },
+                    ],
+                }],
+                pre_token_balances: vec![token_balance(1, mint, Some(1.5))],
+                post_token_balances: vec![
+                    token_balance(1, mint, None),
+                    TokenBalance {
+                        owner: Pubkey::default(),
+                        program_id: Pubkey::default(),
+                        ..token_balance(2, mint, Some(0.0))
+                    },
+                ],
+                loaded_writable_addresses,
+                loaded_readonly_addresses,
+                compute_units_consumed: Some(1234),
+            },
+            index: None,
+        }
+    }
+
+    #[test]
+    fn versioned_round_trip() {
+        for versioned in [false, true] {
+            let transaction = transaction(versioned);
+            let (sdk_transaction, sdk_meta) = transaction.to_versioned().unwrap();
+            assert_eq!(
+                matches!(sdk_transaction.message, VersionedMessage::V0(_)),
+                versioned
+            );
+            let round_tripped =
+                UnifiedTransaction::from_versioned(transaction.slot, &sdk_transaction, sdk_meta)
+                    .unwrap();
+            assert_eq!(round_tripped, transaction);
+        }
+    }
+}
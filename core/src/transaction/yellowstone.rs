@@ -0,0 +1,682 @@
+//! Offline decoding of Yellowstone (Geyser gRPC) transaction captures: files
+//! of length-delimited `SubscribeUpdateTransaction` messages, each prefixed
+//! with its varint-encoded length as written by prost's
+//! `encode_length_delimited`.
+
+use std::io::{ErrorKind, Read};
+
+use anyhow::{anyhow, bail, Context};
+use prost::Message as _;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+
+use super::encoded::parse_pubkey;
+use super::transaction::{
+    CompiledInstruction, InnerInstruction, InnerInstructions, Message, MessageAddressTableLookup,
+    MessageHeader, TokenAmount, TokenBalance, Transaction, TransactionStatusMeta,
+    UnifiedTransaction,
+};
+
+/// The subset of `geyser.proto` and `solana-storage.proto` needed to rebuild a
+/// transaction, with the upstream field numbers. Fields left out are skipped
+/// when decoding.
+pub mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdateTransaction {
+        #[prost(message, optional, tag = "1")]
+        pub transaction: Option<SubscribeUpdateTransactionInfo>,
+        #[prost(uint64, tag = "2")]
+        pub slot: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdateTransactionInfo {
+        #[prost(bytes = "vec", tag = "1")]
+        pub signature: Vec<u8>,
+        #[prost(bool, tag = "2")]
+        pub is_vote: bool,
+        #[prost(message, optional, tag = "3")]
+        pub transaction: Option<Transaction>,
+        #[prost(message, optional, tag = "4")]
+        pub meta: Option<TransactionStatusMeta>,
+        #[prost(uint64, tag = "5")]
+        pub index: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Transaction {
+        #[prost(bytes = "vec", repeated, tag = "1")]
+        pub signatures: Vec<Vec<u8>>,
+        #[prost(message, optional, tag = "2")]
+        pub message: Option<Message>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Message {
+        #[prost(message, optional, tag = "1")]
+        pub header: Option<MessageHeader>,
+        #[prost(bytes = "vec", repeated, tag = "2")]
+        pub account_keys: Vec<Vec<u8>>,
+        #[prost(bytes = "vec", tag = "3")]
+        pub recent_blockhash: Vec<u8>,
+        #[prost(message, repeated, tag = "4")]
+        pub instructions: Vec<CompiledInstruction>,
+        #[prost(bool, tag = "5")]
+        pub versioned: bool,
+        #[prost(message, repeated, tag = "6")]
+        pub address_table_lookups: Vec<MessageAddressTableLookup>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct MessageHeader {
+        #[prost(uint32, tag = "1")]
+        pub num_required_signatures: u32,
+        #[prost(uint32, tag = "2")]
+        pub num_readonly_signed_accounts: u32,
+        #[prost(uint32, tag = "3")]
+        pub num_readonly_unsigned_accounts: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct MessageAddressTableLookup {
+        #[prost(bytes = "vec", tag = "1")]
+        pub account_key: Vec<u8>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub writable_indexes: Vec<u8>,
+        #[prost(bytes = "vec", tag = "3")]
+        pub readonly_indexes: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct CompiledInstruction {
+        #[prost(uint32, tag = "1")]
+        pub program_id_index: u32,
+        #[prost(bytes = "vec", tag = "2")]
+        pub accounts: Vec<u8>,
+        #[prost(bytes = "vec", tag = "3")]
+        pub data: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TransactionStatusMeta {
+        #[prost(uint64, tag = "2")]
+        pub fee: u64,
+        #[prost(uint64, repeated, tag = "3")]
+        pub pre_balances: Vec<u64>,
+        #[prost(uint64, repeated, tag = "4")]
+        pub post_balances: Vec<u64>,
+        #[prost(message, repeated, tag = "5")]
+        pub inner_instructions: Vec<InnerInstructions>,
+        #[prost(message, repeated, tag = "7")]
+        pub pre_token_balances: Vec<TokenBalance>,
+        #[prost(message, repeated, tag = "8")]
+        pub post_token_balances: Vec<TokenBalance>,
+        #[prost(bytes = "vec", repeated, tag = "12")]
+        pub loaded_writable_addresses: Vec<Vec<u8>>,
+        #[prost(bytes = "vec", repeated, tag = "13")]
+        pub loaded_readonly_addresses: Vec<Vec<u8>>,
+        #[prost(uint64, optional, tag = "16")]
+        pub compute_units_consumed: Option<u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct InnerInstructions {
+        #[prost(uint32, tag = "1")]
+        pub index: u32,
+        #[prost(message, repeated, tag = "2")]
+        pub instructions: Vec<InnerInstruction>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct InnerInstruction {
+        #[prost(uint32, tag = "1")]
+        pub program_id_index: u32,
+        #[prost(bytes = "vec", tag = "2")]
+        pub accounts: Vec<u8>,
+        #[prost(bytes = "vec", tag = "3")]
+        pub data: Vec<u8>,
+        #[prost(uint32, optional, tag = "4")]
+        pub stack_height: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TokenBalance {
+        #[prost(uint32, tag = "1")]
+        pub account_index: u32,
+        #[prost(string, tag = "2")]
+        pub mint: String,
+        #[prost(message, optional, tag = "3")]
+        pub ui_token_amount: Option<UiTokenAmount>,
+        #[prost(string, tag = "4")]
+        pub owner: String,
+        #[prost(string, tag = "5")]
+        pub program_id: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct UiTokenAmount {
+        #[prost(double, tag = "1")]
+        pub ui_amount: f64,
+        #[prost(uint32, tag = "2")]
+        pub decimals: u32,
+        #[prost(string, tag = "3")]
+        pub amount: String,
+        #[prost(string, tag = "4")]
+        pub ui_amount_string: String,
+    }
+}
+
+/// Largest message accepted, well above any transaction update, so a corrupt
+/// length prefix fails instead of allocating the whole length
+const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+
+/// Reads a capture one message at a time, so captures larger than memory can
+/// be replayed
+pub struct YellowstoneCaptureReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    /// Messages read so far, for error context
+    position: usize,
+}
+
+impl<R: Read> YellowstoneCaptureReader<R> {
+    /// Length prefixes are read a byte at a time, so `reader` should be
+    /// buffered
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// The next raw message, or `None` at a clean end of the capture
+    pub fn next_update(
+        &mut self,
+    ) -> Result<Option<proto::SubscribeUpdateTransaction>, anyhow::Error> {
+        let Some(length) = self.read_length()? else {
+            return Ok(None);
+        };
+        if length > MAX_MESSAGE_LEN {
+            bail!(
+                "message {} is {} bytes, more than the {} allowed",
+                self.position,
+                length,
+                MAX_MESSAGE_LEN
+            );
+        }
+        self.buffer.resize(length, 0);
+        self.reader
+            .read_exact(&mut self.buffer)
+            .with_context(|| format!("message {} is truncated", self.position))?;
+        let update = proto::SubscribeUpdateTransaction::decode(self.buffer.as_slice())
+            .with_context(|| {
+                format!(
+                    "message {} is not a SubscribeUpdateTransaction",
+                    self.position
+                )
+            })?;
+        self.position += 1;
+        Ok(Some(update))
+    }
+
+    /// The varint length prefix; `None` on end of input before its first byte
+    fn read_length(&mut self) -> Result<Option<usize>, anyhow::Error> {
+        let mut length: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let mut byte = [0u8];
+            match self.reader.read_exact(&mut byte) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof && shift == 0 => return Ok(None),
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("length of message {} is truncated", self.position)
+                    })
+                }
+            }
+            length |= u64::from(byte[0] & 0x7f) << shift;
+            if byte[0] & 0x80 == 0 {
+                return usize::try_from(length)
+                    .map(Some)
+                    .map_err(|_| anyhow!("message {} is {} bytes", self.position, length));
+            }
+        }
+        bail!("length of message {} is not a valid varint", self.position)
+    }
+}
+
+impl<R: Read> Iterator for YellowstoneCaptureReader<R> {
+    type Item = Result<UnifiedTransaction, anyhow::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let position = self.position;
+        match self.next_update() {
+            Ok(Some(update)) => Some(
+                UnifiedTransaction::try_from(update)
+                    .with_context(|| format!("message {}", position)),
+            ),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl TryFrom<proto::SubscribeUpdateTransaction> for UnifiedTransaction {
+    type Error = anyhow::Error;
+
+    /// The block time is not part of the update and is left unset
+    fn try_from(update: proto::SubscribeUpdateTransaction) -> Result<Self, Self::Error> {
+        let info = update
+            .transaction
+            .ok_or_else(|| anyhow!("update has no transaction"))?;
+        let signature = to_signature(&info.signature)?;
+        let transaction = info
+            .transaction
+            .ok_or_else(|| anyhow!("transaction {} has no body", signature))?;
+        let meta = match info.meta {
+            Some(meta) => {
+                convert_meta(meta).with_context(|| format!("transaction {}", signature))?
+            }
+            None => TransactionStatusMeta::default(),
+        };
+
+        Ok(UnifiedTransaction {
+            signature,
+            slot: update.slot,
+            block_time: None,
+            transaction: convert_transaction(transaction)
+                .with_context(|| format!("transaction {}", signature))?,
+            meta,
+            index: Some(info.index),
+        })
+    }
+}
+
+fn convert_transaction(transaction: proto::Transaction) -> Result<Transaction, anyhow::Error> {
+    let message = transaction
+        .message
+        .map(|message| -> Result<Message, anyhow::Error> {
+            Ok(Message {
+                header: message.header.map(|header| MessageHeader {
+                    num_required_signatures: header.num_required_signatures,
+                    num_readonly_signed_accounts: header.num_readonly_signed_accounts,
+                    num_readonly_unsigned_accounts: header.num_readonly_unsigned_accounts,
+                }),
+                account_keys: to_pubkeys(&message.account_keys)?,
+                recent_blockhash: message.recent_blockhash,
+                instructions: message
+                    .instructions
+                    .into_iter()
+                    .map(|ix| CompiledInstruction {
+                        program_id_index: ix.program_id_index,
+                        accounts: ix.accounts,
+                        data: ix.data,
+                    })
+                    .collect(),
+                versioned: message.versioned,
+                address_table_lookups: message
+                    .address_table_lookups
+                    .into_iter()
+                    .map(|lookup| {
+                        Ok(MessageAddressTableLookup {
+                            account_key: to_pubkey(&lookup.account_key)?,
+                            writable_indexes: lookup.writable_indexes,
+                            readonly_indexes: lookup.readonly_indexes,
+                        })
+                    })
+                    .collect::<Result<_, anyhow::Error>>()?,
+            })
+        })
+        .transpose()?;
+
+    Ok(Transaction {
+        signatures: transaction
+            .signatures
+            .iter()
+            .map(|signature| to_signature(signature))
+            .collect::<Result<_, _>>()?,
+        message,
+    })
+}
+
+fn convert_meta(
+    meta: proto::TransactionStatusMeta,
+) -> Result<TransactionStatusMeta, anyhow::Error> {
+    Ok(TransactionStatusMeta {
+        fee: meta.fee,
+        pre_balances: meta.pre_balances,
+        post_balances: meta.post_balances,
+        inner_instructions: meta
+            .inner_instructions
+            .into_iter()
+            .map(|inner| InnerInstructions {
+                index: inner.index,
+                instructions: inner
+                    .instructions
+                    .into_iter()
+                    .map(|ix| InnerInstruction {
+                        program_id_index: ix.program_id_index,
+                        accounts: ix.accounts,
+                        data: ix.data,
+                        stack_height: ix.stack_height,
+                    })
+                    .collect(),
+            })
+            .collect(),
+        pre_token_balances: convert_token_balances(meta.pre_token_balances)?,
+        post_token_balances: convert_token_balances(meta.post_token_balances)?,
+        loaded_writable_addresses: to_pubkeys(&meta.loaded_writable_addresses)?,
+        loaded_readonly_addresses: to_pubkeys(&meta.loaded_readonly_addresses)?,
+        compute_units_consumed: meta.compute_units_consumed,
+    })
+}
+
+/// Empty owners and program ids (nodes before v1.15) become
+/// `Pubkey::default()`
+fn convert_token_balances(
+    balances: Vec<proto::TokenBalance>,
+) -> Result<Vec<TokenBalance>, anyhow::Error> {
+    let optional_pubkey = |address: &str| {
+        if address.is_empty() {
+            Ok(Pubkey::default())
+        } else {
+            parse_pubkey(address)
+        }
+    };
+    balances
+        .into_iter()
+        .map(|balance| {
+            Ok(TokenBalance {
+                account_index: balance.account_index,
+                mint: parse_pubkey(&balance.mint)?,
+                ui_token_amount: balance
+                    .ui_token_amount
+                    .map(|amount| -> Result<TokenAmount, anyhow::Error> {
+                        Ok(TokenAmount {
+                            // The proto has no presence for doubles; zero
+                            // stands for a missing amount, as in solana-storage-proto
+                            ui_amount: (amount.ui_amount != 0.0).then_some(amount.ui_amount),
+                            decimals: amount.decimals,
+                            amount: amount
+                                .amount
+                                .parse()
+                                .with_context(|| format!("token amount {:?}", amount.amount))?,
+                            ui_amount_string: amount.ui_amount_string,
+                        })
+                    })
+                    .transpose()?,
+                owner: optional_pubkey(&balance.owner)?,
+                program_id: optional_pubkey(&balance.program_id)?,
+            })
+        })
+        .collect()
+}
+
+fn to_signature(bytes: &[u8]) -> Result<Signature, anyhow::Error> {
+    Signature::try_from(bytes)
+        .map_err(|_| anyhow!("signature is {} bytes, expected 64", bytes.len()))
+}
+
+fn to_pubkey(bytes: &[u8]) -> Result<Pubkey, anyhow::Error> {
+    Pubkey::try_from(bytes).map_err(|_| anyhow!("pubkey is {} bytes, expected 32", bytes.len()))
+}
+
+fn to_pubkeys(keys: &[Vec<u8>]) -> Result<Vec<Pubkey>, anyhow::Error> {
+    keys.iter().map(|key| to_pubkey(key)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> Pubkey {
+        Pubkey::new_unique()
+    }
+
+    /// An update and the transaction it should convert to
+    fn update(
+        versioned: bool,
+        slot: u64,
+    ) -> (proto::SubscribeUpdateTransaction, UnifiedTransaction) {
+        let signature = Signature::new_unique();
+        let account_keys = [key(), key(), key(), key()];
+        let (loaded_writable, loaded_readonly, table) = if versioned {
+            (vec![key()], vec![key()], Some(key()))
+        } else {
+            (Vec::new(), Vec::new(), None)
+        };
+        let [mint, owner] = [key(), key()];
+        let bytes = |keys: &[Pubkey]| keys.iter().map(|key| key.to_bytes().to_vec()).collect();
+
+        let update = proto::SubscribeUpdateTransaction {
+            transaction: Some(proto::SubscribeUpdateTransactionInfo {
+                signature: signature.as_ref().to_vec(),
+                is_vote: false,
+                transaction: Some(proto::Transaction {
+                    signatures: vec![signature.as_ref().to_vec()],
+                    message: Some(proto::Message {
+                        header: Some(proto::MessageHeader {
+                            num_required_signatures: 1,
+                            num_readonly_signed_accounts: 0,
+                            num_readonly_unsigned_accounts: 1,
+                        }),
+                        account_keys: bytes(&account_keys),
+                        recent_blockhash: vec![9; 32],
+                        instructions: vec![proto::CompiledInstruction {
+                            program_id_index: 3,
+                            accounts: vec![0, 1, 4],
+                            data: vec![3, 1, 2],
+                        }],
+                        versioned,
+                        address_table_lookups: table
+                            .map(|table| proto::MessageAddressTableLookup {
+                                account_key: table.to_bytes().to_vec(),
+                                writable_indexes: vec![2],
+                                readonly_indexes: vec![0],
+                            })
+                            .into_iter()
+                            .collect(),
+                    }),
+                }),
+                meta: Some(proto::TransactionStatusMeta {
+                    fee: 5000,
+                    pre_balances: vec![10, 20, 30, 40],
+                    post_balances: vec![5, 25, 30, 40],
+                    inner_instructions: vec![proto::InnerInstructions {
+                        index: 0,
+                        instructions: vec![proto::InnerInstruction {
+                            program_id_index: 3,
+                            accounts: vec![1],
+                            data: vec![12],
+                            stack_height: Some(2),
+                        }],
+                    }],
+                    pre_token_balances: vec![proto::TokenBalance {
+                        account_index: 1,
+                        mint: mint.to_string(),
+                        ui_token_amount: Some(proto::UiTokenAmount {
+                            ui_amount: 1.5,
+                            decimals: 6,
+                            amount: "1500000".to_string(),
+                            ui_amount_string: "1.5".to_string(),
+                        }),
+                        owner: owner.to_string(),
+                        program_id: spl_token::ID.to_string(),
+                    }],
+                    // Before v1.15 nodes recorded neither owner nor program
+                    post_token_balances: vec![proto::TokenBalance {
+                        account_index: 1,
+                        mint: mint.to_string(),
+                        ui_token_amount: Some(proto::UiTokenAmount {
+                            ui_amount: 0.0,
+                            decimals: 6,
+                            amount: "0".to_string(),
+                            ui_amount_string: "0".to_string(),
+                        }),
+                        owner: String::new(),
+                        program_id: String::new(),
+                    }],
+                    loaded_writable_addresses: bytes(&loaded_writable),
+                    loaded_readonly_addresses: bytes(&loaded_readonly),
+                    compute_units_consumed: Some(1234),
+                }),
+                index: 7,
+            }),
+            slot,
+        };
+
+        let transaction = UnifiedTransaction {
+            signature,
+            slot,
+            block_time: None,
+            transaction: Transaction {
+                signatures: vec![signature],
+                message: Some(Message {
+                    header: Some(MessageHeader {
+                        num_required_signatures: 1,
+                        num_readonly_signed_accounts: 0,
+                        num_readonly_unsigned_accounts: 1,
+                    }),
+                    account_keys: account_keys.to_vec(),
+                    recent_blockhash: vec![9; 32],
+                    instructions: vec![CompiledInstruction {
+                        program_id_index: 3,
+                        accounts: vec![0, 1, 4],
+                        data: vec![3, 1, 2],
+                    }],
+                    versioned,
+                    address_table_lookups: table
+                        .map(|table| MessageAddressTableLookup {
+                            account_key: table,
+                            writable_indexes: vec![2],
+                            readonly_indexes: vec![0],
+                        })
+                        .into_iter()
+                        .collect(),
+                }),
+            },
+            meta: TransactionStatusMeta {
+                fee: 5000,
+                pre_balances: vec![10, 20, 30, 40],
+                post_balances: vec![5, 25, 30, 40],
+                inner_instructions: vec![InnerInstructions {
+                    index: 0,
+                    instructions: vec![InnerInstruction {
+                        program_id_index: 3,
+                        accounts: vec![1],
+                        data: vec![12],
+                        stack_height: Some(2),
+                    }],
+                }],
+                pre_token_balances: vec![TokenBalance {
+                    account_index: 1,
+                    mint,
+                    ui_token_amount: Some(TokenAmount {
+                        ui_amount: Some(1.5),
+                        decimals: 6,
+                        amount: 1_500_000,
+                        ui_amount_string: "1.5".to_string(),
+                    }),
+                    owner,
+                    program_id: spl_token::ID,
+                }],
+                post_token_balances: vec![TokenBalance {
+                    account_index: 1,
+                    mint,
+                    ui_token_amount: Some(TokenAmount {
+                        ui_amount: None,
+                        decimals: 6,
+                        amount: 0,
+                        ui_amount_string: "0".to_string(),
+                    }),
+                    owner: Pubkey::default(),
+                    program_id: Pubkey::default(),
+                }],
+                loaded_writable_addresses: loaded_writable,
+                loaded_readonly_addresses: loaded_readonly,
+                compute_units_consumed: Some(1234),
+            },
+            index: Some(7),
+        };
+        (update, transaction)
+    }
+
+    fn capture(updates: &[proto::SubscribeUpdateTransaction]) -> Vec<u8> {
+        let mut capture = Vec::new();
+        for update in updates {
+            update.encode_length_delimited(&mut capture).unwrap();
+        }
+        capture
+    }
+
+    #[test]
+    fn reads_length_delimited_updates() {
+        let (legacy, legacy_transaction) = update(false, 100);
+        let (v0, v0_transaction) = update(true, 101);
+        let capture = capture(&[legacy, v0]);
+
+        let transactions = YellowstoneCaptureReader::new(capture.as_slice())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(transactions, [legacy_transaction, v0_transaction]);
+
+        assert!(YellowstoneCaptureReader::new(&[][..]).next().is_none());
+    }
+
+    #[test]
+    fn truncated_messages_are_errors() {
+        let capture = capture(&[update(false, 100).0, update(true, 101).0]);
+
+        for cut in [1, capture.len() / 4] {
+            let mut reader = YellowstoneCaptureReader::new(&capture[..capture.len() - cut]);
+            assert!(reader.next().unwrap().is_ok());
+            let err = reader.next().unwrap().unwrap_err();
+            assert!(
+                format!("{:#}", err).contains("message 1 is truncated"),
+                "{:#}",
+                err
+            );
+        }
+
+        // Input ending inside a length prefix
+        let mut reader = YellowstoneCaptureReader::new(&[0x80][..]);
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("length of message 0 is truncated"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn oversized_lengths_are_rejected_before_allocating() {
+        let mut capture = Vec::new();
+        prost::encoding::encode_varint(MAX_MESSAGE_LEN as u64 + 1, &mut capture);
+
+        let mut reader = YellowstoneCaptureReader::new(capture.as_slice());
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("more than"), "{}", err);
+        assert_eq!(reader.buffer.capacity(), 0);
+
+        // Not a varint at all: ten continuation bytes
+        let mut reader = YellowstoneCaptureReader::new(&[0xff; 10][..]);
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("not a valid varint"), "{}", err);
+    }
+
+    #[test]
+    fn undecodable_messages_are_errors() {
+        let mut capture = capture(&[update(false, 100).0]);
+        // Length 2, then a field with the reserved wire type 7
+        capture.extend([2, 0x0f, 0]);
+
+        let mut reader = YellowstoneCaptureReader::new(capture.as_slice());
+        assert!(reader.next().unwrap().is_ok());
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("not a SubscribeUpdateTransaction"),
+            "{:#}",
+            err
+        );
+    }
+}
@@ -0,0 +1,461 @@
+//! Streaming readers for transaction captures, so a capture never has to fit
+//! in memory.
+//!
+//! Two formats are read:
+//! - the JSON envelope written by the capture tool: an object with
+//!   `start_time`, `end_time`, `duration_seconds`, `transaction_count` and a
+//!   `transactions` array, deserialized one element at a time
+//! - JSONL: one `CapturedTransaction` per line, with the metadata derived from
+//!   the transactions
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+use std::path::Path;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread::JoinHandle;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+
+use indexer_core::transaction::transaction::UnifiedTransaction;
+
+/// Transactions parsed from the envelope but not yet consumed
+const ENVELOPE_BUFFER: usize = 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturedTransaction {
+    pub transaction: UnifiedTransaction,
+    pub captured_at: DateTime<Utc>,
+    pub slot: u64,
+}
+
+/// Everything in a capture besides its transactions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureMetadata {
+    /// From the envelope, or the earliest and latest `captured_at` of a
+    /// JSONL capture; `None` for an empty JSONL capture
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub duration_seconds: u64,
+    /// As declared by the envelope, or the lines read for JSONL
+    pub transaction_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    Json,
+    Jsonl,
+}
+
+/// Yields a capture's transactions in order. The metadata is returned by
+/// `finish` once every transaction has been read, since the envelope may put
+/// it after the transactions.
+pub struct CaptureReader {
+    source: Source,
+    transactions_read: usize,
+    earliest: Option<DateTime<Utc>>,
+    latest: Option<DateTime<Utc>>,
+}
+
+enum Source {
+    Json {
+        receiver: Receiver<EnvelopeItem>,
+        parser: Option<JoinHandle<()>>,
+        metadata: Option<Result<CaptureMetadata>>,
+    },
+    Jsonl {
+        lines: Lines<BufReader<File>>,
+        line: usize,
+    },
+}
+
+enum EnvelopeItem {
+    Transaction(Box<CapturedTransaction>),
+    /// Sent last, after the closing brace of the envelope
+    Done(Result<CaptureMetadata>),
+}
+
+impl CaptureReader {
+    pub fn open(path: impl AsRef<Path>, format: CaptureFormat) -> Result<Self> {
+        let path = path.as_ref();
+        let file = BufReader::new(
+            File::open(path).with_context(|| format!("opening {}", path.display()))?,
+        );
+        let source = match format {
+            CaptureFormat::Json => {
+                let (sender, receiver) = sync_channel(ENVELOPE_BUFFER);
+                let parser = std::thread::spawn(move || parse_envelope(file, sender));
+                Source::Json {
+                    receiver,
+                    parser: Some(parser),
+                    metadata: None,
+                }
+            }
+            CaptureFormat::Jsonl => Source::Jsonl {
+                lines: file.lines(),
+                line: 0,
+            },
+        };
+        Ok(Self {
+            source,
+            transactions_read: 0,
+            earliest: None,
+            latest: None,
+        })
+    }
+
+    /// Reads whatever is left of the capture and returns its metadata
+    pub fn finish(mut self) -> Result<CaptureMetadata> {
+        for transaction in self.by_ref() {
+            transaction?;
+        }
+        match &mut self.source {
+            Source::Json { metadata, .. } => metadata
+                .take()
+                .unwrap_or_else(|| Err(anyhow!("capture ended before its metadata"))),
+            Source::Jsonl { .. } => Ok(CaptureMetadata {
+                start_time: self.earliest,
+                end_time: self.latest,
+                duration_seconds: match (self.earliest, self.latest) {
+                    (Some(start), Some(end)) => (end - start).num_seconds().max(0) as u64,
+                    _ => 0,
+                },
+                transaction_count: self.transactions_read,
+            }),
+        }
+    }
+
+    fn next_transaction(&mut self) -> Option<Result<CapturedTransaction>> {
+        match &mut self.source {
+            Source::Json {
+                receiver,
+                parser,
+                metadata,
+            } => {
+                if metadata.is_some() {
+                    return None;
+                }
+                match receiver.recv() {
+                    Ok(EnvelopeItem::Transaction(transaction)) => Some(Ok(*transaction)),
+                    Ok(EnvelopeItem::Done(Ok(done))) => {
+                        *metadata = Some(Ok(done));
+                        None
+                    }
+                    Ok(EnvelopeItem::Done(Err(e))) => {
+                        *metadata = Some(Err(anyhow!("{:#}", e)));
+                        Some(Err(e))
+                    }
+                    // The parser only stops sending early by panicking
+                    Err(_) => {
+                        if let Some(Err(panic)) = parser.take().map(JoinHandle::join) {
+                            std::panic::resume_unwind(panic);
+                        }
+                        *metadata = Some(Err(anyhow!("capture parser stopped")));
+                        None
+                    }
+                }
+            }
+            Source::Jsonl { lines, line } => loop {
+                let text = match lines.next()? {
+                    Ok(text) => text,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                *line += 1;
+                if text.trim().is_empty() {
+                    continue;
+                }
+                return Some(serde_json::from_str(&text).with_context(|| format!("line {}", line)));
+            },
+        }
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = Result<CapturedTransaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let transaction = self.next_transaction()?;
+        if let Ok(transaction) = &transaction {
+            self.transactions_read += 1;
+            let captured_at = transaction.captured_at;
+            self.earliest = Some(self.earliest.map_or(captured_at, |t| t.min(captured_at)));
+            self.latest = Some(self.latest.map_or(captured_at, |t| t.max(captured_at)));
+        }
+        Some(transaction)
+    }
+}
+
+impl Drop for CaptureReader {
+    fn drop(&mut self) {
+        // Unblock a parser still sending, then let it wind down
+        if let Source::Json {
+            receiver, parser, ..
+        } = &mut self.source
+        {
+            let (_, closed) = sync_channel(0);
+            drop(std::mem::replace(receiver, closed));
+            if let Some(parser) = parser.take() {
+                let _ = parser.join();
+            }
+        }
+    }
+}
+
+/// Runs on the parser thread until the envelope is read or the reader is
+/// dropped
+fn parse_envelope(file: BufReader<File>, sender: SyncSender<EnvelopeItem>) {
+    let mut deserializer = serde_json::Deserializer::from_reader(file);
+    let result = deserializer
+        .deserialize_map(EnvelopeVisitor { sender: &sender })
+        .and_then(|metadata| deserializer.end().map(|()| metadata))
+        .map_err(anyhow::Error::from);
+    // Fails only if the reader is gone
+    let _ = sender.send(EnvelopeItem::Done(result));
+}
+
+struct EnvelopeVisitor<'a> {
+    sender: &'a SyncSender<EnvelopeItem>,
+}
+
+impl<'de> Visitor<'de> for EnvelopeVisitor<'_> {
+    type Value = CaptureMetadata;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a transaction capture")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut start_time = None;
+        let mut end_time = None;
+        let mut duration_seconds = None;
+        let mut transaction_count = None;
+        let mut transactions = false;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "start_time" => start_time = Some(map.next_value()?),
+                "end_time" => end_time = Some(map.next_value()?),
+                "duration_seconds" => duration_seconds = Some(map.next_value()?),
+                "transaction_count" => transaction_count = Some(map.next_value()?),
+                "transactions" => {
+                    map.next_value_seed(TransactionsSeed {
+                        sender: self.sender,
+                    })?;
+                    transactions = true;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        if !transactions {
+            return Err(de::Error::missing_field("transactions"));
+        }
+
+        Ok(CaptureMetadata {
+            start_time: Some(start_time.ok_or_else(|| de::Error::missing_field("start_time"))?),
+            end_time: Some(end_time.ok_or_else(|| de::Error::missing_field("end_time"))?),
+            duration_seconds: duration_seconds
+                .ok_or_else(|| de::Error::missing_field("duration_seconds"))?,
+            transaction_count: transaction_count
+                .ok_or_else(|| de::Error::missing_field("transaction_count"))?,
+        })
+    }
+}
+
+/// Forwards each element of the `transactions` array as it is parsed
+struct TransactionsSeed<'a> {
+    sender: &'a SyncSender<EnvelopeItem>,
+}
+
+impl<'de> DeserializeSeed<'de> for TransactionsSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TransactionsSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of captured transactions")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(transaction) = seq.next_element::<CapturedTransaction>()? {
+            self.sender
+                .send(EnvelopeItem::Transaction(Box::new(transaction)))
+                .map_err(|_| de::Error::custom("capture reader dropped"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A capture file removed when dropped
+    struct TempCapture(PathBuf);
+
+    impl TempCapture {
+        fn new(name: &str, content: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("capture-{}-{}", std::process::id(), name));
+            std::fs::write(&path, content).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempCapture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn time(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    fn captured(slot: u64, seconds: i64) -> String {
+        serde_json::to_string(&CapturedTransaction {
+            transaction: UnifiedTransaction {
+                slot,
+                ..Default::default()
+            },
+            captured_at: time(seconds),
+            slot,
+        })
+        .unwrap()
+    }
+
+    fn metadata_fields() -> String {
+        format!(
+            r#""start_time":"{}","end_time":"{}","duration_seconds":60,"transaction_count":2"#,
+            time(0).to_rfc3339(),
+            time(60).to_rfc3339()
+        )
+    }
+
+    fn declared_metadata() -> CaptureMetadata {
+        CaptureMetadata {
+            start_time: Some(time(0)),
+            end_time: Some(time(60)),
+            duration_seconds: 60,
+            transaction_count: 2,
+        }
+    }
+
+    fn read_slots(reader: &mut CaptureReader) -> Vec<u64> {
+        reader.by_ref().map(|tx| tx.unwrap().slot).collect()
+    }
+
+    #[test]
+    fn envelope_metadata_before_and_after_the_transactions() {
+        let transactions = format!("[{},{}]", captured(1, 0), captured(2, 60));
+        let before = TempCapture::new(
+            "before.json",
+            &format!(
+                r#"{{{},"transactions":{}}}"#,
+                metadata_fields(),
+                transactions
+            ),
+        );
+        let after = TempCapture::new(
+            "after.json",
+            &format!(
+                r#"{{"transactions":{},{}}}"#,
+                transactions,
+                metadata_fields()
+            ),
+        );
+
+        for capture in [before, after] {
+            let mut reader = CaptureReader::open(&capture.0, CaptureFormat::Json).unwrap();
+            assert_eq!(read_slots(&mut reader), vec![1, 2]);
+            assert_eq!(reader.finish().unwrap(), declared_metadata());
+        }
+    }
+
+    #[test]
+    fn malformed_envelope_elements_are_errors() {
+        let capture = TempCapture::new(
+            "malformed.json",
+            &format!(
+                r#"{{"transactions":[{},{{"bogus":1}},{}],{}}}"#,
+                captured(1, 0),
+                captured(2, 60),
+                metadata_fields()
+            ),
+        );
+
+        let mut reader = CaptureReader::open(&capture.0, CaptureFormat::Json).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap().slot, 1);
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+        assert!(reader.finish().is_err());
+    }
+
+    #[test]
+    fn jsonl_metadata_comes_from_the_transactions() {
+        let capture = TempCapture::new(
+            "capture.jsonl",
+            &format!(
+                "{}\n\n{}\n{}\n",
+                captured(1, 30),
+                captured(2, 90),
+                captured(3, 0)
+            ),
+        );
+
+        let mut reader = CaptureReader::open(&capture.0, CaptureFormat::Jsonl).unwrap();
+        assert_eq!(read_slots(&mut reader), vec![1, 2, 3]);
+        assert_eq!(
+            reader.finish().unwrap(),
+            CaptureMetadata {
+                start_time: Some(time(0)),
+                end_time: Some(time(90)),
+                duration_seconds: 90,
+                transaction_count: 3,
+            }
+        );
+
+        let empty = TempCapture::new("empty.jsonl", "");
+        let reader = CaptureReader::open(&empty.0, CaptureFormat::Jsonl).unwrap();
+        assert_eq!(
+            reader.finish().unwrap(),
+            CaptureMetadata {
+                start_time: None,
+                end_time: None,
+                duration_seconds: 0,
+                transaction_count: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_jsonl_lines_are_skipped_with_an_error() {
+        let capture = TempCapture::new(
+            "malformed.jsonl",
+            &format!(
+                "{}\n\n{{\"bogus\":1}}\n{}\n",
+                captured(1, 0),
+                captured(2, 60)
+            ),
+        );
+
+        let mut reader = CaptureReader::open(&capture.0, CaptureFormat::Jsonl).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap().slot, 1);
+        let error = reader.next().unwrap().unwrap_err();
+        assert!(format!("{:#}", error).contains("line 3"));
+
+        // Lines are independent, so reading resumes after a bad one
+        assert_eq!(read_slots(&mut reader), vec![2]);
+        assert_eq!(reader.finish().unwrap().transaction_count, 2);
+    }
+}
@@ -1,13 +1,28 @@
-use anyhow::Result;
-use chrono::{DateTime, Utc};
-use clap::Parser;
-use serde::{Deserialize, Serialize};
-use std::time::Instant;
+mod capture;
+
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{info, warn};
 
-use indexer_core::parsing::simple_parser::parse_transaction_simple;
-use indexer_core::redis::SmartAccountRedisClient;
+use indexer_core::parsing::pipeline::ParsePipeline;
+use indexer_core::parsing::registry::{ParserRegistry, ParserStats};
+use indexer_core::redis::latency::parse_duration;
+use indexer_core::redis::population::load_pubkeys;
+use indexer_core::redis::{
+    LatencyModel, LookupPolicy, OwnerSetReplica, PolicyRelevanceStore, PopulationConfig,
+    RelevanceStore, SimulationConfig, SmartAccountRedisClient,
+};
 use indexer_core::transaction::transaction::UnifiedTransaction;
+use indexer_core::transaction::yellowstone::YellowstoneCaptureReader;
+use indexer_core::transaction::Cluster;
+
+use capture::{CaptureFormat, CaptureMetadata, CaptureReader};
 
 #[derive(Parser, Debug)]
 #[command(name = "parsing-exercise")]
@@ -17,22 +32,145 @@ struct Args {
     #[arg(long, default_value = "mainnet_transactions.json")]
     input_file: String,
 
+    /// Format of the input file
+    #[arg(long, value_enum, default_value_t = InputFormat::Json)]
+    input_format: InputFormat,
+
+    /// Cluster the capture was taken on; relevance data is kept per cluster
+    #[arg(long, default_value = "mainnet")]
+    cluster: Cluster,
+
+    /// Transactions prepared and looked up ahead of the one being committed;
+    /// 1 parses them one at a time
+    #[arg(long, default_value_t = 1)]
+    pipeline_depth: usize,
+
+    /// Answer owner checks from an in-process replica of the owner set
+    /// instead of a Redis round trip
+    #[arg(long)]
+    owner_replica: bool,
+
+    /// Redis round trip latency: fixed:<d>, normal:<mean>,<std_dev> or
+    /// percentiles:p50=<d>,p99=<d>,...
+    #[arg(long, default_value = "fixed:500us")]
+    redis_latency: LatencyModel,
+
+    /// Extra Redis latency per key in a request
+    #[arg(long, default_value = "0us", value_parser = parse_duration)]
+    redis_per_key_cost: Duration,
+
+    /// Probability that a Redis call fails
+    #[arg(long, default_value_t = 0.0)]
+    redis_error_rate: f64,
+
+    /// Probability that a Redis call loses its connection
+    #[arg(long, default_value_t = 0.0)]
+    redis_drop_rate: f64,
+
+    /// Redis calls slower than this fail with a timeout
+    #[arg(long, value_parser = parse_duration)]
+    redis_timeout: Option<Duration>,
+
+    /// Seed for reproducible latency and failure sampling
+    #[arg(long)]
+    redis_seed: Option<u64>,
+
+    /// What to do when a relevance lookup fails
+    #[arg(long, value_enum, default_value_t = LookupPolicyKind::Fail)]
+    lookup_policy: LookupPolicyKind,
+
+    /// Attempts per lookup under the retry policy
+    #[arg(long, default_value_t = 3)]
+    lookup_max_attempts: u32,
+
+    /// Backoff before the first retry, doubled on each further retry
+    #[arg(long, default_value = "1ms", value_parser = parse_duration)]
+    lookup_backoff: Duration,
+
+    /// Upper bound on the retry backoff
+    #[arg(long, default_value = "50ms", value_parser = parse_duration)]
+    lookup_max_backoff: Duration,
+
+    /// Relevant owners to load (JSON array, CSV or one base58 key per line)
+    #[arg(long)]
+    owners_file: Option<PathBuf>,
+
+    /// Relevant vault token accounts to load, in the same formats
+    #[arg(long)]
+    vaults_file: Option<PathBuf>,
+
+    /// Fraction of the owners seen in the capture to mark relevant
+    #[arg(long, default_value_t = 0.0)]
+    capture_hit_ratio: f64,
+
+    /// Owner set size, topped up with random filler
+    #[arg(long, default_value_t = 125_000)]
+    owner_count: usize,
+
+    /// Vault set size, topped up with random filler
+    #[arg(long, default_value_t = 25_000)]
+    vault_count: usize,
+
+    /// Seed for the capture sample and the filler
+    #[arg(long, default_value_t = 0)]
+    population_seed: u64,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum InputFormat {
+    /// The capture tool's JSON document
+    Json,
+    /// One captured transaction per line
+    Jsonl,
+    /// Length-delimited Yellowstone gRPC `SubscribeUpdateTransaction` messages
+    YellowstoneGrpc,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LookupPolicyKind {
+    /// Fail the transaction
+    Fail,
+    /// Retry with exponential backoff, then fail the transaction
+    Retry,
+    /// Answer from a snapshot of the relevance data taken at startup
+    Snapshot,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct CapturedTransaction {
-    transaction: UnifiedTransaction,
-    captured_at: DateTime<Utc>,
-    slot: u64,
+/// The input file's transactions, read as they are parsed
+enum TransactionSource {
+    Capture(CaptureReader),
+    Yellowstone(YellowstoneCaptureReader<BufReader<File>>),
+}
+
+impl TransactionSource {
+    fn open(path: &str, format: InputFormat) -> Result<Self> {
+        Ok(match format {
+            InputFormat::Json => Self::Capture(CaptureReader::open(path, CaptureFormat::Json)?),
+            InputFormat::Jsonl => Self::Capture(CaptureReader::open(path, CaptureFormat::Jsonl)?),
+            InputFormat::YellowstoneGrpc => Self::Yellowstone(YellowstoneCaptureReader::new(
+                BufReader::new(File::open(path).with_context(|| format!("opening {}", path))?),
+            )),
+        })
+    }
+
+    /// Capture metadata; gRPC captures have none
+    fn finish(self) -> Result<Option<CaptureMetadata>> {
+        match self {
+            Self::Capture(reader) => reader.finish().map(Some),
+            Self::Yellowstone(_) => Ok(None),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct TransactionCapture {
-    start_time: DateTime<Utc>,
-    end_time: DateTime<Utc>,
-    duration_seconds: u64,
-    transaction_count: usize,
-    transactions: Vec<CapturedTransaction>,
+impl Iterator for TransactionSource {
+    type Item = Result<UnifiedTransaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Capture(reader) => Some(reader.next()?.map(|captured| captured.transaction)),
+            Self::Yellowstone(reader) => reader.next(),
+        }
+    }
 }
 
 #[tokio::main]
@@ -41,64 +179,210 @@ async fn main() -> Result<()> {
 
     let args = Args::parse();
 
-    info!("Loading transactions from: {}", args.input_file);
+    info!("Streaming transactions from: {}", args.input_file);
 
-    // Load captured transactions
-    let file_content = std::fs::read_to_string(&args.input_file)?;
-    let capture: TransactionCapture = serde_json::from_str(&file_content)?;
+    let network = args.cluster.to_network_id();
 
-    let transactions_to_process = capture.transactions.len();
+    // Initialize Redis emulator with realistic data
+    let mut redis_client = SmartAccountRedisClient::with_simulation(SimulationConfig {
+        latency: args.redis_latency.clone(),
+        per_key_cost: args.redis_per_key_cost,
+        error_rate: args.redis_error_rate,
+        drop_rate: args.redis_drop_rate,
+        timeout: args.redis_timeout,
+        seed: args.redis_seed,
+    });
+    // Sampling the capture's owners takes a pass over the capture of its own
+    let mut capture_owners = HashSet::new();
+    if args.capture_hit_ratio > 0.0 {
+        for transaction in TransactionSource::open(&args.input_file, args.input_format)? {
+            let meta = transaction?.meta;
+            capture_owners.extend(
+                meta.pre_token_balances
+                    .iter()
+                    .chain(&meta.post_token_balances)
+                    .map(|balance| balance.owner),
+            );
+        }
+        info!("Found {} owners in the capture", capture_owners.len());
+    }
+    let capture_owners = capture_owners.into_iter().collect();
+    redis_client.populate(&PopulationConfig {
+        network,
+        owner_count: args.owner_count,
+        vault_count: args.vault_count,
+        owners: args
+            .owners_file
+            .as_deref()
+            .map(load_pubkeys)
+            .transpose()?
+            .unwrap_or_default(),
+        vaults: args
+            .vaults_file
+            .as_deref()
+            .map(load_pubkeys)
+            .transpose()?
+            .unwrap_or_default(),
+        capture_owners,
+        capture_hit_ratio: args.capture_hit_ratio,
+        seed: args.population_seed,
+    });
 
-    info!("Loaded {} transactions", capture.transactions.len());
+    let lookup_policy = match args.lookup_policy {
+        LookupPolicyKind::Fail => LookupPolicy::Fail,
+        LookupPolicyKind::Retry => LookupPolicy::Retry {
+            max_attempts: args.lookup_max_attempts,
+            initial_backoff: args.lookup_backoff,
+            max_backoff: args.lookup_max_backoff,
+        },
+        LookupPolicyKind::Snapshot => {
+            LookupPolicy::FallbackToSnapshot(redis_client.snapshot().into())
+        }
+    };
 
-    // Initialize Redis emulator with realistic data
-    let mut redis_client = SmartAccountRedisClient::new();
-    redis_client.populate_with_realistic_data();
+    let relevance_store: Box<dyn RelevanceStore> = if args.owner_replica {
+        let replica = OwnerSetReplica::load(redis_client.clone());
+        info!("Using owner set replica at version {}", replica.version());
+        Box::new(replica)
+    } else {
+        Box::new(redis_client)
+    };
+    let relevance_store = PolicyRelevanceStore::new(relevance_store, lookup_policy);
+    let lookup_metrics = relevance_store.metrics();
 
-    info!("Starting token program parser benchmark...");
+    let registry = Arc::new(ParserRegistry::with_default_parsers());
+    let parser_names: Vec<&str> = registry.parsers().map(|parser| parser.name()).collect();
+    info!(
+        "Starting parser benchmark with parsers: {}",
+        parser_names.join(", ")
+    );
 
     let start_time = Instant::now();
     let mut successful_parses = 0;
     let mut failed_parses = 0;
+    let mut failed_lookups = 0;
+    let mut parser_stats = HashMap::new();
+    let mut shared_lookups = 0;
+    let mut shared_lookup_time = Duration::ZERO;
 
-    // Process transactions
-    for (i, captured_tx) in capture
-        .transactions
-        .iter()
-        .take(transactions_to_process)
-        .enumerate()
-    {
-        match parse_transaction_simple(
-            &redis_client,
-            1, // mainnet
-            captured_tx.transaction.clone(),
-        )
-        .await
-        {
-            Ok(Some(_queue_entry)) => {
-                successful_parses += 1;
-            }
-            Ok(None) => {
-                // Transaction not relevant, this is normal
+    // Process transactions, committing results in capture order
+    let pipeline = ParsePipeline::new(
+        registry.clone(),
+        Arc::new(relevance_store),
+        network,
+        args.pipeline_depth,
+    );
+    let mut source = TransactionSource::open(&args.input_file, args.input_format)?;
+    let mut read_error = None;
+    let transactions = source.by_ref().map_while(|transaction| match transaction {
+        Ok(transaction) => Some(transaction),
+        Err(e) => {
+            read_error = Some(e);
+            None
+        }
+    });
+    let pipeline_stats = pipeline
+        .run(transactions, |i, parsed| {
+            ParserStats::record(&mut parser_stats, &parsed.reports);
+            if let Some(elapsed) = parsed.lookup_elapsed {
+                shared_lookups += 1;
+                shared_lookup_time += elapsed;
             }
-            Err(e) => {
-                failed_parses += 1;
-                warn!("Failed to parse transaction {}: {:?}", i, e);
+
+            match parsed.result {
+                Ok(Some(_queue_entry)) => {
+                    successful_parses += 1;
+                }
+                Ok(None) => {
+                    // Transaction not relevant, this is normal
+                }
+                Err(e) => {
+                    failed_parses += 1;
+                    if e.is_relevance_lookup() {
+                        failed_lookups += 1;
+                    }
+                    warn!("Failed to parse transaction {}: {:?}", i, e);
+                }
             }
-        }
 
-        // Log progress every 1000 transactions
-        if (i + 1) % 1000 == 0 {
-            info!("Processed {} transactions", i + 1);
-        }
-    }
+            // Log progress every 1000 transactions
+            if (i + 1) % 1000 == 0 {
+                info!("Processed {} transactions", i + 1);
+            }
+        })
+        .await;
 
     //#### After parsing, the queue entry is populated with the token account changes and potentially enqueued and sent off to the consumer
     let duration = start_time.elapsed();
+    if let Some(e) = read_error {
+        return Err(e.context(format!(
+            "reading {} after {} transactions",
+            args.input_file, pipeline_stats.transactions
+        )));
+    }
+    let transactions_to_process = pipeline_stats.transactions as usize;
+    if let Some(metadata) = source.finish()? {
+        info!(
+            "Capture: {} transactions over {}s ({} to {})",
+            metadata.transaction_count,
+            metadata.duration_seconds,
+            metadata
+                .start_time
+                .map_or_else(|| "-".to_string(), |time| time.to_rfc3339()),
+            metadata
+                .end_time
+                .map_or_else(|| "-".to_string(), |time| time.to_rfc3339()),
+        );
+        if metadata.transaction_count != transactions_to_process {
+            warn!(
+                "Capture declares {} transactions but {} were read",
+                metadata.transaction_count, transactions_to_process
+            );
+        }
+    }
     let tps = transactions_to_process as f64 / duration.as_secs_f64();
 
     info!("=== BENCHMARK RESULTS ===");
     info!("Total transactions processed: {}", transactions_to_process);
+    info!("Relevant transactions: {}", successful_parses);
+    info!("Failed parses: {}", failed_parses);
+    info!("  of which relevance lookup failures: {}", failed_lookups);
+    let lookups = lookup_metrics.snapshot();
+    info!(
+        "Relevance lookups: {} calls, {} failed attempts, {} retries, {} snapshot fallbacks, {} errors",
+        lookups.calls,
+        lookups.failed_attempts,
+        lookups.retries,
+        lookups.snapshot_fallbacks,
+        lookups.errors
+    );
+    for name in &parser_names {
+        let Some(stats) = parser_stats.get(name) else {
+            continue;
+        };
+        info!(
+            "Parser {}: {} runs, {} relevant, {} errors ({} lookup), {:.3}ms avg",
+            name,
+            stats.runs,
+            stats.relevant,
+            stats.errors,
+            stats.relevance_lookup_errors,
+            stats.average_time().as_secs_f64() * 1000.0
+        );
+    }
+    info!(
+        "Pipeline depth {}: {} owner set revalidations, {} owner index revalidations",
+        args.pipeline_depth,
+        pipeline_stats.owner_set_revalidations,
+        pipeline_stats.owner_index_revalidations
+    );
+    if shared_lookups > 0 {
+        info!(
+            "Shared relevance lookups: {}, {:.3}ms avg",
+            shared_lookups,
+            shared_lookup_time.as_secs_f64() * 1000.0 / shared_lookups as f64
+        );
+    }
     info!("Total duration: {:.2}s", duration.as_secs_f64());
     info!("Transactions per second (TPS): {:.2}", tps);
     info!(